@@ -0,0 +1,70 @@
+use cairo_proto_build::{Config, ServerStub};
+use clap::{ArgGroup, Parser};
+use std::{io::Result, path::PathBuf};
+
+/// Generate the Cairo bindings of proto definitions, without Scarb.
+#[derive(Parser, Clone, Debug)]
+#[command(author, version)]
+#[command(group(ArgGroup::new("stubs").multiple(true).args(["server_stub", "python_stub"])))]
+struct Args {
+    /// Proto definitions to compile, can be repeated.
+    #[arg(long, required = true)]
+    proto: Vec<PathBuf>,
+
+    /// Additional directory to search for imported protos, the directories of the definitions
+    /// are always searched.
+    #[arg(long)]
+    include: Vec<PathBuf>,
+
+    /// Directory of the generated Cairo files.
+    #[arg(long)]
+    out_dir: PathBuf,
+
+    /// Name of the Cairo module generated for the definitions without a `package`.
+    #[arg(long)]
+    package_name: String,
+
+    /// Path of the generated lock file.
+    #[arg(long, default_value = "Oracle.lock")]
+    oracle_lock: PathBuf,
+
+    /// Also generate an axum server skeleton with one handler per oracle method in this file.
+    #[arg(long)]
+    server_stub: Option<PathBuf>,
+
+    /// Also generate a FastAPI server skeleton with Pydantic models of the messages in this file.
+    #[arg(long)]
+    python_stub: Option<PathBuf>,
+
+    /// Overwrite the server skeletons if they already exist.
+    #[arg(long, default_value_t = false, requires = "stubs")]
+    force: bool,
+}
+
+fn main() -> Result<()> {
+    let args: Args = Args::parse();
+
+    let mut includes = args
+        .proto
+        .iter()
+        .filter_map(|proto| proto.parent().map(PathBuf::from))
+        .collect::<Vec<_>>();
+    includes.extend(args.include);
+    includes.dedup();
+
+    Config::new()
+        .out_dir(args.out_dir)
+        .oracle_module(&args.package_name)
+        .oracle_lock(args.oracle_lock.clone())
+        .compile_protos(&args.proto, &includes)?;
+
+    if let Some(stub_output) = &args.server_stub {
+        ServerStub::Rust.write(stub_output, args.force, &args.oracle_lock)?;
+    }
+    if let Some(stub_output) = &args.python_stub {
+        ServerStub::Python.write(stub_output, args.force, &args.oracle_lock)?;
+    }
+
+    println!("Done");
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use cairo_oracle_hint_processor::{
+    load_configuration, load_sierra_program, run_1_batch, OracleConfig, RunResult,
+};
+use cairo_vm::types::layout_name::LayoutName;
+use camino::Utf8PathBuf;
+use clap::{Parser, ValueEnum};
+use scarb_metadata::{MetadataCommand, ScarbCommand};
+use scarb_ui::args::PackagesFilter;
+use scarb_utils::{load_servers_config, oracle_lock_path, resolve_layout, PathBase};
+use serde_json::{json, Value};
+
+// the arguments are read as by `scarb hints-run`, which has more uses for this module
+#[allow(dead_code)]
+#[path = "../scarb-hints-run/deserialization.rs"]
+mod deserialization;
+use deserialization::FeltEncoding;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// A table of the timings.
+    Text,
+    /// A single JSON object with the timings in milliseconds.
+    Json,
+}
+
+/// Run the main function of a package several times, and report the time spent in the VM and
+/// waiting on the oracles.
+#[derive(Parser, Clone, Debug)]
+#[command(author, version)]
+struct Args {
+    #[command(flatten)]
+    packages_filter: PackagesFilter,
+
+    /// Do not rebuild the package.
+    #[arg(long, default_value_t = false)]
+    no_build: bool,
+
+    /// Number of measured runs.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+
+    /// Number of runs made before the measured ones and left out of the report, so the
+    /// connections to the oracle servers are already open.
+    #[arg(long, default_value_t = 1)]
+    warmup: u32,
+
+    /// Layout of the VM, or else the `layout` of the `[tool.hints]` section, `all_cairo` by
+    /// default.
+    #[clap(long = "layout", value_parser=validate_layout)]
+    layout: Option<String>,
+
+    /// Configuration file for oracle servers.
+    #[arg(long)]
+    servers_config_file: Option<PathBuf>,
+
+    /// Oracle server used by every service, overrides servers.json, Oracle.lock and ORACLE_SERVER.
+    #[arg(long)]
+    oracle_server: Option<String>,
+
+    /// Oracle lock file path.
+    #[arg(long)]
+    oracle_lock: Option<PathBuf>,
+
+    /// Profile of the Oracle.lock whose service settings override the others, merged over the
+    /// `default` profile.
+    #[arg(long, value_name = "NAME")]
+    oracle_profile: Option<String>,
+
+    /// Send every oracle request to the server, even when an identical one was already answered
+    /// in the same run.
+    #[arg(long, default_value_t = false)]
+    no_oracle_cache: bool,
+
+    /// Function to execute, either its name or its path, such as `utils::compute`.
+    #[arg(long, default_value = "main")]
+    entrypoint: String,
+
+    /// Arguments of the Cairo function.
+    #[arg(long = "args")]
+    args: Option<String>,
+
+    /// Encoding of the felts of the arguments given as strings.
+    #[arg(long, value_enum, default_value_t = FeltEncoding::Auto)]
+    felt_input_encoding: FeltEncoding,
+
+    /// Gas available to the main function.
+    #[arg(long)]
+    available_gas: Option<usize>,
+
+    /// Format of the report.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+}
+
+fn validate_layout(value: &str) -> Result<String, String> {
+    match value {
+        "plain"
+        | "small"
+        | "dex"
+        | "recursive"
+        | "starknet"
+        | "starknet_with_keccak"
+        | "recursive_large_output"
+        | "recursive_with_poseidon"
+        | "all_cairo"
+        | "all_solidity"
+        | "dynamic" => Ok(value.to_string()),
+        _ => Err(format!("{value} is not a valid layout")),
+    }
+}
+
+fn str_into_layout(value: &str) -> LayoutName {
+    match value {
+        "plain" => LayoutName::plain,
+        "small" => LayoutName::small,
+        "dex" => LayoutName::dex,
+        "recursive" => LayoutName::recursive,
+        "starknet" => LayoutName::starknet,
+        "starknet_with_keccak" => LayoutName::starknet_with_keccak,
+        "recursive_large_output" => LayoutName::recursive_large_output,
+        "recursive_with_poseidon" => LayoutName::recursive_with_poseidon,
+        "all_solidity" => LayoutName::all_solidity,
+        "all_cairo" => LayoutName::all_cairo,
+        "dynamic" => LayoutName::dynamic,
+        _ => LayoutName::all_cairo,
+    }
+}
+
+/// Mean, median and 99th percentile of the durations of the measured runs.
+struct Summary {
+    mean: Duration,
+    p50: Duration,
+    p99: Duration,
+}
+
+impl Summary {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort_unstable();
+        let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+        Self {
+            mean,
+            p50: percentile(&durations, 50),
+            p99: percentile(&durations, 99),
+        }
+    }
+
+    fn json(&self) -> Value {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        json!({ "mean": ms(self.mean), "p50": ms(self.p50), "p99": ms(self.p99) })
+    }
+}
+
+/// The nearest-rank percentile of `sorted`, which is not empty.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+fn main() -> Result<()> {
+    let args: Args = Args::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let func_args = args
+        .args
+        .as_deref()
+        .map(|value| deserialization::func_args_from_str(value, args.felt_input_encoding))
+        .transpose()
+        .context("invalid --args")?
+        .unwrap_or_default();
+
+    let metadata = MetadataCommand::new().inherit_stderr().exec()?;
+    let package = args.packages_filter.match_one(&metadata)?;
+    let layout = resolve_layout(Some(&package), args.layout.clone(), validate_layout)
+        .map_err(anyhow::Error::msg)?;
+
+    if !args.no_build {
+        ScarbCommand::new()
+            .arg("build")
+            .arg("--package")
+            .arg(&package.name)
+            .run()?;
+    }
+    let profile = env::var("SCARB_PROFILE").unwrap_or("dev".into());
+    let sierra_path = Utf8PathBuf::from(env::var("SCARB_TARGET_DIR")?)
+        .join(&profile)
+        .join(format!("{}.sierra.json", package.name));
+    if !sierra_path.exists() {
+        bail!("package has not been compiled with the {profile} profile, file does not exist: {sierra_path}");
+    }
+    let sierra_program = load_sierra_program(sierra_path.as_std_path())?;
+
+    let (lock_path, _) =
+        oracle_lock_path(Some(&package), args.oracle_lock.clone(), PathBase::Package)?;
+    let mut configuration = load_configuration(&lock_path)?;
+    configuration
+        .apply_profile(args.oracle_profile.as_deref())
+        .map_err(anyhow::Error::msg)?;
+
+    configuration.servers_config = load_servers_config(
+        Some(&package),
+        args.servers_config_file.clone(),
+        PathBase::Package,
+    )?;
+
+    // the requests and responses printed by each call would be measured along with them
+    let oracle_config = OracleConfig {
+        server: args.oracle_server.clone(),
+        fallback_server: env::var("ORACLE_SERVER").ok(),
+        no_cache: args.no_oracle_cache,
+        quiet: true,
+        ..OracleConfig::default()
+    };
+    // the runs of a batch share the compiled program and the connections to the servers
+    let batch = vec![func_args; (args.warmup + args.runs) as usize];
+    let results = run_1_batch(
+        &configuration,
+        &oracle_config,
+        &str_into_layout(&layout),
+        &batch,
+        args.available_gas,
+        &sierra_program,
+        &format!("::{}", args.entrypoint),
+        false,
+    )?;
+    let results = results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| result.with_context(|| format!("run {} failed", i + 1)))
+        .collect::<Result<Vec<RunResult>>>()?;
+    let measured = &results[args.warmup as usize..];
+
+    let summary = |duration: fn(&RunResult) -> Duration| {
+        Summary::new(measured.iter().map(duration).collect())
+    };
+    let total = summary(|result| result.timings.total);
+    let vm = summary(|result| result.timings.vm);
+    let oracle = summary(|result| result.timings.oracle);
+    let per_run = |count: fn(&RunResult) -> usize| {
+        measured.iter().map(count).sum::<usize>() as f64 / measured.len() as f64
+    };
+    let calls = per_run(|result| result.stats.oracle_calls);
+    let cache_hits = per_run(|result| result.stats.cache_hits);
+
+    match args.output_format {
+        OutputFormat::Text => {
+            println!(
+                "{} over {} runs, after {} warm-up runs",
+                args.entrypoint, args.runs, args.warmup
+            );
+            println!("{:<8} {:>12} {:>12} {:>12}", "", "mean", "p50", "p99");
+            for (name, summary) in [("total", &total), ("vm", &vm), ("oracle", &oracle)] {
+                let [mean, p50, p99] =
+                    [summary.mean, summary.p50, summary.p99].map(|d| format!("{d:.3?}"));
+                println!("{name:<8} {mean:>12} {p50:>12} {p99:>12}");
+            }
+            println!("Oracle calls per run: {calls:.1} ({cache_hits:.1} from the cache)");
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "runs": args.runs,
+                "warmup": args.warmup,
+                "total_ms": total.json(),
+                "vm_ms": vm.json(),
+                "oracle_ms": oracle.json(),
+                "oracle_calls_per_run": calls,
+                "cache_hits_per_run": cache_hits,
+            })
+        ),
+    }
+    Ok(())
+}
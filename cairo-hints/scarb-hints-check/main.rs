@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use cairo_proto_build::Config;
+use cairo_proto_serde::configuration::Configuration;
+use cairo_proto_serde::schema::schema_hash;
+use clap::Parser;
+use scarb_metadata::MetadataCommand;
+use scarb_ui::args::PackagesFilter;
+use scarb_utils::{absolute_path, oracle_lock_path, proto_paths, PathBase};
+
+/// Check that the generated Cairo bindings and Oracle.lock match the proto definitions.
+#[derive(Parser, Clone, Debug)]
+#[command(author, version)]
+struct Args {
+    /// Name of the package.
+    #[command(flatten)]
+    packages_filter: PackagesFilter,
+
+    #[arg(long)]
+    definitions: Option<PathBuf>,
+
+    /// Directory searched for the imported protos, in addition to the directory of the
+    /// definitions. Can be repeated.
+    #[arg(long = "proto-path")]
+    proto_paths: Vec<PathBuf>,
+
+    #[arg(long)]
+    cairo_output: Option<PathBuf>,
+
+    #[arg(long)]
+    oracle_module: Option<String>,
+
+    #[arg(long)]
+    oracle_lock: Option<PathBuf>,
+
+    /// Print the schema hash of every service of the proto definitions, to pin in the
+    /// `service_options` of the Oracle.lock, instead of checking the files.
+    #[arg(long, default_value_t = false)]
+    print_schema_hash: bool,
+}
+
+fn main() -> Result<ExitCode> {
+    let args: Args = Args::parse();
+    let metadata = MetadataCommand::new().inherit_stderr().exec()?;
+    let package = args.packages_filter.match_one(&metadata)?;
+
+    let definitions = absolute_path(&package, args.definitions, "definitions", None)
+        .context("oracle.proto definitions path must be provided either as an argument (--definitions proto/oracle.proto) or in the Scarb.toml file in [tool.hints] section.")?;
+
+    let includes = proto_paths(&package, args.proto_paths, &definitions);
+
+    let cairo_output: PathBuf = absolute_path(
+        &package,
+        args.cairo_output,
+        "cairo_output",
+        Some(PathBuf::from("src")),
+    )
+    .expect("the cairo output has a default");
+
+    let oracle_module = args
+        .oracle_module
+        .or_else(|| {
+            package
+                .tool_metadata("hints")
+                .and_then(|tool_config| tool_config["oracle_module"].as_str().map(String::from))
+        })
+        .unwrap_or("lib.cairo".to_string());
+
+    let (lock_output, _) = oracle_lock_path(Some(&package), args.oracle_lock, PathBase::Package)?;
+
+    // Generate everything in a scratch directory, then compare it with the checked in files
+    let generated = tempfile::tempdir()?;
+    let generated_lock = generated.path().join("Oracle.lock");
+    Config::new()
+        .out_dir(generated.path())
+        .oracle_module(&oracle_module)
+        .oracle_lock(&generated_lock)
+        .compile_protos(&[&definitions], &includes)?;
+
+    if args.print_schema_hash {
+        let proto: Configuration = serde_json::from_slice(&fs::read(&generated_lock)?)?;
+        for service in proto.services.keys() {
+            println!("{service} {}", schema_hash(&proto, service).unwrap());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut mismatches = 0;
+    let mut generated_files = fs::read_dir(generated.path())?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    generated_files.sort();
+    for path in generated_files
+        .iter()
+        .filter(|path| **path != generated_lock)
+    {
+        let checked_in = cairo_output.join(path.file_name().unwrap());
+        let expected = fs::read_to_string(path)?;
+        match fs::read_to_string(&checked_in) {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => {
+                mismatches += 1;
+                println!("--- {} (checked in)", checked_in.display());
+                println!("+++ {} (generated)", checked_in.display());
+                print_diff(&actual, &expected);
+            }
+            Err(_) => {
+                mismatches += 1;
+                println!("{} is missing", checked_in.display());
+            }
+        }
+    }
+
+    let proto: Configuration = serde_json::from_slice(&fs::read(&generated_lock)?)?;
+    match fs::read(&lock_output) {
+        Ok(lock) => {
+            let lock: Configuration = serde_json::from_slice(&lock)?;
+            let differences = [
+                compare("enum", &lock.enums, &proto.enums),
+                compare("message", &lock.messages, &proto.messages),
+                compare("service", &lock.services, &proto.services),
+            ]
+            .concat();
+            if !differences.is_empty() {
+                mismatches += 1;
+                println!("--- {} (checked in)", lock_output.display());
+                println!("+++ {} (generated)", lock_output.display());
+                for difference in differences {
+                    println!("{difference}");
+                }
+            }
+        }
+        Err(_) => {
+            mismatches += 1;
+            println!("{} is missing", lock_output.display());
+        }
+    }
+
+    if mismatches == 0 {
+        println!("Cairo bindings and Oracle.lock are up to date");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("{mismatches} file(s) out of date, run `scarb hints-generate` to update them");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Lists the entries of the lock that are missing from, or differ with, the proto definitions.
+fn compare<T: PartialEq>(
+    kind: &str,
+    lock: &BTreeMap<String, T>,
+    proto: &BTreeMap<String, T>,
+) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (name, value) in lock {
+        match proto.get(name) {
+            None => differences.push(format!("- {kind} {name} has no proto definition")),
+            Some(definition) if definition != value => {
+                differences.push(format!("~ {kind} {name} differs from its proto definition"))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in proto.keys().filter(|name| !lock.contains_key(*name)) {
+        differences.push(format!("+ {kind} {name} is missing from the lock"));
+    }
+    differences
+}
+
+/// Prints the lines removed from `old` and added in `new`, with their line numbers.
+fn print_diff(old: &str, new: &str) {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // lengths of the longest common subsequences of the suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            println!("-{:>5} {}", i + 1, old[i]);
+            i += 1;
+        } else {
+            println!("+{:>5} {}", j + 1, new[j]);
+            j += 1;
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use std::{io::Result, path::PathBuf};
-use cairo_proto_build::Config;
-use clap::Parser;
+use cairo_proto_build::{Config, ServerStub};
+use clap::{ArgGroup, Parser};
 use scarb_metadata::MetadataCommand;
 use scarb_ui::args::PackagesFilter;
-use scarb_utils::absolute_path;
+use scarb_utils::{absolute_path, proto_paths};
 
 /// Execute the main function of a package.
 #[derive(Parser, Clone, Debug)]
 #[command(author, version)]
+#[command(group(ArgGroup::new("stubs").multiple(true).args(["server_stub", "python_stub"])))]
 struct Args {
     /// Name of the package.
     #[command(flatten)]
@@ -16,6 +17,11 @@ struct Args {
     #[arg(long)]
     definitions: Option<PathBuf>,
 
+    /// Directory searched for the imported protos, in addition to the directory of the
+    /// definitions. Can be repeated.
+    #[arg(long = "proto-path")]
+    proto_paths: Vec<PathBuf>,
+
     #[clap(long)]
     cairo_output: Option<PathBuf>,
 
@@ -24,6 +30,26 @@ struct Args {
 
     #[structopt(long)]
     oracle_lock: Option<PathBuf>,
+
+    /// Also generate an axum server skeleton with one handler per oracle method.
+    #[arg(long, default_value_t = false)]
+    server_stub: bool,
+
+    /// File of the generated server skeleton, relative to the package directory.
+    #[arg(long)]
+    server_stub_output: Option<PathBuf>,
+
+    /// Also generate a FastAPI server skeleton with Pydantic models of the messages.
+    #[arg(long, default_value_t = false)]
+    python_stub: bool,
+
+    /// File of the generated Python server skeleton, relative to the package directory.
+    #[arg(long)]
+    python_stub_output: Option<PathBuf>,
+
+    /// Overwrite the server skeletons if they already exist.
+    #[arg(long, default_value_t = false, requires = "stubs")]
+    force: bool,
 }
 
 fn main() -> Result<()> {
@@ -34,7 +60,7 @@ fn main() -> Result<()> {
     let definitions = absolute_path(&package, args.definitions, "definitions", None)
         .expect("oracle.proto definitions path must be provided either as an argument (--definitions proto/oracle.proto) or in the Scarb.toml file in [tool.hints] section.");
 
-    let includes = definitions.parent().unwrap();
+    let includes = proto_paths(&package, args.proto_paths, &definitions);
 
     let cairo_output: PathBuf = absolute_path(&package, args.cairo_output, "cairo_output", Some(PathBuf::from("src")))
         .expect("cairo output path must be provided either as an argument (--cairo-output src) or in the Scarb.toml file in the [tool.hints] section.");
@@ -51,12 +77,24 @@ fn main() -> Result<()> {
     Config::new()
         .out_dir(cairo_output)
         .oracle_module(&oracle_module)
-        .oracle_lock(lock_output)
+        .oracle_lock(lock_output.clone())
         .compile_protos(
             &[&definitions], 
-            &[includes]
+            &includes
         )?;
 
+    if args.server_stub {
+        let stub_output = absolute_path(&package, args.server_stub_output, "server_stub", Some(PathBuf::from("rust/src/server.rs")))
+            .expect("server stub path must be provided either as an argument (--server-stub-output rust/src/server.rs) or in the Scarb.toml file in the [tool.hints] section.");
+        ServerStub::Rust.write(&stub_output, args.force, &lock_output)?;
+    }
+
+    if args.python_stub {
+        let stub_output = absolute_path(&package, args.python_stub_output, "python_stub", Some(PathBuf::from("python/server.py")))
+            .expect("python stub path must be provided either as an argument (--python-stub-output python/server.py) or in the Scarb.toml file in the [tool.hints] section.");
+        ServerStub::Python.write(&stub_output, args.force, &lock_output)?;
+    }
+
     println!("Done");
     Ok(())
 }
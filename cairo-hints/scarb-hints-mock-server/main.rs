@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use cairo_oracle_hint_processor::dry_run::zeroed_response;
+use cairo_proto_serde::configuration::{Configuration, MethodDeclaration};
+use clap::Parser;
+use scarb_metadata::MetadataCommand;
+use scarb_ui::args::PackagesFilter;
+use scarb_utils::{absolute_path, oracle_lock_path, PathBase};
+use serde_json::{json, Value};
+
+/// Serve canned oracle responses for every method of the Oracle.lock.
+#[derive(Parser, Clone, Debug)]
+#[command(author, version)]
+struct Args {
+    /// Name of the package.
+    #[command(flatten)]
+    packages_filter: PackagesFilter,
+
+    /// Oracle lock file path.
+    #[arg(long)]
+    oracle_lock: Option<PathBuf>,
+
+    /// JSON file mapping method names to their response, relative to the package directory.
+    #[arg(long)]
+    fixtures: Option<PathBuf>,
+
+    /// Address the server listens on.
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Port the server listens on, 0 picks a free one.
+    #[arg(long, default_value_t = 3000)]
+    port: u16,
+}
+
+fn main() -> Result<()> {
+    let args: Args = Args::parse();
+    let metadata = MetadataCommand::new().inherit_stderr().exec()?;
+    let package = args.packages_filter.match_one(&metadata)?;
+
+    let (lock_path, _) = oracle_lock_path(Some(&package), args.oracle_lock, PathBase::Package)?;
+    let configuration: Configuration = serde_json::from_slice(&fs::read(&lock_path)?)?;
+    let methods = configuration
+        .services
+        .values()
+        .flat_map(|service| service.methods.iter())
+        .collect::<HashMap<_, _>>();
+
+    let fixtures: HashMap<String, Value> =
+        match absolute_path(&package, args.fixtures, "mock_fixtures", None) {
+            Some(path) => serde_json::from_slice(&fs::read(&path)?)?,
+            None => HashMap::new(),
+        };
+    for selector in fixtures
+        .keys()
+        .filter(|selector| !methods.contains_key(selector))
+    {
+        eprintln!(
+            "warning: the fixtures answer {selector}, which is not a method of the Oracle.lock"
+        );
+    }
+
+    let listener = TcpListener::bind((args.host.as_str(), args.port))?;
+    let address = listener.local_addr()?;
+    println!("Mock oracle server listening on http://{address}");
+    println!("Run the program with `scarb hints-run --oracle-server http://{address}`");
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        if let Err(e) = serve(stream, &configuration, &methods, &fixtures) {
+            eprintln!("error: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Answers a single `POST /<method>` request, then closes the connection.
+fn serve(
+    mut stream: TcpStream,
+    configuration: &Configuration,
+    methods: &HashMap<&String, &MethodDeclaration>,
+    fixtures: &HashMap<String, Value>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the method is the last segment, so the paths of a `path_template` such as `/v2/{method}`
+    // are answered too
+    let selector = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let request: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let (status, response) = match methods.get(&selector) {
+        Some(method) => {
+            let result = fixtures
+                .get(&selector)
+                .cloned()
+                .unwrap_or_else(|| zeroed_response(configuration, method, &request));
+            println!("{selector} {request} -> {result}");
+            ("200 OK", json!({ "result": result }))
+        }
+        None => {
+            println!("{selector} {request} -> unknown method");
+            (
+                "404 Not Found",
+                json!({ "error": format!("unknown method {selector}") }),
+            )
+        }
+    };
+
+    let response = response.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )?;
+    stream.flush()
+}
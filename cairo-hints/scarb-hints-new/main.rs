@@ -20,8 +20,11 @@ struct Args {
     path: Utf8PathBuf,
     #[clap(long = "name", value_parser)]
     name: Option<PackageName>,
-    #[clap(long = "lang", value_enum)]
-    lang: Lang,
+    #[clap(long = "lang", value_enum, required_unless_present = "minimal")]
+    lang: Option<Lang>,
+    /// Only generate the Cairo package, the proto and the lock, without the example server.
+    #[clap(long = "minimal", conflicts_with = "lang")]
+    minimal: bool,
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
@@ -50,7 +53,8 @@ pub struct NewArgs {
     pub path: Utf8PathBuf,
     #[command(flatten)]
     pub init: InitArgs,
-    pub lang: Lang,
+    /// Language of the example server, none with `--minimal`.
+    pub lang: Option<Lang>,
 }
 
 pub fn run(args: NewArgs, config: &Config) -> Result<()> {
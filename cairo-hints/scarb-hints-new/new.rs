@@ -22,7 +22,7 @@ pub struct InitOptions {
     pub path: Utf8PathBuf,
     pub name: Option<PackageName>,
     pub vcs: VersionControl,
-    pub lang: Lang,
+    pub lang: Option<Lang>,
 }
 
 #[derive(Debug)]
@@ -96,7 +96,7 @@ struct MkOpts {
     path: Utf8PathBuf,
     name: PackageName,
     version_control: VersionControl,
-    lang: Lang,
+    lang: Option<Lang>,
 }
 
 fn mk(
@@ -115,6 +115,15 @@ fn mk(
     init_vcs(&canonical_path, version_control)?;
     write_vcs_ignore(&canonical_path, config, version_control)?;
 
+    let start_server = match lang {
+        Some(Lang::Rust) => "Start the hints server: `cd rust; cargo run`",
+        Some(Lang::Js) => "Start the hints server: `cd js; npm install; npm start`",
+        Some(Lang::Ts) => "Start the hints server: `cd ts; npm install; npm start`",
+        None => {
+            "Start a server answering `POST /sqrt` on port 3000, \
+            `scarb hints-generate --server-stub` generates one"
+        }
+    };
     let filename = canonical_path.join("README.md");
     if !filename.exists() {
         fsx::create_dir_all(filename.parent().unwrap())?;
@@ -124,7 +133,7 @@ fn mk(
             formatdoc! {r#"
                 # Example Project
 
-                It calculates `sqrt` using an RPC server implemented in Rust.
+                It calculates `sqrt` using an RPC server.
 
                 ## Prerequisites
 
@@ -134,10 +143,7 @@ fn mk(
 
                 ## Usage
 
-                1. Start the hints server:
-                    1. Rust: `cd rust; cargo run`
-                    2. Javascript: `cd js; npm install; npm start`
-                    3. Typescript: `cd ts; npm install; npm start`
+                1. {start_server}
                 2. Run `scarb hints-run --layout all_cairo`
 
                 ## Extra options
@@ -173,9 +179,10 @@ fn mk(
     }
 
     match lang {
-        Lang::Rust => mk_rust(&canonical_path, &name, &config)?,
-        Lang::Js => mk_js(&canonical_path, &name, &config)?,
-        Lang::Ts => mk_ts(&canonical_path, &name, config)?,
+        Some(Lang::Rust) => mk_rust(&canonical_path, &name, &config)?,
+        Some(Lang::Js) => mk_js(&canonical_path, &name, &config)?,
+        Some(Lang::Ts) => mk_ts(&canonical_path, &name, config)?,
+        None => {}
     }
     mk_cairo(&canonical_path, &name, &config)?;
 
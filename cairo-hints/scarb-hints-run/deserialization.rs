@@ -1,7 +1,9 @@
-use std::{ops::Deref, str::FromStr};
+use std::{collections::HashMap, ops::Deref, str::FromStr};
 
 use cairo_felt::Felt252;
 use cairo_lang_runner::Arg;
+use cairo_oracle_hint_processor::{FuncArg, FuncArgs};
+use clap::ValueEnum;
 // use cairo_vm::Felt252;
 // use cairo_felt::Felt252;
 use serde::{de::Visitor, Deserialize};
@@ -18,6 +20,26 @@ pub enum ArgsError {
     NumberOutOfRange,
     #[error("failed to parse arguments: {0}")]
     ParseError(#[from] serde_json::Error),
+    #[error("invalid argument {0}, expected a number, a string or an array")]
+    InvalidArgument(Value),
+    #[error("invalid felt {0}, expected a decimal number with --felt-input-encoding dec")]
+    HexFeltNotAllowed(String),
+    #[error("{0}")]
+    Felt(String),
+    #[error("unbalanced brackets in the arguments {0:?}")]
+    UnbalancedBrackets(String),
+}
+
+/// Encoding of the felts given as strings in the arguments of the Cairo function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FeltEncoding {
+    /// Hexadecimal with a `0x` prefix, decimal otherwise.
+    #[default]
+    Auto,
+    /// Decimal only.
+    Dec,
+    /// Hexadecimal, with or without a `0x` prefix.
+    Hex,
 }
 
 /// `Args` is a wrapper around a vector of `Arg`.
@@ -155,3 +177,151 @@ impl<'de> Deserialize<'de> for Args {
         deserializer.deserialize_seq(Args(Vec::new()))
     }
 }
+
+/// Parses the arguments of the Cairo function given by `--args`: felts separated by spaces, and
+/// arrays of felts between brackets, such as `1 [2 3] 4`.
+pub fn func_args_from_str(s: &str, encoding: FeltEncoding) -> Result<FuncArgs, ArgsError> {
+    let unbalanced = || ArgsError::UnbalancedBrackets(s.to_string());
+    let mut args = Vec::new();
+    let mut array: Option<Vec<cairo_vm::Felt252>> = None;
+    for token in s.split_whitespace() {
+        let (opens, token) = match token.strip_prefix('[') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+        let (closes, token) = match token.strip_suffix(']') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+        if opens {
+            if array.is_some() {
+                return Err(unbalanced());
+            }
+            array = Some(Vec::new());
+        }
+        if !token.is_empty() {
+            let felt = felt_from_str(token, encoding)?;
+            match &mut array {
+                Some(values) => values.push(felt),
+                None => args.push(FuncArg::Single(felt)),
+            }
+        }
+        if closes {
+            args.push(FuncArg::Array(array.take().ok_or_else(unbalanced)?));
+        }
+    }
+    if array.is_some() {
+        return Err(unbalanced());
+    }
+    Ok(FuncArgs(args))
+}
+
+/// Parses the arguments of the Cairo function from the JSON format of `Args`, such as
+/// `[1, "2", [3, "0x4"]]`.
+pub fn func_args_from_json(s: &str, encoding: FeltEncoding) -> Result<FuncArgs, ArgsError> {
+    func_args_from_values(&serde_json::from_str::<Vec<Value>>(s)?, encoding)
+}
+
+/// Parses a JSON array of argument lists, each in the format of [`func_args_from_json`].
+pub fn batch_func_args_from_json(
+    s: &str,
+    encoding: FeltEncoding,
+) -> Result<Vec<FuncArgs>, ArgsError> {
+    serde_json::from_str::<Vec<Vec<Value>>>(s)?
+        .into_iter()
+        .map(|values| func_args_from_values(&values, encoding))
+        .collect()
+}
+
+fn func_args_from_values(values: &[Value], encoding: FeltEncoding) -> Result<FuncArgs, ArgsError> {
+    let felt = |value: &Value| felt_from_json(value, encoding);
+    let args = values
+        .iter()
+        .map(|value| match value {
+            Value::Array(values) => Ok(FuncArg::Array(
+                values.iter().map(felt).collect::<Result<_, _>>()?,
+            )),
+            value => felt(value).map(FuncArg::Single),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(FuncArgs(args))
+}
+
+fn felt_from_json(value: &Value, encoding: FeltEncoding) -> Result<cairo_vm::Felt252, ArgsError> {
+    match value {
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => Ok(n.into()),
+            (None, Some(n)) => Ok(-cairo_vm::Felt252::from(n.unsigned_abs())),
+            _ => Err(ArgsError::NumberOutOfRange),
+        },
+        Value::String(n) => felt_from_str(n, encoding),
+        value => Err(ArgsError::InvalidArgument(value.clone())),
+    }
+}
+
+/// Parses a felt of the arguments, in `[0, P)`: a value of P or more is rejected rather than
+/// reduced modulo P. A negative decimal, such as the argument of an `i32`, is the felt `P - n`.
+fn felt_from_str(s: &str, encoding: FeltEncoding) -> Result<cairo_vm::Felt252, ArgsError> {
+    if let Some(magnitude) = s
+        .strip_prefix('-')
+        .filter(|_| encoding != FeltEncoding::Hex)
+    {
+        return felt_from_str(magnitude, FeltEncoding::Dec).map(|felt| -felt);
+    }
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+    let felt = match (encoding, hex) {
+        (FeltEncoding::Dec, Some(_)) => return Err(ArgsError::HexFeltNotAllowed(s.to_string())),
+        (_, Some(digits)) => format!("0x{digits}"),
+        (FeltEncoding::Hex, None) => format!("0x{s}"),
+        (_, None) => s.to_string(),
+    };
+    cairo_proto_serde::felt_from_str(&felt).map_err(ArgsError::Felt)
+}
+
+/// Parses the return values expected by `--expect-output`: felts in decimal or `0x` hexadecimal,
+/// separated by commas or whitespace. Only what is between the first `[` and the last `]` is
+/// read when there are brackets, so the file can hold the printed `Return values : [...]` line
+/// or a JSON array of numbers or strings.
+pub fn expected_felts_from_str(s: &str) -> Result<Vec<cairo_vm::Felt252>, ArgsError> {
+    let values = match (s.find('['), s.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &s[start + 1..end],
+        _ => s,
+    };
+    values
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|value| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+        .map(|value| felt_from_str(value, FeltEncoding::Auto))
+        .collect()
+}
+
+/// Parses the file of `--panic-map`: a JSON object from panic felts, in decimal or `0x`
+/// hexadecimal, to the message printed next to them, such as `{"42": "InsufficientBalance"}`.
+pub fn panic_map_from_json(s: &str) -> Result<HashMap<cairo_vm::Felt252, String>, ArgsError> {
+    serde_json::from_str::<HashMap<String, String>>(s)?
+        .into_iter()
+        .map(|(felt, message)| Ok((felt_from_str(&felt, FeltEncoding::Auto)?, message)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{func_args_from_str, FeltEncoding};
+    use cairo_oracle_hint_processor::FuncArg;
+    use cairo_vm::Felt252;
+
+    #[test]
+    fn it_reads_negative_decimal_arguments() {
+        let args = func_args_from_str("-1 [-42 7]", FeltEncoding::Auto).unwrap();
+        let [FuncArg::Single(single), FuncArg::Array(array)] = &args.0[..] else {
+            panic!("unexpected arguments {:?}", args.0);
+        };
+        assert_eq!(*single, -Felt252::ONE);
+        assert_eq!(array, &[-Felt252::from(42), Felt252::from(7)]);
+        // the magnitude must still be less than P
+        let p = "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+        assert!(func_args_from_str(&format!("-{p}"), FeltEncoding::Auto).is_err());
+        assert!(func_args_from_str("-0x1", FeltEncoding::Auto).is_err());
+        assert!(func_args_from_str("-1", FeltEncoding::Hex).is_err());
+    }
+}
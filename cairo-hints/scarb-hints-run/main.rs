@@ -1,25 +1,101 @@
 use std::{
-    collections::HashMap,
-    env,
-    fs::{self, File},
-    io::BufReader,
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::{self, Write},
     path::PathBuf,
+    process::ExitCode,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
-use anyhow::{Context, Result};
-use cairo_lang_sierra::program::VersionedProgram;
-use cairo_oracle_hint_processor::{run_1, Error, FuncArg, FuncArgs};
-use cairo_proto_serde::configuration::Configuration;
+use cairo_lang_sierra::program::{GenericArg, Program, Statement};
+use cairo_oracle_hint_processor::dry_run::{zeroed_response, MockResponse};
+use cairo_oracle_hint_processor::{
+    load_configuration, load_sierra_program, run_1, run_1_batch, Error, LastOracleCall,
+    OracleCacheMode, OracleCompression, OracleConfig, PanicLocation, RunMetrics, RunResult,
+    DUMP_INPUT_FILE, DUMP_TRANSCRIPT_FILE,
+};
+use cairo_proto_serde::configuration::{Configuration, Encoding, FieldType};
+use cairo_proto_serde::msgpack::encode_value;
+use cairo_proto_serde::schema::{json_schema, type_name};
 use cairo_vm::types::layout_name::LayoutName;
+use cairo_vm::types::relocatable::MaybeRelocatable;
 use cairo_vm::Felt252;
 use camino::Utf8PathBuf;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
-use scarb_metadata::{MetadataCommand, ScarbCommand};
+use log::info;
+use scarb_metadata::{MetadataCommand, PackageMetadata, ScarbCommand};
 use scarb_ui::args::PackagesFilter;
-use scarb_utils::absolute_path;
+use scarb_utils::{
+    load_servers_config, oracle_lock_path, resolve_layout, resolve_path_or_cwd,
+    resolve_socket_path, stale_bindings, PathBase,
+};
+use serde_json::{json, Value};
 
 mod deserialization;
+use deserialization::FeltEncoding;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod watch;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Compression {
+    /// Uncompressed requests and responses.
+    None,
+    /// Gzip compressed requests and responses, when the server supports them.
+    Gzip,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CacheMode {
+    /// Answer the calls from the cache, without adding the new responses to it.
+    Read,
+    /// Send every call to the oracles and add their responses to the cache.
+    Write,
+    /// Answer the calls from the cache, add the responses of the others to it.
+    Readwrite,
+    /// Neither read nor write the cache.
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum WireEncoding {
+    /// JSON bodies.
+    Json,
+    /// MessagePack bodies, with the felts as 32 bytes binaries.
+    Msgpack,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line, with the fields of the record, such as the service, method,
+    /// latency and status of the oracle calls.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// Human readable return values.
+    Text,
+    /// A single JSON object with the return values or the panic data, and the oracle calls.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FeltFormat {
+    /// Decimal.
+    Dec,
+    /// Hexadecimal, without prefix.
+    Hex,
+    /// Hexadecimal, with a `0x` prefix.
+    #[value(name = "hex0x")]
+    Hex0x,
+    /// Decimal, the panic data followed by the short string it decodes to.
+    Auto,
+}
 
 /// Execute the main function of a package.
 #[derive(Parser, Clone, Debug)]
@@ -33,12 +109,43 @@ struct Args {
     #[arg(long, default_value_t = false)]
     no_build: bool,
 
-    #[clap(long = "layout", default_value = "all_cairo", value_parser=validate_layout)]
-    layout: String,
+    /// Capture the output of `scarb build` and only print it when the build fails.
+    #[arg(long, default_value_t = false, conflicts_with = "no_build")]
+    build_quiet: bool,
+
+    /// Build and run again each time the Cairo sources, the protos, `Scarb.toml` or the Oracle.lock
+    /// of the package change, until interrupted.
+    #[arg(long, conflicts_with_all = ["no_build", "list_oracles"])]
+    watch: bool,
+
+    /// Run this `.sierra.json` program without Scarb: no package is read nor built, and the
+    /// relative paths are resolved against the current directory. Needs `--oracle-lock`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "oracle_lock",
+        conflicts_with_all = ["watch", "profile", "build_quiet"]
+    )]
+    sierra_file: Option<PathBuf>,
+
+    /// Profile the package is built with and its Sierra artifact read from, instead of the
+    /// `SCARB_PROFILE` environment variable.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Layout of the VM, or else the `layout` of the `[tool.hints]` section, `all_cairo` by
+    /// default.
+    #[clap(long = "layout", value_parser=validate_layout)]
+    layout: Option<String>,
 
     #[arg(long, default_value_t = false)]
     proof_mode: bool,
 
+    /// Directory the relative paths of `--oracle-lock`, `--servers-config-file`,
+    /// `--oracle-ca-cert`, `--input-file` and `--inputs-file` are resolved against.
+    #[arg(long, value_enum, default_value_t = PathBase::Package)]
+    paths_relative_to: PathBase,
+
     /// Configuration file for oracle servers.
     #[arg(long)]
     servers_config_file: Option<PathBuf>,
@@ -47,62 +154,550 @@ struct Args {
     #[arg(long)]
     oracle_lock: Option<PathBuf>,
 
+    /// Profile of the Oracle.lock whose service settings override the others, merged over the
+    /// `default` profile.
+    #[arg(long, value_name = "NAME")]
+    oracle_profile: Option<String>,
+
     #[arg(long)]
     trace_file: Option<PathBuf>,
 
     #[arg(long)]
     memory_file: Option<PathBuf>,
 
+    /// CASM of the program written by `--casm-output`, run instead of compiling its Sierra.
+    #[arg(long, conflicts_with = "casm_output")]
+    casm_file: Option<PathBuf>,
+
+    /// Write the CASM the Sierra program compiles to, to run it again with `--casm-file`.
+    #[arg(long)]
+    casm_output: Option<PathBuf>,
+
+    /// Oracle server used by every service, overrides servers.json, Oracle.lock and ORACLE_SERVER.
+    #[arg(long)]
+    oracle_server: Option<String>,
+
+    /// PEM certificate of the CA of HTTPS oracle servers, relative to the package directory.
+    #[arg(long)]
+    oracle_ca_cert: Option<PathBuf>,
+
+    /// Don't verify the TLS certificates of the oracle servers.
+    #[arg(long, default_value_t = false)]
+    oracle_insecure: bool,
+
+    /// Header added to every oracle request, as `name=value`. May be repeated.
+    #[arg(long = "oracle-header", value_parser = parse_header)]
+    oracle_headers: Vec<(String, String)>,
+
+    /// Timeout of each oracle request in milliseconds, overrides the `Oracle.lock` value.
+    #[arg(long)]
+    oracle_timeout_ms: Option<u64>,
+
+    /// Maximum number of idle connections kept open to each oracle host.
+    #[arg(long)]
+    oracle_pool_size: Option<usize>,
+
+    /// Size in bytes above which an oracle response is rejected, 16 MiB by default.
+    #[arg(long)]
+    oracle_max_response_bytes: Option<u64>,
+
+    /// Length in bytes the body of a failed oracle response is truncated to in the error, 1024 by
+    /// default.
+    #[arg(long)]
+    oracle_error_body_bytes: Option<usize>,
+
+    /// Consecutive transport failures of an oracle server after which its calls fail without
+    /// reaching it, for --oracle-breaker-cooldown-secs.
+    #[arg(long)]
+    oracle_breaker_threshold: Option<u32>,
+
+    /// How long the calls to a failing oracle server are short-circuited, 30s by default.
+    #[arg(long, requires = "oracle_breaker_threshold")]
+    oracle_breaker_cooldown_secs: Option<u64>,
+
+    /// Parse the oracle responses while they are received, instead of buffering them first.
+    #[arg(long, default_value_t = false)]
+    oracle_stream_responses: bool,
+
+    /// Number of HTTP redirects an oracle request follows, 5 by default, 0 to refuse them.
+    #[arg(long)]
+    oracle_max_redirects: Option<usize>,
+
+    /// Hosts the oracle requests may reach, as `host` or `host:port` separated by commas. May be
+    /// repeated. Overrides ORACLE_ALLOWED_HOSTS, any host is allowed without either.
+    #[arg(long, value_name = "HOST[:PORT]", value_delimiter = ',')]
+    oracle_allowed_hosts: Vec<String>,
+
+    /// Header carrying the trace id of the run and the span id of each oracle request,
+    /// `traceparent` by default.
+    #[arg(long, value_name = "NAME")]
+    oracle_trace_header: Option<String>,
+
+    /// Compression of the bodies exchanged with the HTTP oracle servers.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    oracle_compression: Compression,
+
+    /// Encoding of the bodies exchanged with the HTTP oracle servers, overriding the `encoding`
+    /// of their service in the Oracle.lock. Default is JSON.
+    #[arg(long, value_enum)]
+    oracle_encoding: Option<WireEncoding>,
+
+    /// Seed sent to the oracle servers in the `x-oracle-seed` header, for reproducible randomness.
+    #[arg(long)]
+    oracle_seed: Option<u64>,
+
+    /// Don't probe the `health_path` of the oracle services before the run.
+    #[arg(long, default_value_t = false)]
+    skip_health_check: bool,
+
+    /// Send every oracle request to the server, even when an identical one was already answered.
+    #[arg(long, default_value_t = false)]
+    no_oracle_cache: bool,
+
+    /// Cache the oracle responses to this directory across runs, by request and schema of the
+    /// service.
+    #[arg(long, conflicts_with = "no_oracle_cache")]
+    oracle_cache_dir: Option<PathBuf>,
+
+    /// How the runs use the `--oracle-cache-dir`.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = CacheMode::Readwrite,
+        requires = "oracle_cache_dir"
+    )]
+    oracle_cache_mode: CacheMode,
+
+    /// Fetch the responses of the `prefetch` requests of the Oracle.lock while the program
+    /// compiles.
+    #[arg(long, default_value_t = false)]
+    oracle_prefetch: bool,
+
+    /// Record every oracle request and response to this newline-delimited JSON file.
+    #[arg(long)]
+    oracle_transcript: Option<PathBuf>,
+
+    /// Serve the oracle responses from a transcript instead of the oracle servers.
+    #[arg(long, conflicts_with = "oracle_transcript")]
+    oracle_replay: Option<PathBuf>,
+
+    /// Write the arguments, oracle calls, registers and memory of the run to this directory if it
+    /// panics.
+    #[arg(long, value_name = "DIR")]
+    dump_on_panic: Option<PathBuf>,
+
+    /// Keep the felts the program gives to the `partial_output` cheatcode, and print them along
+    /// with the panic data if the run panics.
+    #[arg(long, default_value_t = false)]
+    capture_partial: bool,
+
+    /// JSON object from panic felts to the message printed next to them, such as
+    /// `{"42": "InsufficientBalance"}`.
+    #[arg(long, value_name = "PATH")]
+    panic_map: Option<PathBuf>,
+
+    /// Run again with the arguments and oracle responses of a panic dump.
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["args", "input_file", "inputs_file", "oracle_replay"]
+    )]
+    from_dump: Option<PathBuf>,
+
+    /// Answer the oracle calls with default responses and list them, without contacting the servers.
+    #[arg(long, default_value_t = false, conflicts_with = "oracle_replay")]
+    dry_run: bool,
+
+    /// Run with the responses of `--dry-run` up to the first call to this oracle method, given by
+    /// name or as `Service.method`, and print the body of its request.
+    #[arg(
+        long,
+        value_name = "METHOD",
+        conflicts_with_all = ["oracle_replay", "inputs_file", "expect_output"]
+    )]
+    sample_request: Option<String>,
+
+    /// Gas available to the main function, the gas used is printed at the end of the run.
+    #[arg(long)]
+    available_gas: Option<usize>,
+
+    /// Abort the run once it has lasted this many seconds.
+    #[arg(long, value_name = "SECONDS")]
+    time_limit_secs: Option<u64>,
+
+    /// Abort the run once the VM has executed this many steps.
+    #[arg(long, value_name = "N")]
+    max_steps: Option<usize>,
+
+    /// Log the size and latency of each oracle call, and the time spent in the oracles.
+    #[arg(long, short, default_value_t = false)]
+    verbose: bool,
+
+    /// Format of the logs written to stderr.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Print the steps executed and the oracle calls made to stderr every SECONDS, 5 by default.
+    #[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "5")]
+    progress: Option<u64>,
+
+    /// Print only the results of the run and the errors, not the oracle calls nor the files
+    /// written.
+    #[arg(long, short, default_value_t = false, conflicts_with_all = ["verbose", "progress"])]
+    quiet: bool,
+
+    /// Serve Prometheus metrics of the run on this address, such as `127.0.0.1:9100`.
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_addr: Option<String>,
+
+    /// Print the request and response of every oracle method instead of running, as a JSON
+    /// schema with `--output-format json`.
+    #[arg(long, default_value_t = false)]
+    list_oracles: bool,
+
+    /// Print the number of statements, functions and oracle calls of the Sierra program instead of
+    /// running it.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["list_oracles", "watch"])]
+    print_sierra_stats: bool,
+
+    /// Format of the return values or panic data printed at the end of the run.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Format of the felts of the return values and panic data.
+    #[arg(long, value_enum, default_value_t = FeltFormat::Auto)]
+    felt_format: FeltFormat,
+
+    /// Function to execute, either its name or its path, such as `utils::compute`.
+    #[arg(long, default_value = "main")]
+    entrypoint: String,
+
     /// Arguments of the Cairo function.
-    #[arg(long = "args", default_value = "", value_parser=process_args)]
-    args: FuncArgs,
-}
-
-pub fn process_args(value: &str) -> Result<FuncArgs, String> {
-    if value.is_empty() {
-        return Ok(FuncArgs::default());
-    }
-    let mut args = Vec::new();
-    let mut input = value.split(' ');
-    while let Some(value) = input.next() {
-        // First argument in an array
-        if value.starts_with('[') {
-            if value.ends_with(']') {
-                if value.len() == 2 {
-                    args.push(FuncArg::Array(Vec::new()));
-                } else {
-                    args.push(FuncArg::Array(vec![Felt252::from_dec_str(
-                        value.strip_prefix('[').unwrap().strip_suffix(']').unwrap(),
-                    )
-                    .unwrap()]));
+    #[arg(long = "args")]
+    args: Option<String>,
+
+    /// Encoding of the felts of the arguments given as strings: `auto` reads `0x` prefixed ones
+    /// as hexadecimal and the others as decimal.
+    #[arg(long, value_enum, default_value_t = FeltEncoding::Auto)]
+    felt_input_encoding: FeltEncoding,
+
+    /// JSON file with the arguments of the Cairo function, relative to the package directory.
+    /// Defaults to the `input_file` of the `[tool.hints]` section when no arguments are given.
+    #[arg(long, conflicts_with = "args")]
+    input_file: Option<PathBuf>,
+
+    /// File with the expected return values, in decimal or 0x hexadecimal; the run fails with a
+    /// diff when they differ.
+    #[arg(long, conflicts_with = "inputs_file")]
+    expect_output: Option<PathBuf>,
+
+    /// JSON file with an array of argument lists, the function is run once for each of them
+    /// and a JSON array of the results is printed.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "args",
+            "input_file",
+            "trace_file",
+            "memory_file",
+            "oracle_transcript",
+            "dump_on_panic",
+        ]
+    )]
+    inputs_file: Option<PathBuf>,
+}
+
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected `name=value`, got {value:?}"))
+}
+
+/// Tries to decode a panic felt as a short string.
+fn panic_string(felt: &Felt252) -> Option<String> {
+    String::from_utf8(felt.to_bytes_be().to_vec())
+        .ok()
+        .map(|msg| msg.trim_start_matches('\0').to_string())
+}
+
+/// Renders a felt of the return values or panic data, without the short string of `auto`.
+fn format_felt(felt: &Felt252, format: FeltFormat) -> String {
+    match format {
+        FeltFormat::Dec | FeltFormat::Auto => felt.to_string(),
+        FeltFormat::Hex => format!("{:x}", felt.to_biguint()),
+        FeltFormat::Hex0x => format!("{:#x}", felt.to_biguint()),
+    }
+}
+
+fn format_return_value(value: &MaybeRelocatable, format: FeltFormat) -> String {
+    match value {
+        MaybeRelocatable::Int(felt) => format_felt(felt, format),
+        MaybeRelocatable::RelocatableValue(relocatable) => relocatable.to_string(),
+    }
+}
+
+/// JSON output of a successful run.
+fn success_json(result: &RunResult, available_gas: Option<usize>, format: FeltFormat) -> Value {
+    let return_values = result
+        .return_values
+        .iter()
+        .map(|m| format_return_value(m, format));
+    let mut output = json!({
+        "status": "success",
+        "return_values": return_values.collect_vec(),
+        "oracle_calls": result.oracle_calls,
+        "stats": result.stats,
+    });
+    if let Some((available, remaining)) = available_gas.zip(result.remaining_gas) {
+        output["gas_used"] = json!(available - remaining);
+    }
+    output
+}
+
+/// JSON output of a run that panicked.
+fn panic_json(
+    panic_data: &[Felt252],
+    location: Option<&PanicLocation>,
+    last_oracle_call: Option<&LastOracleCall>,
+    partial_output: Option<&[Felt252]>,
+    panic_map: &HashMap<Felt252, String>,
+    format: FeltFormat,
+) -> Value {
+    let panic_felt = |m: &Felt252| {
+        let mut felt = json!({ "value": format_felt(m, format), "string": panic_string(m) });
+        if let Some(message) = panic_map.get(m) {
+            felt["message"] = json!(message);
+        }
+        felt
+    };
+    let mut output = json!({
+        "status": "panicked",
+        "panic_data": panic_data.iter().map(panic_felt).collect_vec(),
+    });
+    if let Some(location) = location {
+        output["location"] = json!({
+            "statement_idx": location.statement_idx,
+            "function": location.function,
+        });
+    }
+    if let Some(call) = last_oracle_call {
+        output["last_oracle_call"] = json!({
+            "service": call.service,
+            "method": call.method,
+            "ordinal": call.ordinal,
+            "steps_before_panic": call.steps_before_panic,
+        });
+    }
+    if let Some(partial_output) = partial_output {
+        output["partial_output"] = json!(partial_output
+            .iter()
+            .map(|m| format_felt(m, format))
+            .collect_vec());
+    }
+    output
+}
+
+/// Lists the differences between the expected and actual return values, empty when they match.
+/// A pointer never matches an expected felt.
+fn output_diff(
+    expected: &[Felt252],
+    actual: &[MaybeRelocatable],
+    format: FeltFormat,
+) -> Vec<String> {
+    let mut diff = expected
+        .iter()
+        .zip(actual)
+        .enumerate()
+        .filter(|(_, (expected, actual))| actual.get_int_ref() != Some(*expected))
+        .map(|(i, (expected, actual))| {
+            format!(
+                "[{i}]: expected {}, got {}",
+                format_felt(expected, format),
+                format_return_value(actual, format)
+            )
+        })
+        .collect_vec();
+    if expected.len() != actual.len() {
+        diff.push(format!(
+            "expected {} values, got {}",
+            expected.len(),
+            actual.len()
+        ));
+    }
+    diff
+}
+
+/// Returns the body the oracle server would receive for `request`, in the encoding of the
+/// service. gRPC services are given the JSON representation of the request.
+fn sample_request_body(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    service: &str,
+    method: &str,
+    request: &Value,
+) -> Result<Vec<u8>, Error> {
+    let encoding = oracle_config
+        .encoding
+        .or_else(|| configuration.service_options.get(service)?.encoding)
+        .unwrap_or_default();
+    match encoding {
+        Encoding::Json => Ok(request.to_string().into_bytes()),
+        Encoding::Msgpack => {
+            let input = &configuration.services[service].methods[method].input;
+            encode_value(configuration, input, request).map_err(Error::ConfigError)
+        }
+    }
+}
+
+/// Prints the size of the Sierra program and its oracle call sites, by method.
+fn print_sierra_stats(program: &Program, configuration: &Configuration, format: OutputFormat) {
+    // the oracle calls are invocations of the `cheatcode` libfuncs, whose argument is the selector
+    let selectors = program
+        .libfunc_declarations
+        .iter()
+        .filter(|declaration| declaration.long_id.generic_id.0 == "cheatcode")
+        .filter_map(
+            |declaration| match declaration.long_id.generic_args.first() {
+                Some(GenericArg::Value(selector)) => {
+                    let name = String::from_utf8(selector.to_bytes_be().1)
+                        .unwrap_or_else(|_| format!("{selector:#x}"));
+                    Some((&declaration.id, name))
                 }
-            } else {
-                let mut array_arg =
-                    vec![Felt252::from_dec_str(value.strip_prefix('[').unwrap()).unwrap()];
-                // Process following args in array
-                let mut array_end = false;
-                while !array_end {
-                    if let Some(value) = input.next() {
-                        // Last arg in array
-                        if value.ends_with(']') {
-                            array_arg.push(
-                                Felt252::from_dec_str(value.strip_suffix(']').unwrap()).unwrap(),
-                            );
-                            array_end = true;
-                        } else {
-                            array_arg.push(Felt252::from_dec_str(value).unwrap())
-                        }
-                    }
+                _ => None,
+            },
+        )
+        .collect::<HashMap<_, _>>();
+    let mut oracle_calls = BTreeMap::<&str, usize>::new();
+    for statement in &program.statements {
+        if let Statement::Invocation(invocation) = statement {
+            if let Some(selector) = selectors.get(&invocation.libfunc_id) {
+                *oracle_calls.entry(selector).or_default() += 1;
+            }
+        }
+    }
+    let service_of = |selector: &str| {
+        configuration
+            .services
+            .iter()
+            .find(|(_, methods)| methods.methods.contains_key(selector))
+            .map(|(service, _)| service.clone())
+    };
+
+    match format {
+        OutputFormat::Text => {
+            println!("Statements:      {}", program.statements.len());
+            println!("Functions:       {}", program.funcs.len());
+            println!("Types:           {}", program.type_declarations.len());
+            println!("Libfuncs:        {}", program.libfunc_declarations.len());
+            println!("Oracle calls:    {}", oracle_calls.values().sum::<usize>());
+            for (selector, calls) in &oracle_calls {
+                match service_of(selector) {
+                    Some(service) => println!("  {service}.{selector}: {calls}"),
+                    None => println!("  {selector}: {calls} (not declared in the Oracle.lock)"),
                 }
-                // Finalize array
-                args.push(FuncArg::Array(array_arg))
             }
-        } else {
-            // Single argument
-            args.push(FuncArg::Single(Felt252::from_dec_str(value).unwrap()))
         }
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "statements": program.statements.len(),
+                "functions": program.funcs.len(),
+                "types": program.type_declarations.len(),
+                "libfuncs": program.libfunc_declarations.len(),
+                "oracle_calls": oracle_calls
+                    .iter()
+                    .map(|(selector, calls)| json!({
+                        "service": service_of(selector),
+                        "method": selector,
+                        "calls": calls,
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        ),
+    }
+}
+
+/// Prints the request and response of each oracle method, then the fields of the messages and
+/// the values of the enums they use.
+fn print_oracles(configuration: &Configuration) {
+    let mut used = Vec::new();
+    for (service, methods) in &configuration.services {
+        for (selector, method) in methods
+            .methods
+            .iter()
+            .sorted_by_key(|(selector, _)| *selector)
+        {
+            println!("{service}.{selector}");
+            println!("  request:  {}", type_name(&method.input));
+            println!("  response: {}", type_name(&method.output));
+            used.extend([&method.input, &method.output]);
+        }
+    }
+
+    // the types reachable from the methods, each printed once
+    let mut printed = std::collections::HashSet::new();
+    while let Some(ty) = used.pop() {
+        match ty {
+            FieldType::Option(ty) | FieldType::Array(ty) => used.push(ty),
+            FieldType::Message(name) if printed.insert(name) => {
+                println!("\nmessage {name}");
+                for field in configuration.messages.get(name).into_iter().flatten() {
+                    println!("  {}: {}", field.name, type_name(&field.ty));
+                    used.push(&field.ty);
+                }
+            }
+            FieldType::Enum(name) if printed.insert(name) => {
+                println!("\nenum {name}");
+                for mapping in configuration.enums.get(name).into_iter().flatten() {
+                    println!("  {} = {}", mapping.name, mapping.nb);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Resolves the path of a file written by the run like the other paths, and creates its directory
+/// so the run doesn't fail once it is over.
+fn output_path(
+    package: Option<&PackageMetadata>,
+    arg: Option<PathBuf>,
+    base: PathBase,
+) -> Result<Option<PathBuf>, Error> {
+    let Some((path, _)) = resolve_path_or_cwd(package, arg, "", None, base) else {
+        return Ok(None);
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(Error::IO)?;
+    }
+    Ok(Some(path))
+}
+
+/// Adds the key-values of a log record to its JSON line, numbers and booleans keeping their type.
+struct JsonFields<'a>(&'a mut serde_json::Map<String, Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            json!(value)
+        } else if let Some(value) = value.to_u64() {
+            json!(value)
+        } else if let Some(value) = value.to_i64() {
+            json!(value)
+        } else if let Some(value) = value.to_f64() {
+            json!(value)
+        } else {
+            json!(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
     }
-    Ok(FuncArgs(args))
 }
 
 fn validate_layout(value: &str) -> Result<String, String> {
@@ -110,9 +705,11 @@ fn validate_layout(value: &str) -> Result<String, String> {
         "plain"
         | "small"
         | "dex"
+        | "recursive"
         | "starknet"
         | "starknet_with_keccak"
         | "recursive_large_output"
+        | "recursive_with_poseidon"
         | "all_cairo"
         | "all_solidity"
         | "dynamic" => Ok(value.to_string()),
@@ -137,94 +734,678 @@ fn str_into_layout(value: &str) -> LayoutName {
     }
 }
 
-fn main() -> Result<(), Error> {
-    let args: Args = Args::parse();
-    let metadata = MetadataCommand::new().inherit_stderr().exec().unwrap();
-    let package = args.packages_filter.match_one(&metadata).unwrap();
+/// Exit codes of the runner, so that scripts can tell the failures apart.
+const EXIT_FAILURE: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_PANIC: u8 = 3;
+const EXIT_ORACLE_FAILURE: u8 = 4;
+const EXIT_ORACLE_REJECTED: u8 = 5;
+const EXIT_TIME_LIMIT: u8 = 6;
+const EXIT_OUTPUT_MISMATCH: u8 = 7;
+const EXIT_INTERRUPTED: u8 = 130;
+
+fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::RunPanic { .. } => EXIT_PANIC,
+        Error::OracleRejected { .. } => EXIT_ORACLE_REJECTED,
+        Error::TimeLimitExceeded { .. } | Error::StepLimitExceeded { .. } => EXIT_TIME_LIMIT,
+        Error::OracleConnection { .. }
+        | Error::OracleTls { .. }
+        | Error::OracleRedirect { .. }
+        | Error::OracleUnavailable { .. }
+        | Error::OracleCircuitOpen { .. }
+        | Error::OracleHttpError { .. }
+        | Error::OracleEmptyResponse { .. }
+        | Error::OracleStreamEnded { .. }
+        | Error::Grpc(_)
+        | Error::OracleResponseInvalid { .. }
+        | Error::OracleSignatureInvalid { .. }
+        | Error::OracleResponseTooLarge { .. }
+        | Error::OracleProvider(_)
+        | Error::ReplayDivergence { .. } => EXIT_ORACLE_FAILURE,
+        Error::Cli(_)
+        | Error::IO(_)
+        | Error::FileNotFound(_)
+        | Error::LockParse { .. }
+        | Error::SierraParse { .. }
+        | Error::CasmParse { .. }
+        | Error::ConfigError(_)
+        | Error::ServersConfigFileError(_)
+        | Error::EntryPointNotFound(_)
+        | Error::IncompatibleEntryPoint { .. }
+        | Error::GasNotMetered { .. }
+        | Error::OracleHostNotAllowed { .. }
+        | Error::InvalidArgument { .. }
+        | Error::ArgumentsSizeMismatch { .. }
+        | Error::ArgumentUnaligned { .. }
+        | Error::UnknownOracleMethods(_)
+        | Error::SchemaMismatch { .. }
+        | Error::BindingsStale { .. }
+        | Error::LayoutMissingBuiltins { .. }
+        | Error::PackageNotSelected { .. }
+        | Error::PackageNotBuilt { .. }
+        | Error::Transcript(_) => EXIT_USAGE,
+        Error::EncodeTrace(_)
+        | Error::VirtualMachine(_)
+        | Error::Trace(_)
+        | Error::PublicInput(_)
+        | Error::Runner(_)
+        | Error::ProgramRegistry(_)
+        | Error::Compilation(_)
+        | Error::SierraCompilation(_)
+        | Error::Metadata(_)
+        | Error::Program(_)
+        | Error::Memory(_)
+        | Error::NoRetTypesInSignature
+        | Error::NoTypeSizeForId(_)
+        | Error::TypeIdNoDebugName(_)
+        | Error::NoInfoForType(_)
+        | Error::FailedToExtractReturnValues
+        | Error::ScarbMetadata(_)
+        | Error::Build(_)
+        | Error::SampleRequest { .. } => EXIT_FAILURE,
+    }
+}
+
+/// The package selected by `--package` in the Scarb workspace, failing with the members of the
+/// workspace when there is no single one.
+fn scarb_package(
+    packages_filter: &PackagesFilter,
+    build_quiet: bool,
+) -> Result<PackageMetadata, Error> {
+    let mut metadata_command = MetadataCommand::new();
+    if !build_quiet {
+        metadata_command.inherit_stderr();
+    }
+    let metadata = metadata_command
+        .exec()
+        .map_err(|e| Error::ScarbMetadata(e.to_string()))?;
+    // in a workspace the package must be selected, its own Oracle.lock and artifacts are used
+    packages_filter
+        .match_one(&metadata)
+        .map_err(|err| Error::PackageNotSelected {
+            reason: format!("{err:#}"),
+            members: metadata
+                .packages
+                .iter()
+                .filter(|p| metadata.workspace.members.contains(&p.id))
+                .map(|p| p.name.clone())
+                .collect(),
+        })
+}
+
+/// Builds the package with `profile` unless `no_build`, and returns the path of its Sierra
+/// artifact.
+fn build_package(
+    package: &PackageMetadata,
+    profile: Option<&str>,
+    no_build: bool,
+    build_quiet: bool,
+) -> Result<PathBuf, Error> {
+    let scarb_profile = match profile {
+        Some(profile) => profile.to_string(),
+        None => env::var("SCARB_PROFILE").unwrap_or("dev".into()),
+    };
 
-    if !args.no_build {
-        ScarbCommand::new().arg("build").run().unwrap();
+    if !no_build {
+        let mut build = ScarbCommand::new();
+        if let Some(profile) = profile {
+            build.arg("--profile").arg(profile);
+        }
+        build.arg("build").arg("--package").arg(&package.name);
+        if build_quiet {
+            let output = build.command().output()?;
+            if !output.status.success() {
+                io::stderr().write_all(&output.stdout)?;
+                io::stderr().write_all(&output.stderr)?;
+                return Err(Error::Build(output.status.to_string()));
+            }
+        } else {
+            build.run().map_err(|e| Error::Build(e.to_string()))?;
+        }
     }
 
     let filename = format!("{}.sierra.json", package.name);
-    let scarb_target_dir = env::var("SCARB_TARGET_DIR").unwrap();
-    let scarb_profile = env::var("SCARB_PROFILE").unwrap();
+    let scarb_target_dir = env::var("SCARB_TARGET_DIR").map_err(|_| {
+        Error::ScarbMetadata(
+            "SCARB_TARGET_DIR is not set, run the command through Scarb, as `scarb hints-run`"
+                .to_string(),
+        )
+    })?;
     let path = Utf8PathBuf::from(scarb_target_dir.clone())
         .join(scarb_profile.clone())
         .join(filename.clone());
 
-    path.try_exists()
-        .expect("package has not been compiled, file does not exist: {filename}");
-
-    let lock_output = absolute_path(&package, args.oracle_lock, "oracle_lock", Some(PathBuf::from("Oracle.lock")))
-        .expect("lock path must be provided either as an argument (--oracle-lock src) or in the Scarb.toml file in the [tool.hints] section.");
-    let lock_file = File::open(lock_output).map_err(|e| Error::IO(e))?;
-    let reader = BufReader::new(lock_file);
-    let mut service_configuration: Configuration =
-        serde_json::from_reader(reader).map_err(|e| Error::IO(e.into()))?;
-
-    // Get the servers config path using absolute_path
-    let servers_config_path = absolute_path(&package, None, "servers_config", Some(PathBuf::from("servers.json")))
-        .expect("servers config path must be provided either in the Scarb.toml file in the [tool.hints] section or default to servers.json in the project root.");
-
-    // Read and parse the servers config file
-    let config_content = fs::read_to_string(&servers_config_path).map_err(|e| Error::IO(e))?;
-    let servers_config: HashMap<String, String> = serde_json::from_str(&config_content)
-        .map_err(|e| Error::ServersConfigFileError(format!("Failed to parse servers config: {}", e)))?;
-
-    // Add the servers_config to the Configuration
-    service_configuration.servers_config = servers_config;
-
-    let sierra_program = serde_json::from_str::<VersionedProgram>(
-        &fs::read_to_string(path.clone())
-            .with_context(|| format!("failed to read Sierra file: {path}"))
-            .unwrap(),
-    )
-    .with_context(|| format!("failed to deserialize Sierra program: {path}"))
-    .unwrap()
-    .into_v1()
-    .with_context(|| format!("failed to load Sierra program: {path}"))
-    .unwrap();
-
-    let sierra_program = sierra_program.program;
-
-    match run_1(
+    if !path.exists() {
+        return Err(Error::PackageNotBuilt {
+            profile: scarb_profile,
+            path: path.into_std_path_buf(),
+        });
+    }
+
+    Ok(path.into_std_path_buf())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(Error::Cli(err)) => err.exit(),
+        // the panic data is already printed
+        Err(err @ Error::RunPanic { .. }) => ExitCode::from(exit_code(&err)),
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, Error> {
+    let args: Args = Args::parse();
+    let default_level = match (args.verbose, args.quiet) {
+        (true, _) => "info",
+        (_, true) => "error",
+        _ => "warn",
+    };
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level));
+    if args.log_format == LogFormat::Json {
+        logger.format(|buf, record| {
+            let mut line = serde_json::Map::new();
+            line.insert(
+                "timestamp".into(),
+                json!(buf.timestamp_millis().to_string()),
+            );
+            line.insert("level".into(), json!(record.level().as_str()));
+            line.insert("target".into(), json!(record.target()));
+            line.insert("message".into(), json!(record.args().to_string()));
+            let _ = record.key_values().visit(&mut JsonFields(&mut line));
+            writeln!(buf, "{}", Value::Object(line))
+        });
+    }
+    logger.init();
+    // a bad argument is reported before the package is built
+    let cli_args = args
+        .args
+        .as_deref()
+        .map(|value| deserialization::func_args_from_str(value, args.felt_input_encoding))
+        .transpose()
+        .map_err(|e| Error::ConfigError(format!("Invalid --args: {e}")))?;
+    // a Sierra file is run without Scarb, there is no package to read the paths from nor to build
+    let package = match args.sierra_file {
+        Some(_) => None,
+        None => Some(scarb_package(&args.packages_filter, args.build_quiet)?),
+    };
+    let package = package.as_ref();
+    let layout = resolve_layout(package, args.layout.clone(), validate_layout)
+        .map_err(Error::ConfigError)?;
+
+    let base = args.paths_relative_to;
+    let (lock_output, lock_source) = oracle_lock_path(package, args.oracle_lock, base)?;
+    info!(
+        "Using the oracle lock {}, given by {lock_source}",
+        lock_output.display()
+    );
+    let mut service_configuration = load_configuration(&lock_output)?;
+    service_configuration
+        .apply_profile(args.oracle_profile.as_deref())
+        .map_err(Error::ConfigError)?;
+
+    if args.list_oracles {
+        match args.output_format {
+            OutputFormat::Text => print_oracles(&service_configuration),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&json_schema(&service_configuration))
+                    .map_err(|e| Error::IO(e.into()))?
+            ),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(method) = &args.sample_request {
+        let declared = service_configuration
+            .services
+            .iter()
+            .any(|(service, methods)| {
+                methods
+                    .methods
+                    .keys()
+                    .any(|name| name == method || format!("{service}.{name}") == *method)
+            });
+        if !declared {
+            return Err(Error::ConfigError(format!(
+                "Unknown oracle method {method}, --list-oracles prints the declared ones"
+            )));
+        }
+    }
+
+    if args.watch {
+        let package = package.expect("--watch conflicts with --sierra-file");
+        return watch::watch(package, &lock_output);
+    }
+
+    // the program would otherwise fail decoding the responses of the changed messages
+    let stale = package.and_then(|package| stale_bindings(package).into_iter().next());
+    if let Some((bindings, protos)) = stale {
+        return Err(Error::BindingsStale { bindings, protos });
+    }
+
+    let sierra_path = match package {
+        Some(package) => build_package(
+            package,
+            args.profile.as_deref(),
+            args.no_build,
+            args.build_quiet,
+        )?,
+        None => {
+            let sierra_file = args.sierra_file.clone();
+            let (path, _) = resolve_path_or_cwd(None, sierra_file, "", None, base)
+                .expect("a run without package is given --sierra-file");
+            path
+        }
+    };
+
+    service_configuration.servers_config =
+        load_servers_config(package, args.servers_config_file, base)?;
+
+    let sierra_program = load_sierra_program(&sierra_path)?;
+    if args.print_sierra_stats {
+        print_sierra_stats(&sierra_program, &service_configuration, args.output_format);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let run_metrics = Arc::new(RunMetrics::default());
+    {
+        // a blocked oracle request can't be cancelled, the process exits instead; the transcript
+        // is flushed after every call so it already holds the calls made so far
+        let run_metrics = run_metrics.clone();
+        ctrlc::set_handler(move || {
+            let calls = run_metrics.oracle_calls.load(Ordering::Relaxed);
+            let last_service = run_metrics.last_service.lock().unwrap().clone();
+            eprintln!(
+                "Interrupted after {calls} oracle calls, last service contacted: {}",
+                last_service.as_deref().unwrap_or("none")
+            );
+            std::process::exit(EXIT_INTERRUPTED.into());
+        })
+        .expect("failed to install the Ctrl-C handler");
+    }
+
+    #[cfg(feature = "metrics")]
+    let metrics = match &args.metrics_addr {
+        Some(addr) => {
+            let metrics = Arc::new(metrics::Metrics::start(run_metrics.clone()));
+            metrics::serve(addr, metrics.clone())?;
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    let from_dump =
+        resolve_path_or_cwd(package, args.from_dump, "", None, base).map(|(dir, _)| dir);
+    let oracle_config = OracleConfig {
+        timeout: args.oracle_timeout_ms.map(Duration::from_millis),
+        server: args
+            .oracle_server
+            .map(|server| resolve_socket_path(package, server)),
+        fallback_server: env::var("ORACLE_SERVER")
+            .ok()
+            .map(|server| resolve_socket_path(package, server)),
+        ca_cert: resolve_path_or_cwd(package, args.oracle_ca_cert, "oracle_ca_cert", None, base)
+            .map(|(path, _)| path),
+        insecure: args.oracle_insecure,
+        headers: args.oracle_headers,
+        pool_size: args.oracle_pool_size,
+        max_response_bytes: args.oracle_max_response_bytes,
+        error_body_bytes: args.oracle_error_body_bytes,
+        breaker_threshold: args.oracle_breaker_threshold,
+        breaker_cooldown: args.oracle_breaker_cooldown_secs.map(Duration::from_secs),
+        stream_responses: args.oracle_stream_responses,
+        max_redirects: args.oracle_max_redirects,
+        trace_header: args.oracle_trace_header,
+        allowed_hosts: if args.oracle_allowed_hosts.is_empty() {
+            env::var("ORACLE_ALLOWED_HOSTS")
+                .map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(|host| host.trim().to_string())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            args.oracle_allowed_hosts
+        },
+        encoding: args.oracle_encoding.map(|encoding| match encoding {
+            WireEncoding::Json => Encoding::Json,
+            WireEncoding::Msgpack => Encoding::Msgpack,
+        }),
+        compression: match args.oracle_compression {
+            Compression::None => OracleCompression::None,
+            Compression::Gzip => OracleCompression::Gzip,
+        },
+        no_cache: args.no_oracle_cache,
+        cache_dir: resolve_path_or_cwd(package, args.oracle_cache_dir, "", None, base)
+            .map(|(dir, _)| dir),
+        cache_mode: match args.oracle_cache_mode {
+            CacheMode::Read => OracleCacheMode::Read,
+            CacheMode::Write => OracleCacheMode::Write,
+            CacheMode::Readwrite => OracleCacheMode::ReadWrite,
+            CacheMode::Off => OracleCacheMode::Off,
+        },
+        transcript: output_path(package, args.oracle_transcript, base)?,
+        dump_on_panic: resolve_path_or_cwd(package, args.dump_on_panic, "", None, base)
+            .map(|(dir, _)| dir),
+        capture_partial: args.capture_partial,
+        replay: args
+            .oracle_replay
+            .or_else(|| from_dump.as_ref().map(|dir| dir.join(DUMP_TRANSCRIPT_FILE))),
+        mock_response: (args.dry_run || args.sample_request.is_some())
+            .then_some(zeroed_response as MockResponse),
+        seed: args.oracle_seed,
+        metrics: Some(run_metrics),
+        skip_health_check: args.skip_health_check,
+        prefetch: args.oracle_prefetch,
+        time_limit: args.time_limit_secs.map(Duration::from_secs),
+        max_steps: args.max_steps,
+        casm_file: resolve_path_or_cwd(package, args.casm_file, "", None, base)
+            .map(|(path, _)| path),
+        casm_output: output_path(package, args.casm_output, base)?,
+        progress_interval: args.progress.map(Duration::from_secs),
+        quiet: args.quiet,
+        sample_request: args.sample_request.clone(),
+    };
+
+    let panic_map = match resolve_path_or_cwd(package, args.panic_map, "", None, base) {
+        Some((path, _)) => {
+            let content = fs::read_to_string(&path).map_err(Error::IO)?;
+            deserialization::panic_map_from_json(&content).map_err(|e| {
+                Error::ConfigError(format!("Invalid panic map {}: {e}", path.display()))
+            })?
+        }
+        None => HashMap::new(),
+    };
+
+    if let Some((inputs_file, _)) = resolve_path_or_cwd(package, args.inputs_file, "", None, base) {
+        let content = fs::read_to_string(&inputs_file).map_err(Error::IO)?;
+        let batch = deserialization::batch_func_args_from_json(&content, args.felt_input_encoding)
+            .map_err(|e| {
+                Error::ConfigError(format!(
+                    "Invalid inputs file {}: {e}",
+                    inputs_file.display()
+                ))
+            })?;
+        let results = run_1_batch(
+            &service_configuration,
+            &oracle_config,
+            &str_into_layout(&layout),
+            &batch,
+            args.available_gas,
+            &sierra_program,
+            &format!("::{}", args.entrypoint),
+            args.proof_mode,
+        )?;
+        let output = results
+            .iter()
+            .map(|result| match result {
+                Ok(result) => success_json(result, args.available_gas, args.felt_format),
+                Err(Error::RunPanic {
+                    panic_data,
+                    location,
+                    last_oracle_call,
+                    partial_output,
+                }) => panic_json(
+                    panic_data,
+                    location.as_ref(),
+                    last_oracle_call.as_ref(),
+                    partial_output.as_deref(),
+                    &panic_map,
+                    args.felt_format,
+                ),
+                Err(err) => json!({ "status": "error", "message": err.to_string() }),
+            })
+            .collect_vec();
+        println!("{}", json!(output));
+        // the first failed run gives the exit code of the batch
+        return match results.into_iter().find_map(Result::err) {
+            Some(err) => Err(err),
+            None => Ok(ExitCode::SUCCESS),
+        };
+    }
+
+    let trace_file = output_path(package, args.trace_file, base)?;
+    let memory_file = output_path(package, args.memory_file, base)?;
+
+    let input_file = match &from_dump {
+        Some(dir) => Some(dir.join(DUMP_INPUT_FILE)),
+        // the input file of Scarb.toml is only a default, arguments given on the command line win
+        None if args.args.is_some() => None,
+        None => resolve_path_or_cwd(package, args.input_file, "input_file", None, base).map(
+            |(path, source)| {
+                info!("Using the input file {}, given by {source}", path.display());
+                path
+            },
+        ),
+    };
+    let func_args = match input_file {
+        Some(input_file) => {
+            let content = fs::read_to_string(&input_file).map_err(Error::IO)?;
+            deserialization::func_args_from_json(&content, args.felt_input_encoding).map_err(
+                |e| Error::ConfigError(format!("Invalid input file {}: {e}", input_file.display())),
+            )?
+        }
+        None => cli_args.unwrap_or_default(),
+    };
+
+    let expected_output = match resolve_path_or_cwd(package, args.expect_output, "", None, base) {
+        Some((path, _)) => {
+            let content = fs::read_to_string(&path).map_err(Error::IO)?;
+            let expected = deserialization::expected_felts_from_str(&content).map_err(|e| {
+                Error::ConfigError(format!("Invalid expected output {}: {e}", path.display()))
+            })?;
+            Some((path, expected))
+        }
+        None => None,
+    };
+
+    let result = run_1(
         &service_configuration,
-        &str_into_layout(&args.layout),
-        &args.trace_file,
-        &args.memory_file,
-        &args.args,
+        &oracle_config,
+        &str_into_layout(&layout),
+        &trace_file,
+        &memory_file,
+        &func_args,
+        args.available_gas,
         &sierra_program,
-        "::main",
+        &format!("::{}", args.entrypoint),
         args.proof_mode,
-    ) {
+    );
+    let gas_used = result
+        .as_ref()
+        .ok()
+        .and_then(|result| args.available_gas.zip(result.remaining_gas))
+        .map(|(available, remaining)| available - remaining);
+    // the trace and memory are only written by a run that completes, the transcript by any run and
+    // the panic dump by a run that panics
+    let written = [("Trace", &trace_file), ("Memory", &memory_file)]
+        .into_iter()
+        .filter(|_| result.is_ok())
+        .chain([
+            ("Oracle transcript", &oracle_config.transcript),
+            ("CASM", &oracle_config.casm_output),
+        ])
+        .chain(
+            Some(("Panic dump", &oracle_config.dump_on_panic))
+                .filter(|_| matches!(result, Err(Error::RunPanic { .. }))),
+        )
+        .filter(|_| !args.quiet);
+    for (artifact, path) in written {
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            eprintln!("{artifact} written to {}", path.display());
+        }
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = &metrics {
+        metrics.finish();
+        let gas_used = gas_used.unwrap_or_default() as u64;
+        metrics.gas_used.store(gas_used, Ordering::Relaxed);
+    }
+
+    match result {
         Err(Error::Cli(err)) => err.exit(),
-        Ok(return_values) => {
-            if !return_values.is_empty() {
-                let return_values_string_list =
-                    return_values.iter().map(|m| m.to_string()).join(", ");
-                println!("Return values : [{}]", return_values_string_list);
+        Err(Error::SampleRequest {
+            service,
+            method,
+            ordinal,
+            request,
+        }) => {
+            match args.output_format {
+                OutputFormat::Text => {
+                    let body = sample_request_body(
+                        &service_configuration,
+                        &oracle_config,
+                        &service,
+                        &method,
+                        &request,
+                    )?;
+                    io::stdout().write_all(&body)?;
+                }
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "service": service,
+                        "method": method,
+                        "call": ordinal,
+                        "request": request,
+                    }))
+                    .map_err(|e| Error::IO(e.into()))?
+                ),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Ok(_) if args.sample_request.is_some() => Err(Error::ConfigError(format!(
+            "The run returned without calling oracle method {}",
+            args.sample_request.unwrap_or_default()
+        ))),
+        Ok(result) => {
+            match args.output_format {
+                OutputFormat::Text => {
+                    if !result.return_values.is_empty() {
+                        let return_values_string_list = result
+                            .return_values
+                            .iter()
+                            .map(|m| format_return_value(m, args.felt_format))
+                            .join(", ");
+                        println!("Return values : [{}]", return_values_string_list);
+                    }
+                    if let Some(gas_used) = gas_used {
+                        println!("Gas used: {gas_used}");
+                    }
+                }
+                OutputFormat::Json => println!(
+                    "{}",
+                    success_json(&result, args.available_gas, args.felt_format)
+                ),
+            }
+            if let Some((path, expected)) = &expected_output {
+                let diff = output_diff(expected, &result.return_values, args.felt_format);
+                if !diff.is_empty() {
+                    eprintln!(
+                        "error: the return values don't match the expected output {}",
+                        path.display()
+                    );
+                    diff.iter().for_each(|line| eprintln!("  {line}"));
+                    return Ok(ExitCode::from(EXIT_OUTPUT_MISMATCH));
+                }
             }
-            Ok(())
+            Ok(ExitCode::SUCCESS)
         }
-        Err(Error::RunPanic(panic_data)) => {
-            if !panic_data.is_empty() {
-                let panic_data_string_list = panic_data
+        Err(Error::RunPanic {
+            panic_data,
+            location,
+            last_oracle_call,
+            partial_output,
+        }) => {
+            match args.output_format {
+                OutputFormat::Text if !panic_data.is_empty() => {
+                    let panic_data_string_list = panic_data
+                        .iter()
+                        .map(|m| {
+                            // a known error code is explained whatever the felt format
+                            let msg = panic_map.get(m).cloned().or_else(|| {
+                                panic_string(m).filter(|_| args.felt_format == FeltFormat::Auto)
+                            });
+                            let felt = format_felt(m, args.felt_format);
+                            match msg {
+                                Some(msg) => format!("{felt} ('{msg}')"),
+                                None => felt,
+                            }
+                        })
+                        .join(", ");
+                    match &location {
+                        Some(location) => println!(
+                            "Run panicked with: [{}] at {}",
+                            panic_data_string_list, location
+                        ),
+                        None => println!("Run panicked with: [{}]", panic_data_string_list),
+                    }
+                    if let Some(call) = &last_oracle_call {
+                        eprintln!("Last oracle call before the panic: {call}");
+                    }
+                }
+                OutputFormat::Text => {}
+                OutputFormat::Json => println!(
+                    "{}",
+                    panic_json(
+                        &panic_data,
+                        location.as_ref(),
+                        last_oracle_call.as_ref(),
+                        partial_output.as_deref(),
+                        &panic_map,
+                        args.felt_format
+                    )
+                ),
+            }
+            // what the program computed before the panic, printed even without panic data
+            if let (OutputFormat::Text, Some(partial_output)) =
+                (&args.output_format, &partial_output)
+            {
+                let partial_output_list = partial_output
                     .iter()
-                    .map(|m| {
-                        // Try to parse to utf8 string
-                        let msg = String::from_utf8(m.to_bytes_be().to_vec());
-                        if let Ok(msg) = msg {
-                            format!("{} ('{}')", m, msg)
-                        } else {
-                            m.to_string()
-                        }
-                    })
+                    .map(|m| format_felt(m, args.felt_format))
                     .join(", ");
-                println!("Run panicked with: [{}]", panic_data_string_list);
+                println!("Partial output: [{partial_output_list}]");
             }
-            Ok(())
+            Err(Error::RunPanic {
+                panic_data,
+                location,
+                last_oracle_call,
+                partial_output,
+            })
+        }
+        Err(Error::OracleRejected { service, message })
+            if matches!(args.output_format, OutputFormat::Json) =>
+        {
+            println!(
+                "{}",
+                json!({ "status": "rejected", "service": service, "message": message })
+            );
+            Err(Error::OracleRejected { service, message })
         }
         Err(err) => Err(err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{str_into_layout, validate_layout};
+    use cairo_oracle_hint_processor::SUGGESTED_LAYOUTS;
+
+    #[test]
+    fn it_accepts_the_suggested_layouts() {
+        for layout in SUGGESTED_LAYOUTS {
+            let name = validate_layout(layout.to_str()).unwrap();
+            assert_eq!(str_into_layout(&name), layout);
+        }
+    }
+}
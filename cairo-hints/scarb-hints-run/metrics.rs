@@ -0,0 +1,111 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cairo_oracle_hint_processor::RunMetrics;
+
+/// Metrics of the run, served in the Prometheus text format.
+#[derive(Debug)]
+pub struct Metrics {
+    pub run: Arc<RunMetrics>,
+    pub gas_used: AtomicU64,
+    started: Instant,
+    duration: Mutex<Option<Duration>>,
+}
+
+impl Metrics {
+    pub fn start(run: Arc<RunMetrics>) -> Self {
+        Self {
+            run,
+            gas_used: AtomicU64::new(0),
+            started: Instant::now(),
+            duration: Mutex::new(None),
+        }
+    }
+
+    /// Stops the run duration.
+    pub fn finish(&self) {
+        *self.duration.lock().unwrap() = Some(self.started.elapsed());
+    }
+
+    fn render(&self) -> String {
+        let duration = self
+            .duration
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| self.started.elapsed());
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed).to_string();
+        let metrics = [
+            (
+                "oracle_calls_total",
+                "counter",
+                "Oracle calls made by the run.",
+                load(&self.run.oracle_calls),
+            ),
+            (
+                "oracle_failures_total",
+                "counter",
+                "Oracle calls that stopped the run.",
+                load(&self.run.oracle_failures),
+            ),
+            (
+                "oracle_retries_total",
+                "counter",
+                "Oracle requests retried after a transient failure.",
+                load(&self.run.oracle_retries),
+            ),
+            (
+                "gas_used",
+                "gauge",
+                "Gas used by the run, 0 until it ends.",
+                load(&self.gas_used),
+            ),
+            (
+                "run_duration_seconds",
+                "gauge",
+                "Time elapsed since the start of the run.",
+                duration.as_secs_f64().to_string(),
+            ),
+        ];
+        let mut body = String::new();
+        for (name, kind, help, value) in metrics {
+            body.push_str(&format!("# HELP cairo_hints_{name} {help}\n"));
+            body.push_str(&format!("# TYPE cairo_hints_{name} {kind}\n"));
+            body.push_str(&format!("cairo_hints_{name} {value}\n"));
+        }
+        body
+    }
+}
+
+/// Serves `metrics` on `addr` from a background thread, until the process exits.
+pub fn serve(addr: &str, metrics: Arc<Metrics>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = respond(stream, &metrics);
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    // every path answers with the metrics, only the request head needs to be consumed
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" {
+        line.clear();
+    }
+    let body = metrics.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
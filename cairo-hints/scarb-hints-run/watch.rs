@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use cairo_oracle_hint_processor::Error;
+use scarb_metadata::PackageMetadata;
+use scarb_utils::{absolute_path, proto_paths};
+
+/// How often the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time without further changes before a run starts, so that a burst of saves runs only once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Modification time of each watched file.
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// The directories watched for files of an extension, and the files watched on their own.
+struct Watched {
+    dirs: Vec<(PathBuf, &'static str)>,
+    files: Vec<PathBuf>,
+}
+
+/// Runs the command again, without `--watch`, each time the Cairo sources, the protos, the
+/// `Scarb.toml` or the Oracle.lock of the package change. The oracle servers keep running between
+/// runs, each run builds the package and connects to them again.
+pub fn watch(package: &PackageMetadata, oracle_lock: &Path) -> Result<(), Error> {
+    let exe = env::current_exe()?;
+    let args = env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--watch")
+        .collect::<Vec<_>>();
+    let watched = watched_paths(package, oracle_lock);
+    let mut snapshot = watched_files(&watched);
+
+    for run in 1.. {
+        eprintln!("──── run {run} ────");
+        let status = Command::new(&exe).args(&args).status()?;
+        let outcome = match status.code() {
+            Some(code) => format!("exited with code {code}"),
+            None => "was terminated".to_string(),
+        };
+        eprintln!("──── run {run} {outcome}, watching for changes ────");
+
+        let changed = loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = watched_files(&watched);
+            if current != snapshot {
+                break changed_file(&snapshot, &current);
+            }
+        };
+        // wait for the saves to settle
+        loop {
+            thread::sleep(DEBOUNCE);
+            let current = watched_files(&watched);
+            if current == snapshot {
+                break;
+            }
+            snapshot = current;
+        }
+        eprintln!();
+        eprintln!("{} changed", changed.display());
+    }
+    Ok(())
+}
+
+/// The directories of the Cairo sources of the targets, the directories the `definitions` proto
+/// and its imports are read from, the `Scarb.toml` and the Oracle.lock.
+fn watched_paths(package: &PackageMetadata, oracle_lock: &Path) -> Watched {
+    let mut dirs = package
+        .targets
+        .iter()
+        .filter_map(|target| target.source_path.parent())
+        .map(|dir| (dir.as_std_path().to_path_buf(), "cairo"))
+        .collect::<Vec<_>>();
+    if let Some(definitions) = absolute_path(package, None, "definitions", None) {
+        dirs.extend(
+            proto_paths(package, Vec::new(), &definitions)
+                .into_iter()
+                .map(|dir| (dir, "proto")),
+        );
+    }
+    dirs.sort();
+    dirs.dedup();
+    Watched {
+        dirs,
+        files: vec![
+            package.manifest_path.as_std_path().to_path_buf(),
+            oracle_lock.to_path_buf(),
+        ],
+    }
+}
+
+fn watched_files(watched: &Watched) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for (dir, extension) in &watched.dirs {
+        collect_files(dir, extension, &mut snapshot);
+    }
+    for file in &watched.files {
+        snapshot.insert(file.clone(), modified(file));
+    }
+    snapshot
+}
+
+/// Adds the files with the `extension` under `dir`, skipping the hidden directories, the `target`
+/// directory the build writes to, `node_modules` and the nested Cargo projects.
+fn collect_files(dir: &Path, extension: &str, snapshot: &mut Snapshot) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            let skipped = name.starts_with('.')
+                || name == "target"
+                || name == "node_modules"
+                || path.join("Cargo.toml").exists();
+            if !skipped {
+                collect_files(&path, extension, snapshot);
+            }
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            let modified = modified(&path);
+            snapshot.insert(path, modified);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Returns a file that was added, removed or modified between the two snapshots.
+fn changed_file(before: &Snapshot, after: &Snapshot) -> PathBuf {
+    after
+        .iter()
+        .find(|(path, modified)| before.get(*path) != Some(modified))
+        .or_else(|| before.iter().find(|(path, _)| !after.contains_key(*path)))
+        .map(|(path, _)| path.clone())
+        .unwrap_or_default()
+}
@@ -1,18 +1,15 @@
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
 use std::path::PathBuf;
 use std::{env, fs};
 
 use anyhow::{Context, Result};
 use cairo_lang_hints_test_runner::{CompiledTestRunner, TestRunConfig};
 use cairo_lang_test_plugin::TestCompilation;
-use cairo_proto_serde::configuration::Configuration;
+use cairo_oracle_hint_processor::{load_configuration, OracleConfig};
 use cairo_vm::types::layout_name::LayoutName;
 use clap::Parser;
 use scarb_metadata::{Metadata, MetadataCommand, PackageMetadata, ScarbCommand, TargetMetadata};
 use scarb_ui::args::PackagesFilter;
-use scarb_utils::absolute_path;
+use scarb_utils::{load_servers_config, oracle_lock_path, resolve_layout, PathBase};
 
 /// Execute all unit tests of a local package.
 #[derive(Parser, Clone, Debug)]
@@ -37,11 +34,22 @@ struct Args {
     #[arg(long)]
     servers_config_file: Option<PathBuf>,
 
+    /// Oracle server used by every service, overrides servers.json, Oracle.lock and ORACLE_SERVER.
+    #[arg(long)]
+    oracle_server: Option<String>,
+
     #[arg(long)]
     oracle_lock: Option<PathBuf>,
 
-    #[clap(long = "layout", default_value = "all_cairo", value_parser=validate_layout)]
-    layout: String,
+    /// Profile of the Oracle.lock whose service settings override the others, merged over the
+    /// `default` profile.
+    #[arg(long, value_name = "NAME")]
+    oracle_profile: Option<String>,
+
+    /// Layout of the VM, or else the `layout` of the `[tool.hints]` section, `all_cairo` by
+    /// default.
+    #[clap(long = "layout", value_parser=validate_layout)]
+    layout: Option<String>,
 }
 
 fn validate_layout(value: &str) -> Result<String, String> {
@@ -49,9 +57,11 @@ fn validate_layout(value: &str) -> Result<String, String> {
         "plain"
         | "small"
         | "dex"
+        | "recursive"
         | "starknet"
         | "starknet_with_keccak"
         | "recursive_large_output"
+        | "recursive_with_poseidon"
         | "all_cairo"
         | "all_solidity"
         | "dynamic" => Ok(value.to_string()),
@@ -78,6 +88,7 @@ fn str_into_layout(value: &str) -> LayoutName {
 
 fn main() -> Result<()> {
     let args: Args = Args::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
 
     let metadata = MetadataCommand::new().inherit_stderr().exec()?;
 
@@ -99,25 +110,25 @@ fn main() -> Result<()> {
 
     for package in matched {
         println!("testing {} ...", package.name);
-
-        let lock_output = absolute_path(&package, args.oracle_lock.clone(), "oracle_lock", Some(PathBuf::from("Oracle.lock")))
-            .expect("lock path must be provided either as an argument (--oracle-lock src) or in the Scarb.toml file in the [tool.hints] section.");
-        let lock_file = File::open(lock_output)?;
-        let reader = BufReader::new(lock_file);
-        let mut service_config: Configuration = serde_json::from_reader(reader)?;
-
-        // Get the servers config path
-        let servers_config_path = absolute_path(&package, None, "servers_config", Some(PathBuf::from("servers.json")))
-            .expect("servers config path must be provided either in the Scarb.toml file in the [tool.hints] section or default to servers.json in the project root.");
-
-        // Read and parse the servers config file
-        let config_content = fs::read_to_string(&servers_config_path)
-            .with_context(|| format!("failed to read servers config file: {}", servers_config_path.display()))?;
-        let servers_config: HashMap<String, String> = serde_json::from_str(&config_content)
-            .with_context(|| format!("failed to parse servers config file: {}", servers_config_path.display()))?;
-
-        // Add the server_config to the Configuration
-        service_config.servers_config = servers_config;
+        let layout = resolve_layout(Some(&package), args.layout.clone(), validate_layout)
+            .map_err(anyhow::Error::msg)?;
+
+        let (lock_output, _) =
+            oracle_lock_path(Some(&package), args.oracle_lock.clone(), PathBase::Package)?;
+        let mut service_config = load_configuration(&lock_output)?;
+        service_config
+            .apply_profile(args.oracle_profile.as_deref())
+            .map_err(anyhow::Error::msg)?;
+
+        let servers_config_file = args.servers_config_file.clone();
+        service_config.servers_config =
+            load_servers_config(Some(&package), servers_config_file, PathBase::Package)?;
+
+        let oracle_config = OracleConfig {
+            server: args.oracle_server.clone(),
+            fallback_server: env::var("ORACLE_SERVER").ok(),
+            ..OracleConfig::default()
+        };
 
         for target in find_testable_targets(&package) {
             let file_path = target_dir.join(format!("{}.test.json", target.name.clone()));
@@ -133,7 +144,7 @@ fn main() -> Result<()> {
                 ignored: args.ignored,
             };
             let runner = CompiledTestRunner::new(test_compilation, config);
-            runner.run(&service_config, &str_into_layout(&args.layout))?;
+            runner.run(&service_config, &oracle_config, &str_into_layout(&layout))?;
             println!();
         }
     }
@@ -0,0 +1,144 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anyhow::Result;
+use cairo_oracle_hint_processor::conformance::verify_servers;
+use cairo_oracle_hint_processor::{load_configuration, OracleConfig};
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use scarb_metadata::MetadataCommand;
+use scarb_ui::args::PackagesFilter;
+use scarb_utils::{load_servers_config, oracle_lock_path, PathBase};
+use serde_json::json;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// A line per request, then the number of passed and failed ones.
+    Text,
+    /// A JSON array of the requests, with their response or failure.
+    Json,
+}
+
+/// Send representative requests to every method of the Oracle.lock, and check that the oracle
+/// servers answer them the way the hint processor expects.
+#[derive(Parser, Clone, Debug)]
+#[command(author, version)]
+struct Args {
+    #[command(flatten)]
+    packages_filter: PackagesFilter,
+
+    /// Oracle lock file path.
+    #[arg(long)]
+    oracle_lock: Option<PathBuf>,
+
+    /// Configuration file for oracle servers.
+    #[arg(long)]
+    servers_config_file: Option<PathBuf>,
+
+    /// Directory the relative paths of `--oracle-lock` and `--servers-config-file` are resolved
+    /// against.
+    #[arg(long, value_enum, default_value_t = PathBase::Package)]
+    paths_relative_to: PathBase,
+
+    /// Oracle server used by every service, overrides servers.json, Oracle.lock and ORACLE_SERVER.
+    #[arg(long)]
+    oracle_server: Option<String>,
+
+    /// Timeout of each oracle request, in milliseconds.
+    #[arg(long, value_name = "MS")]
+    oracle_timeout_ms: Option<u64>,
+
+    /// Only check this method, by name or as `Service.method`. Can be repeated.
+    #[arg(long = "method", value_name = "METHOD")]
+    methods: Vec<String>,
+
+    /// Format of the report.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+}
+
+fn main() -> Result<ExitCode> {
+    let args: Args = Args::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let metadata = MetadataCommand::new().inherit_stderr().exec()?;
+    let package = args.packages_filter.match_one(&metadata)?;
+
+    let base = args.paths_relative_to;
+    let (lock_path, _) = oracle_lock_path(Some(&package), args.oracle_lock.clone(), base)?;
+    let mut configuration = load_configuration(&lock_path)?;
+    configuration.servers_config =
+        load_servers_config(Some(&package), args.servers_config_file.clone(), base)?;
+
+    let unknown = args
+        .methods
+        .iter()
+        .filter(|name| {
+            !configuration.services.iter().any(|(service, methods)| {
+                methods
+                    .methods
+                    .keys()
+                    .any(|method| method == *name || format!("{service}.{method}") == **name)
+            })
+        })
+        .collect_vec();
+    if !unknown.is_empty() {
+        anyhow::bail!(
+            "unknown oracle method {}, `scarb hints-run --list-oracles` prints the declared ones",
+            unknown.iter().join(", ")
+        );
+    }
+
+    let oracle_config = OracleConfig {
+        timeout: args.oracle_timeout_ms.map(Duration::from_millis),
+        server: args.oracle_server.clone(),
+        fallback_server: env::var("ORACLE_SERVER").ok(),
+        quiet: true,
+        ..OracleConfig::default()
+    };
+    let checks = verify_servers(&configuration, &oracle_config, &args.methods)?;
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+
+    match args.output_format {
+        OutputFormat::Text => {
+            for check in &checks {
+                let case = check.case.name();
+                let name = format!("{}.{} ({case} request)", check.service, check.method);
+                match &check.outcome {
+                    Ok(_) => println!("PASS {name}"),
+                    Err(reason) => {
+                        println!("FAIL {name}: {reason}");
+                        println!("     request: {}", check.request);
+                    }
+                }
+            }
+            println!("{} passed, {failed} failed", checks.len() - failed);
+        }
+        OutputFormat::Json => {
+            let report = checks
+                .iter()
+                .map(|check| {
+                    let mut entry = json!({
+                        "service": check.service,
+                        "method": check.method,
+                        "case": check.case.name(),
+                        "request": check.request,
+                        "passed": check.passed(),
+                    });
+                    match &check.outcome {
+                        Ok(response) => entry["response"] = response.clone(),
+                        Err(reason) => entry["error"] = json!(reason),
+                    }
+                    entry
+                })
+                .collect_vec();
+            println!("{}", json!(report));
+        }
+    }
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -1,13 +1,228 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
 
+use cairo_oracle_hint_processor::Error;
+use cairo_proto_build::stale_protos;
+use clap::ValueEnum;
 use scarb_metadata::PackageMetadata;
 
+/// Directory the relative paths given on the command line are resolved against. Paths read from
+/// `Scarb.toml` and default paths are always relative to the package directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PathBase {
+    /// The directory of the package manifest.
+    #[default]
+    Package,
+    /// The current working directory.
+    Cwd,
+}
+
+/// Where a resolved path comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathSource {
+    Flag,
+    Manifest,
+    Default,
+}
+
+impl fmt::Display for PathSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PathSource::Flag => "the command line",
+            PathSource::Manifest => "Scarb.toml",
+            PathSource::Default => "the default",
+        })
+    }
+}
+
 pub fn absolute_path(
     package: &PackageMetadata,
     arg: Option<PathBuf>,
     config_key: &str,
     default: Option<PathBuf>,
 ) -> Option<PathBuf> {
+    resolve_path(package, arg, config_key, default, PathBase::Package).map(|(path, _)| path)
+}
+
+/// Directories searched for the files imported by the `definitions` proto: the directory of
+/// `definitions`, then the `args` given with `--proto-path`, or else the `proto_path` list of the
+/// `[tool.hints]` section. Relative paths are resolved against the package directory.
+pub fn proto_paths(
+    package: &PackageMetadata,
+    args: Vec<PathBuf>,
+    definitions: &Path,
+) -> Vec<PathBuf> {
+    let paths = if args.is_empty() {
+        package
+            .tool_metadata("hints")
+            .and_then(|tool_config| tool_config["proto_path"].as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|path| path.as_str().map(PathBuf::from))
+            .collect()
+    } else {
+        args
+    };
+    let mut includes = vec![definitions.parent().unwrap().to_path_buf()];
+    for path in paths {
+        let path = absolute_path(package, Some(path), "", None).unwrap();
+        if !includes.contains(&path) {
+            includes.push(path);
+        }
+    }
+    includes
+}
+
+/// The layout given on the command line, or else the `layout` of the `[tool.hints]` section,
+/// checked with `validate`, or else `all_cairo`.
+pub fn resolve_layout(
+    package: Option<&PackageMetadata>,
+    arg: Option<String>,
+    validate: fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    if let Some(layout) = arg {
+        return Ok(layout);
+    }
+    let configured = package
+        .and_then(|package| package.tool_metadata("hints"))
+        .and_then(|tool_config| tool_config["layout"].as_str().map(String::from));
+    match configured {
+        Some(layout) => validate(&layout)
+            .map_err(|e| format!("{e}: the `layout` of the [tool.hints] section of Scarb.toml")),
+        None => Ok("all_cairo".to_string()),
+    }
+}
+
+/// Compares the Cairo modules of the `cairo_output` directory with the `definitions` proto of the
+/// `[tool.hints]` section, and returns those generated from protos that changed since, with the
+/// changed protos. A package without `definitions` has nothing to compare.
+pub fn stale_bindings(package: &PackageMetadata) -> Vec<(PathBuf, Vec<String>)> {
+    let Some(definitions) = absolute_path(package, None, "definitions", None) else {
+        return Vec::new();
+    };
+    let includes = proto_paths(package, Vec::new(), &definitions);
+    let cairo_output = absolute_path(package, None, "cairo_output", Some(PathBuf::from("src")))
+        .expect("the cairo output has a default");
+    let Ok(entries) = fs::read_dir(cairo_output) else {
+        return Vec::new();
+    };
+    let mut stale = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "cairo")
+        })
+        .filter_map(|path| {
+            let protos = stale_protos(&fs::read_to_string(&path).ok()?, &includes);
+            (!protos.is_empty()).then_some((path, protos))
+        })
+        .collect::<Vec<_>>();
+    stale.sort();
+    stale
+}
+
+/// The Oracle.lock given with `--oracle-lock`, or else the `oracle_lock` of the `[tool.hints]`
+/// section, `Oracle.lock` by default, with where it comes from.
+pub fn oracle_lock_path(
+    package: Option<&PackageMetadata>,
+    arg: Option<PathBuf>,
+    base: PathBase,
+) -> Result<(PathBuf, PathSource), Error> {
+    resolve_path_or_cwd(
+        package,
+        arg,
+        "oracle_lock",
+        Some(PathBuf::from("Oracle.lock")),
+        base,
+    )
+    .ok_or_else(|| {
+        Error::ConfigError(
+            "the lock path must be given with --oracle-lock or as the `oracle_lock` of the \
+                 [tool.hints] section of Scarb.toml"
+                .to_string(),
+        )
+    })
+}
+
+/// Reads the servers of the services from the file given with `--servers-config-file`, or else
+/// the `servers_config` of the `[tool.hints]` section, `servers.json` by default. A missing file
+/// configures no server, the servers may also come from the flag, Oracle.lock or `ORACLE_SERVER`.
+/// The socket paths of the `unix://` servers are resolved relative to the package directory.
+pub fn load_servers_config(
+    package: Option<&PackageMetadata>,
+    arg: Option<PathBuf>,
+    base: PathBase,
+) -> Result<HashMap<String, String>, Error> {
+    let default = Some(PathBuf::from("servers.json"));
+    let Some((path, _)) = resolve_path_or_cwd(package, arg, "servers_config", default, base) else {
+        return Ok(HashMap::new());
+    };
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| {
+        Error::ServersConfigFileError(format!("Failed to read {}: {e}", path.display()))
+    })?;
+    let servers: HashMap<String, String> = serde_json::from_str(&content).map_err(|e| {
+        Error::ServersConfigFileError(format!("Failed to parse {}: {e}", path.display()))
+    })?;
+    Ok(servers
+        .into_iter()
+        .map(|(selector, server)| (selector, resolve_socket_path(package, server)))
+        .collect())
+}
+
+/// Resolves the socket path of a `unix://` server relative to the package directory.
+pub fn resolve_socket_path(package: Option<&PackageMetadata>, server: String) -> String {
+    match server.strip_prefix("unix://") {
+        Some(socket) => {
+            let socket = PathBuf::from(socket);
+            let base = PathBase::Package;
+            let socket = resolve_path_or_cwd(package, Some(socket.clone()), "", None, base)
+                .map_or(socket, |(path, _)| path);
+            format!("unix://{}", socket.display())
+        }
+        None => server,
+    }
+}
+
+/// Same as [`resolve_path`] for a run that may have no package, such as one given a Sierra file
+/// outside of Scarb: without a package there is no `[tool.hints]` section, and the relative paths
+/// are resolved against the current directory.
+pub fn resolve_path_or_cwd(
+    package: Option<&PackageMetadata>,
+    arg: Option<PathBuf>,
+    config_key: &str,
+    default: Option<PathBuf>,
+    base: PathBase,
+) -> Option<(PathBuf, PathSource)> {
+    if let Some(package) = package {
+        return resolve_path(package, arg, config_key, default, base);
+    }
+    let (path, source) = arg
+        .map(|arg| (arg, PathSource::Flag))
+        .or(default.map(|path| (path, PathSource::Default)))?;
+    let path = if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir()
+            .expect("the current directory is not accessible")
+            .join(path)
+    };
+    Some((path, source))
+}
+
+/// Same as [`absolute_path`], resolving a relative `arg` against `base` and telling where the
+/// path comes from.
+pub fn resolve_path(
+    package: &PackageMetadata,
+    arg: Option<PathBuf>,
+    config_key: &str,
+    default: Option<PathBuf>,
+    base: PathBase,
+) -> Option<(PathBuf, PathSource)> {
     let manifest_path = package.manifest_path.clone().into_std_path_buf();
     let project_dir = manifest_path.parent().expect(
         format!(
@@ -17,17 +232,24 @@ pub fn absolute_path(
         .as_str(),
     );
 
-    let definitions = arg
+    let (definitions, source) = arg
+        .map(|arg| (arg, PathSource::Flag))
         .or_else(|| {
             package
                 .tool_metadata("hints")
                 .and_then(|tool_config| tool_config[config_key].as_str().map(PathBuf::from))
+                .map(|path| (path, PathSource::Manifest))
         })
-        .or(default)?;
+        .or(default.map(|path| (path, PathSource::Default)))?;
 
-    if definitions.is_absolute() {
-        Some(definitions)
+    let path = if definitions.is_absolute() {
+        definitions
+    } else if source == PathSource::Flag && base == PathBase::Cwd {
+        std::env::current_dir()
+            .expect("the current directory is not accessible")
+            .join(definitions)
     } else {
-        Some(project_dir.join(definitions))
-    }
+        project_dir.join(definitions)
+    };
+    Some((path, source))
 }
@@ -14,7 +14,7 @@ use cairo_lang_test_plugin::test_config::{PanicExpectation, TestExpectation};
 use cairo_lang_test_plugin::{
     compile_test_prepared_db, test_plugin_suite, TestCompilation, TestConfig,
 };
-use cairo_oracle_hint_processor::{run_1, Error, FuncArgs};
+use cairo_oracle_hint_processor::{run_1, Error, FuncArgs, OracleConfig};
 use cairo_proto_serde::configuration::Configuration;
 use cairo_vm::types::layout_name::LayoutName;
 use cairo_vm::Felt252 as VMFelt;
@@ -50,10 +50,11 @@ impl TestRunner {
     pub fn run(
         &self,
         configuration: &Configuration,
+        oracle_config: &OracleConfig,
         layout: &LayoutName,
     ) -> Result<Option<TestsSummary>> {
         let runner = CompiledTestRunner::new(self.compiler.build()?, self.config.clone());
-        runner.run(configuration, layout)
+        runner.run(configuration, oracle_config, layout)
     }
 }
 
@@ -72,10 +73,12 @@ impl CompiledTestRunner {
         Self { compiled, config }
     }
 
-    /// Execute preconfigured test execution.
+    /// Execute preconfigured test execution, every test calling the oracle servers of
+    /// `oracle_config`.
     pub fn run(
         self,
         configuration: &Configuration,
+        oracle_config: &OracleConfig,
         layout: &LayoutName,
     ) -> Result<Option<TestsSummary>> {
         let (compiled, filtered_out) = filter_test_cases(
@@ -93,6 +96,7 @@ impl CompiledTestRunner {
             compiled.named_tests,
             compiled.sierra_program,
             configuration,
+            oracle_config,
             layout,
         )?;
 
@@ -128,8 +132,13 @@ impl CompiledTestRunner {
                                 })
                                 .join(", ");
                             println!("Run panicked with: [{}]", panic_data_string_list);
+                        } else {
+                            println!("Run panicked.");
                         }
                     }
+                    RunResultValue::Error(error) => {
+                        println!("Run failed: {error}");
+                    }
                 }
             }
             println!();
@@ -253,6 +262,8 @@ pub enum RunResultValue {
     Success(Vec<VMFelt>),
     /// Run panicked, returning the carried error data.
     Panic(Vec<VMFelt>),
+    /// Run couldn't complete, e.g. because an oracle call failed.
+    Error(String),
 }
 
 /// The status of a ran test.
@@ -277,9 +288,9 @@ pub struct TestsSummary {
     failed_run_results: Vec<RunResultValue>,
 }
 
-fn is_equal_vec_felt(a: &Vec<VMFelt>, b: &Vec<Felt252>) -> bool {
+fn is_equal_vec_felt(a: &[VMFelt], b: &[Felt252]) -> bool {
     a.iter().map(|f| f.to_biguint()).collect_vec()
-        != b.iter().map(|f: &Felt252| f.to_biguint()).collect_vec()
+        == b.iter().map(|f: &Felt252| f.to_biguint()).collect_vec()
 }
 
 /// Runs the tests and process the results for a summary.
@@ -289,6 +300,7 @@ pub fn run_tests(
     // _function_set_costs: OrderedHashMap<FunctionId, OrderedHashMap<CostTokenType, i32>>,
     // _contracts_info: OrderedHashMap<Felt252, ContractInfo>,
     configuration: &Configuration,
+    oracle_config: &OracleConfig,
     layout: &LayoutName,
 ) -> Result<TestsSummary> {
     println!("running {} tests", named_tests.len());
@@ -308,10 +320,12 @@ pub fn run_tests(
 
                 let r = run_1(
                     configuration,
+                    oracle_config,
                     layout,
                     &None,
                     &None,
                     &FuncArgs::default(),
+                    None,
                     &sierra_program,
                     &name,
                     false,
@@ -323,11 +337,11 @@ pub fn run_tests(
                         status: match r {
                             Ok(_) => match test.expectation {
                                 TestExpectation::Success => TestStatus::Success,
-                                TestExpectation::Panics(_) => TestStatus::Fail(
-                                    RunResultValue::Panic([VMFelt::default()].to_vec()),
-                                ),
+                                TestExpectation::Panics(_) => {
+                                    TestStatus::Fail(RunResultValue::Success(vec![]))
+                                }
                             },
-                            Err(Error::RunPanic(panic_data)) => match test.expectation {
+                            Err(Error::RunPanic { panic_data, .. }) => match test.expectation {
                                 TestExpectation::Success => {
                                     TestStatus::Fail(RunResultValue::Panic(panic_data))
                                 }
@@ -342,7 +356,8 @@ pub fn run_tests(
                                     }
                                 }
                             },
-                            Err(e) => panic!("Error: {:?}", e),
+                            // an oracle that fails fails the test, not the whole run
+                            Err(e) => TestStatus::Fail(RunResultValue::Error(e.to_string())),
                         },
                         gas_usage: None,
                     }),
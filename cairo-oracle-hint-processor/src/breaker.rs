@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Cooldown of a breaker unless configured otherwise.
+pub const DEFAULT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct State {
+    /// Transport failures since the last successful call.
+    failures: u32,
+    /// Set while the calls are short-circuited.
+    open_until: Option<Instant>,
+}
+
+/// Short-circuits the calls to an oracle server after `threshold` consecutive transport failures,
+/// for `cooldown`. The call let through once the cooldown is over opens the breaker again if it
+/// fails, and any successful call closes it.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    /// Disabled when `None`.
+    threshold: Option<u32>,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: Option<u32>, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns how long the calls are still short-circuited, with the number of failures that
+    /// opened the breaker, or `None` when a call can be made.
+    pub fn open(&self) -> Option<(Duration, u32)> {
+        let state = self.state.lock().unwrap();
+        let remaining = state.open_until?.checked_duration_since(Instant::now())?;
+        (!remaining.is_zero()).then_some((remaining, state.failures))
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    pub fn record_failure(&self) {
+        let Some(threshold) = self.threshold else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CircuitBreaker;
+    use std::time::Duration;
+
+    #[test]
+    fn it_opens_after_consecutive_failures_until_a_success() {
+        let breaker = CircuitBreaker::new(Some(2), Duration::from_secs(60));
+        breaker.record_failure();
+        assert!(breaker.open().is_none());
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.open().is_none());
+        breaker.record_failure();
+        assert!(matches!(breaker.open(), Some((_, 2))));
+
+        // once the cooldown is over a call is let through, and opens the breaker again if it fails
+        let breaker = CircuitBreaker::new(Some(1), Duration::ZERO);
+        breaker.record_failure();
+        assert!(breaker.open().is_none());
+
+        let disabled = CircuitBreaker::new(None, Duration::from_secs(60));
+        (0..10).for_each(|_| disabled.record_failure());
+        assert!(disabled.open().is_none());
+    }
+}
@@ -1,6 +1,11 @@
-use cairo_lang_casm::{casm, casm_extend, hints::Hint, instructions::Instruction};
+use cairo_lang_casm::{
+    casm, casm_extend,
+    hints::{Hint, StarknetHint},
+    instructions::Instruction,
+};
 use cairo_lang_sierra::{
     extensions::{
+        array::ArrayType,
         bitwise::BitwiseType,
         core::{CoreLibfunc, CoreType},
         ec::EcOpType,
@@ -13,13 +18,13 @@ use cairo_lang_sierra::{
         ConcreteType, NamedType,
     },
     ids::ConcreteTypeId,
-    program::{Function, Program as SierraProgram},
+    program::{Function, Program as SierraProgram, Statement},
     program_registry::ProgramRegistry,
 };
 use cairo_lang_sierra_ap_change::calc_ap_changes;
 use cairo_lang_sierra_gas::gas_info::GasInfo;
 use cairo_lang_sierra_to_casm::{
-    compiler::{CairoProgram, SierraToCasmConfig},
+    compiler::SierraToCasmConfig,
     metadata::{calc_metadata, Metadata, MetadataComputationConfig, MetadataError},
 };
 use cairo_lang_sierra_type_size::get_type_size_map;
@@ -30,16 +35,29 @@ use cairo_vm::{
     serde::deserialize_program::{ApTracking, FlowTrackingData, HintParams, ReferenceManager},
     types::{builtin_name::BuiltinName, program::Program, relocatable::MaybeRelocatable, layout_name::LayoutName},
     vm::{
-        errors::{runner_errors::RunnerError, vm_errors::VirtualMachineError},
+        errors::vm_errors::VirtualMachineError,
         runners::cairo_runner::{CairoRunner, RunResources, RunnerMode},
+        trace::trace_entry::RelocatedTraceEntry,
         vm_core::VirtualMachine,
     },
     Felt252,
 };
 use itertools::chain;
-use std::collections::HashMap;
+use log::{info, warn};
+use num_traits::ToPrimitive;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::time::Instant;
 
-use crate::{rpc_hint_processor::Rpc1HintProcessor, Error, FuncArg};
+use crate::casm::CasmProgram;
+use crate::dry_run::print_call_inventory;
+use crate::dump::PanicDump;
+use crate::rpc_hint_processor::{OracleCache, PARTIAL_OUTPUT_SELECTOR};
+use crate::transport::OracleServer;
+use crate::{
+    rpc_hint_processor::Rpc1HintProcessor, Error, FuncArg, OracleConfig, OracleProvider,
+    PanicLocation, RunResult, RunTimings,
+};
 
 #[derive(Debug)]
 pub struct Cairo1RunConfig<'a> {
@@ -51,6 +69,8 @@ pub struct Cairo1RunConfig<'a> {
     // Should be true if either air_public_input or cairo_pie_output are needed
     // Sets builtins stop_ptr by calling `final_stack` on each builtin
     pub finalize_builtins: bool,
+    // Gas given to the entry function, the remaining gas is reported when set
+    pub available_gas: Option<usize>,
 }
 
 impl Default for Cairo1RunConfig<'_> {
@@ -62,35 +82,111 @@ impl Default for Cairo1RunConfig<'_> {
             layout: &LayoutName::all_cairo,
             proof_mode: false,
             finalize_builtins: false,
+            available_gas: None,
         }
     }
 }
 
+/// A Sierra program compiled to CASM, which can be run several times.
+pub struct CompiledProgram<'a> {
+    sierra_program: &'a SierraProgram,
+    sierra_program_registry: ProgramRegistry<CoreType, CoreLibfunc>,
+    type_sizes: UnorderedHashMap<ConcreteTypeId, i16>,
+    casm_program: CasmProgram,
+}
+
+/// Compiles `sierra_program` to CASM, or reads its CASM from `casm_file`. The CASM is then
+/// written to `casm_output`, if given.
+pub fn compile_program<'a>(
+    sierra_program: &'a SierraProgram,
+    casm_file: Option<&Path>,
+    casm_output: Option<&Path>,
+) -> Result<CompiledProgram<'a>, Error> {
+    let sierra_program_registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(sierra_program)?;
+    let type_sizes =
+        get_type_size_map(sierra_program, &sierra_program_registry).unwrap_or_default();
+    let started = Instant::now();
+    let casm_program = match casm_file {
+        Some(path) => CasmProgram::load(path, sierra_program)?,
+        None => {
+            let metadata = create_metadata(sierra_program, Some(Default::default()))?;
+            let config = SierraToCasmConfig {
+                gas_usage_check: false,
+                max_bytecode_size: usize::MAX,
+            };
+            let casm_program =
+                cairo_lang_sierra_to_casm::compiler::compile(sierra_program, &metadata, config)?;
+            CasmProgram::new(sierra_program, &casm_program)
+        }
+    };
+    info!(
+        "{} the CASM in {:.3}s",
+        if casm_file.is_some() {
+            "read"
+        } else {
+            "compiled"
+        },
+        started.elapsed().as_secs_f64()
+    );
+    if let Some(path) = casm_output {
+        casm_program.write(path)?;
+    }
+    Ok(CompiledProgram {
+        sierra_program,
+        sierra_program_registry,
+        type_sizes,
+        casm_program,
+    })
+}
+
 // Runs a Cairo 1 program
-// Returns the runner & VM after execution + the return values
+// Returns the runner & VM after execution + the return values and oracle calls
+// The oracle servers are resolved from the configuration unless `servers` are given
+// The cache of the oracle responses starts with the `prefetched` ones
+#[allow(clippy::too_many_arguments)]
 pub fn cairo_run_program(
-    sierra_program: &SierraProgram,
+    compiled: &CompiledProgram,
     cairo_run_config: Cairo1RunConfig,
     configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    provider: Option<&dyn OracleProvider>,
+    servers: Option<&HashMap<String, OracleServer>>,
+    prefetched: Option<&OracleCache>,
     entry_func_name: &str,
-) -> Result<(CairoRunner, VirtualMachine, Vec<MaybeRelocatable>), Error> {
-    let metadata = create_metadata(sierra_program, Some(Default::default()))?;
-    let sierra_program_registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(sierra_program)?;
-    let type_sizes =
-        get_type_size_map(sierra_program, &sierra_program_registry).unwrap_or_default();
-    let config = SierraToCasmConfig {
-        gas_usage_check: false,
-        max_bytecode_size: usize::MAX,
-    };
-    let casm_program =
-        cairo_lang_sierra_to_casm::compiler::compile(sierra_program, &metadata, config)?;
+) -> Result<(CairoRunner, VirtualMachine, RunResult), Error> {
+    let CompiledProgram {
+        sierra_program,
+        sierra_program_registry,
+        type_sizes,
+        casm_program,
+    } = compiled;
 
+    let run_started = Instant::now();
     let main_func = find_function(sierra_program, entry_func_name)?;
 
-    let initial_gas = 9999999999999_usize;
+    let initial_gas = cairo_run_config
+        .available_gas
+        .unwrap_or(9999999999999_usize);
 
     // Modified entry code to be compatible with custom cairo1 Proof Mode.
     // This adds code that's needed for dictionaries, adjusts ap for builtin pointers, adds initial gas for the gas builtin if needed, and sets up other necessary code for cairo1
+    check_args(
+        &sierra_program_registry,
+        &type_sizes,
+        main_func,
+        cairo_run_config.proof_mode,
+        cairo_run_config.args,
+    )?;
+    check_gas(
+        &sierra_program_registry,
+        main_func,
+        cairo_run_config.available_gas,
+    )?;
+
+    let incompatible = |reason: &Error| Error::IncompatibleEntryPoint {
+        name: main_func.id.to_string(),
+        reason: reason.to_string(),
+    };
     let (entry_code, builtins) = create_entry_code(
         &sierra_program_registry,
         &casm_program,
@@ -99,14 +195,20 @@ pub fn cairo_run_program(
         initial_gas,
         cairo_run_config.proof_mode,
         cairo_run_config.args,
-    )?;
+    )
+    .map_err(|err| match err {
+        Error::ArgumentsSizeMismatch { .. } | Error::ArgumentUnaligned { .. } => incompatible(&err),
+        err => err,
+    })?;
+
+    check_layout(cairo_run_config.layout, &builtins)?;
 
     // Fetch return type data
     let return_type_id = main_func
         .signature
         .ret_types
         .last()
-        .ok_or(Error::NoRetTypesInSignature)?;
+        .ok_or_else(|| incompatible(&Error::NoRetTypesInSignature))?;
     let return_type_size = type_sizes
         .get(return_type_id)
         .cloned()
@@ -125,23 +227,54 @@ pub fn cairo_run_program(
 
     // This is the program we are actually running/proving
     // With (embedded proof mode), cairo1 header and the libfunc footer
-    let instructions = chain!(
-        proof_mode_header.iter(),
-        entry_code.iter(),
-        casm_program.instructions.iter(),
-        libfunc_footer.iter(),
-    );
+    let header = chain!(proof_mode_header.iter(), entry_code.iter());
+    let casm_start = header
+        .clone()
+        .map(|instruction| instruction.body.op_size())
+        .sum::<usize>();
+    let footer_start = casm_start + casm_program.words.len();
+    let hints = chain!(
+        instruction_hints(header.clone(), 0),
+        casm_program
+            .hints
+            .iter()
+            .map(|(offset, hints)| (casm_start + offset, hints.clone())),
+        instruction_hints(libfunc_footer.iter(), footer_start),
+    )
+    .collect::<Vec<_>>();
 
-    let (processor_hints, program_hints) = build_hints_vec(instructions.clone());
+    let (processor_hints, program_hints) = build_hints_vec(hints);
+    check_oracle_calls(&processor_hints, configuration)?;
 
     let hint_processor = Cairo1HintProcessor::new(&processor_hints, RunResources::default());
-    let mut hint_processor = Rpc1HintProcessor::new(hint_processor, configuration)?;
+    let mut hint_processor = match servers {
+        Some(servers) => Rpc1HintProcessor::with_servers(
+            hint_processor,
+            configuration,
+            oracle_config,
+            provider,
+            servers.clone(),
+            prefetched,
+        )?,
+        None => Rpc1HintProcessor::new(hint_processor, configuration, oracle_config, provider)?,
+    };
+    hint_processor.set_input(cairo_run_config.args);
 
-    let data: Vec<MaybeRelocatable> = instructions
-        .flat_map(|inst| inst.assemble().encode())
-        .map(|x| Felt252::from(&x))
-        .map(MaybeRelocatable::from)
-        .collect();
+    let encode = |instruction: &Instruction| {
+        instruction
+            .assemble()
+            .encode()
+            .iter()
+            .map(Felt252::from)
+            .collect::<Vec<_>>()
+    };
+    let data: Vec<MaybeRelocatable> = chain!(
+        header.clone().flat_map(encode),
+        casm_program.words.iter().cloned(),
+        libfunc_footer.iter().flat_map(encode),
+    )
+    .map(MaybeRelocatable::from)
+    .collect();
 
     let data_len = data.len();
 
@@ -189,7 +322,33 @@ pub fn cairo_run_program(
     additional_initialization(&mut vm, data_len)?;
 
     // Run it until the end / infinite loop in proof_mode
-    runner.run_until_pc(end, &mut vm, &mut hint_processor)?;
+    let started = Instant::now();
+    let result = runner.run_until_pc(end, &mut vm, &mut hint_processor);
+    let run_time = started.elapsed();
+    let oracle_time = hint_processor.oracle_time();
+    info!(
+        "{} oracle calls took {:.3}s, the VM {:.3}s",
+        hint_processor.oracle_calls().values().sum::<usize>(),
+        oracle_time.as_secs_f64(),
+        run_time.saturating_sub(oracle_time).as_secs_f64()
+    );
+    let stats = hint_processor.stats();
+    info!(
+        "oracle calls: {}, {} from the cache, {} retried, {} JSON bytes sent, {} JSON bytes received",
+        stats.oracle_calls,
+        stats.cache_hits,
+        stats.retried_calls,
+        stats.json_bytes_sent,
+        stats.json_bytes_received
+    );
+    // a sampled run only prints the request it stopped at
+    if oracle_config.mock_response.is_some() && oracle_config.sample_request.is_none() {
+        print_call_inventory(hint_processor.mocked_calls());
+    }
+    // Oracle failures are reported as the error recorded by the hint processor
+    if let Err(err) = result {
+        return Err(hint_processor.take_error().unwrap_or(err.into()));
+    }
     if cairo_run_config.proof_mode {
         // As we will be inserting the return values into the output segment after running the main program (right before the infinite loop) the computed size for the output builtin will be 0
         // We need to manually set the segment size for the output builtin's segment so memory hole counting doesn't fail due to having a higher accessed address count than the segment's size
@@ -200,7 +359,49 @@ pub fn cairo_run_program(
     runner.end_run(false, false, &mut vm, &mut hint_processor)?;
 
     // Fetch return values
-    let return_values = fetch_return_values(return_type_size, return_type_id, &vm)?;
+    let return_values = match fetch_return_values(return_type_size, return_type_id, &vm) {
+        // The statement that raised the panic can only be found in the trace
+        Err(Error::RunPanic { panic_data, .. })
+            if cairo_run_config.trace_enabled || oracle_config.dump_on_panic.is_some() =>
+        {
+            runner.relocate(&mut vm, true)?;
+            let location = runner.relocated_trace.as_ref().and_then(|trace| {
+                find_panic_location(trace, casm_start, &casm_program, sierra_program)
+            });
+            if let Some(dir) = &oracle_config.dump_on_panic {
+                let dump = PanicDump {
+                    entry_func_name,
+                    args: cairo_run_config.args,
+                    panic_data: &panic_data,
+                    location: location.as_ref(),
+                    calls: hint_processor.recorded_calls(),
+                };
+                // the panic is reported even if it can't be dumped
+                if let Err(e) = dump.write(dir, &runner, &vm) {
+                    warn!("failed to write the panic dump to {}: {e}", dir.display());
+                }
+            }
+            return Err(Error::RunPanic {
+                panic_data,
+                location,
+                last_oracle_call: hint_processor.last_call(),
+                partial_output: hint_processor.partial_output(),
+            });
+        }
+        Err(Error::RunPanic { panic_data, .. }) => {
+            return Err(Error::RunPanic {
+                panic_data,
+                location: None,
+                last_oracle_call: hint_processor.last_call(),
+                partial_output: hint_processor.partial_output(),
+            })
+        }
+        result => result?,
+    };
+    let remaining_gas = match cairo_run_config.available_gas {
+        Some(_) => fetch_remaining_gas(&main_func.signature.ret_types, &type_sizes, &vm)?,
+        None => None,
+    };
 
     // Set stop pointers for builtins so we can obtain the air public input
     if cairo_run_config.finalize_builtins {
@@ -222,7 +423,18 @@ pub fn cairo_run_program(
 
     runner.relocate(&mut vm, true)?;
 
-    Ok((runner, vm, return_values))
+    let result = RunResult {
+        return_values,
+        remaining_gas,
+        oracle_calls: hint_processor.oracle_calls().clone(),
+        stats,
+        timings: RunTimings {
+            total: run_started.elapsed(),
+            vm: run_time.saturating_sub(oracle_time),
+            oracle: oracle_time,
+        },
+    };
+    Ok((runner, vm, result))
 }
 
 fn additional_initialization(vm: &mut VirtualMachine, data_len: usize) -> Result<(), Error> {
@@ -245,51 +457,165 @@ fn additional_initialization(vm: &mut VirtualMachine, data_len: usize) -> Result
     Ok(())
 }
 
-#[allow(clippy::type_complexity)]
-fn build_hints_vec<'b>(
-    instructions: impl Iterator<Item = &'b Instruction>,
-) -> (Vec<(usize, Vec<Hint>)>, HashMap<usize, Vec<HintParams>>) {
-    let mut hints: Vec<(usize, Vec<Hint>)> = Vec::new();
-    let mut program_hints: HashMap<usize, Vec<HintParams>> = HashMap::new();
+/// Checks that every oracle called by the program is declared in the configuration, before
+/// running it.
+fn check_oracle_calls(
+    hints: &[(usize, Vec<Hint>)],
+    configuration: &Configuration,
+) -> Result<(), Error> {
+    let unknown = hints
+        .iter()
+        .flat_map(|(_, hints)| hints)
+        .filter_map(|hint| match hint {
+            Hint::Starknet(StarknetHint::Cheatcode { selector, .. }) => {
+                let bytes = selector.value.to_bytes_be().1;
+                Some(String::from_utf8(bytes).unwrap_or_else(|_| format!("{:#x}", selector.value)))
+            }
+            _ => None,
+        })
+        .filter(|selector| {
+            selector != PARTIAL_OUTPUT_SELECTOR
+                && !configuration
+                    .services
+                    .values()
+                    .any(|service| service.methods.contains_key(selector))
+        })
+        .collect::<BTreeSet<_>>();
 
-    let mut hint_offset = 0;
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::UnknownOracleMethods(unknown.into_iter().collect()))
+    }
+}
 
+/// Hints of `instructions`, by offset from the start of the program, `start` being the offset
+/// of the first instruction.
+fn instruction_hints<'b>(
+    instructions: impl Iterator<Item = &'b Instruction>,
+    start: usize,
+) -> Vec<(usize, Vec<Hint>)> {
+    let mut hints = Vec::new();
+    let mut hint_offset = start;
     for instruction in instructions {
         if !instruction.hints.is_empty() {
             hints.push((hint_offset, instruction.hints.clone()));
-            program_hints.insert(
-                hint_offset,
-                vec![HintParams {
-                    code: hint_offset.to_string(),
-                    accessible_scopes: Vec::new(),
-                    flow_tracking_data: FlowTrackingData {
-                        ap_tracking: ApTracking::default(),
-                        reference_ids: HashMap::new(),
-                    },
-                }],
-            );
         }
         hint_offset += instruction.body.op_size();
     }
+    hints
+}
+
+#[allow(clippy::type_complexity)]
+fn build_hints_vec(
+    hints: Vec<(usize, Vec<Hint>)>,
+) -> (Vec<(usize, Vec<Hint>)>, HashMap<usize, Vec<HintParams>>) {
+    let program_hints = hints
+        .iter()
+        .map(|(hint_offset, _)| {
+            let params = HintParams {
+                code: hint_offset.to_string(),
+                accessible_scopes: Vec::new(),
+                flow_tracking_data: FlowTrackingData {
+                    ap_tracking: ApTracking::default(),
+                    reference_ids: HashMap::new(),
+                },
+            };
+            (*hint_offset, vec![params])
+        })
+        .collect();
     (hints, program_hints)
 }
 
-/// Finds first function ending with `name_suffix`.
+/// Builtins provided by each layout, `dynamic` provides all of them.
+fn layout_builtins(layout: LayoutName) -> Option<&'static [BuiltinName]> {
+    use BuiltinName::*;
+    let builtins: &[BuiltinName] = match layout {
+        LayoutName::plain => &[],
+        LayoutName::small | LayoutName::dex => &[output, pedersen, range_check, ecdsa],
+        LayoutName::recursive => &[output, pedersen, range_check, bitwise],
+        LayoutName::recursive_large_output | LayoutName::recursive_with_poseidon => {
+            &[output, pedersen, range_check, bitwise, poseidon]
+        }
+        LayoutName::starknet => &[
+            output,
+            pedersen,
+            range_check,
+            ecdsa,
+            bitwise,
+            ec_op,
+            poseidon,
+        ],
+        LayoutName::starknet_with_keccak | LayoutName::all_cairo => &[
+            output,
+            pedersen,
+            range_check,
+            ecdsa,
+            bitwise,
+            ec_op,
+            keccak,
+            poseidon,
+        ],
+        LayoutName::all_solidity => &[output, pedersen, range_check, ecdsa, bitwise, ec_op, keccak],
+        LayoutName::dynamic => return None,
+    };
+    Some(builtins)
+}
+
+/// Layouts suggested when a program needs more builtins, from the smallest.
+pub const SUGGESTED_LAYOUTS: [LayoutName; 6] = [
+    LayoutName::small,
+    LayoutName::recursive,
+    LayoutName::recursive_with_poseidon,
+    LayoutName::starknet,
+    LayoutName::starknet_with_keccak,
+    LayoutName::all_cairo,
+];
+
+/// Fails with the builtins of the entry function that `layout` doesn't provide, and the smallest
+/// layout providing all of them.
+fn check_layout(layout: &LayoutName, builtins: &[BuiltinName]) -> Result<(), Error> {
+    let Some(provided) = layout_builtins(*layout) else {
+        return Ok(());
+    };
+    let missing = builtins
+        .iter()
+        .filter(|builtin| !provided.contains(builtin))
+        .map(|builtin| builtin.to_str().to_string())
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let suggestion = SUGGESTED_LAYOUTS.into_iter().find(|candidate| {
+        let provided = layout_builtins(*candidate).unwrap_or_default();
+        builtins.iter().all(|builtin| provided.contains(builtin))
+    });
+    Err(Error::LayoutMissingBuiltins {
+        layout: layout.to_str().to_string(),
+        missing,
+        suggestion: suggestion.map(|layout| layout.to_str().to_string()),
+    })
+}
+
+/// Finds the first function whose path ends with the path segments of `name_suffix`, so both
+/// `::main` and `hello::main` find `hello::main` but `ain` doesn't.
 fn find_function<'a>(
     sierra_program: &'a SierraProgram,
-    name_suffix: &'a str,
-) -> Result<&'a Function, RunnerError> {
+    name_suffix: &str,
+) -> Result<&'a Function, Error> {
+    let name_suffix = name_suffix.trim_start_matches("::");
+    let suffix = format!("::{name_suffix}");
     sierra_program
         .funcs
         .iter()
         .find(|f| {
             if let Some(name) = &f.id.debug_name {
-                name.ends_with(name_suffix)
+                format!("::{name}").ends_with(&suffix)
             } else {
                 false
             }
         })
-        .ok_or_else(|| RunnerError::MissingMain)
+        .ok_or_else(|| Error::EntryPointNotFound(name_suffix.to_string()))
 }
 
 /// Creates a list of instructions that will be appended to the program's bytecode.
@@ -338,7 +664,7 @@ fn create_proof_mode_header(builtin_count: i16, return_type_size: i16) -> Vec<In
 /// function, as well as the builtins required to execute the program.
 fn create_entry_code(
     sierra_program_registry: &ProgramRegistry<CoreType, CoreLibfunc>,
-    casm_program: &CairoProgram,
+    casm_program: &CasmProgram,
     type_sizes: &UnorderedHashMap<ConcreteTypeId, i16>,
     func: &Function,
     initial_gas: usize,
@@ -476,8 +802,7 @@ fn create_entry_code(
 
     let before_final_call = ctx.current_code_offset;
     let final_call_size = 3;
-    let offset = final_call_size
-        + casm_program.debug_info.sierra_statement_info[func.entry_point.0].code_offset;
+    let offset = final_call_size + casm_program.statement_offsets[func.entry_point.0];
 
     casm_extend! {ctx,
         call rel offset;
@@ -488,6 +813,115 @@ fn create_entry_code(
     Ok((ctx.instructions, builtins))
 }
 
+/// Checks that the `available_gas` can be enforced: the gas is only metered when the entry
+/// function takes the `GasBuiltin`, which the compiler adds to the programs built with
+/// `enable-gas`.
+fn check_gas(
+    sierra_program_registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    func: &Function,
+    available_gas: Option<usize>,
+) -> Result<(), Error> {
+    let uses_gas = func.signature.param_types.iter().any(|ty| {
+        get_info(sierra_program_registry, ty)
+            .is_some_and(|info| info.long_id.generic_id == GasBuiltinType::ID)
+    });
+    match (available_gas, uses_gas) {
+        (Some(_), false) => Err(Error::GasNotMetered {
+            name: func.id.to_string(),
+        }),
+        (None, true) => {
+            info!(
+                "{} uses gas, but no available gas is given: the run is not limited",
+                func.id
+            );
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `args` have the shape of the parameters of `func`: an array for each array or
+/// span parameter, and as many values as the size of the other ones, so a bad input is reported
+/// before the program runs.
+fn check_args(
+    sierra_program_registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    type_sizes: &UnorderedHashMap<ConcreteTypeId, i16>,
+    func: &Function,
+    proof_mode: bool,
+    args: &[FuncArg],
+) -> Result<(), Error> {
+    let (_, builtin_offset) = get_function_builtins(func, proof_mode);
+    let missing = |ty: &ConcreteTypeId| Error::InvalidArgument {
+        index: args.len(),
+        reason: format!("missing for the {ty} parameter"),
+    };
+    let mut args = args.iter().enumerate();
+    for ty in func.signature.param_types.iter() {
+        let info = get_info(sierra_program_registry, ty)
+            .ok_or_else(|| Error::NoInfoForType(ty.clone()))?;
+        let generic_ty = &info.long_id.generic_id;
+        if builtin_offset.contains_key(generic_ty)
+            || [SystemType::ID, GasBuiltinType::ID, SegmentArenaType::ID].contains(generic_ty)
+        {
+            continue;
+        }
+
+        let is_array = generic_ty == &ArrayType::ID
+            || ty
+                .debug_name
+                .as_ref()
+                .is_some_and(|name| name.starts_with("core::array::Span::<"));
+        if is_array {
+            match args.next() {
+                Some((_, FuncArg::Array(_))) => {}
+                Some((index, FuncArg::Single(value))) => {
+                    return Err(Error::InvalidArgument {
+                        index,
+                        reason: format!("expected an array for {ty}, got {value}"),
+                    })
+                }
+                None => return Err(missing(ty)),
+            }
+            continue;
+        }
+
+        // the values of a struct are flattened, with 2 cells for each of its arrays
+        let ty_size = type_sizes
+            .get(ty)
+            .cloned()
+            .ok_or_else(|| Error::NoTypeSizeForId(ty.clone()))?;
+        let mut size = 0;
+        while size < ty_size {
+            let Some((index, arg)) = args.next() else {
+                return Err(missing(ty));
+            };
+            size += match arg {
+                FuncArg::Array(_) if ty_size == 1 => {
+                    return Err(Error::InvalidArgument {
+                        index,
+                        reason: format!("expected a single value for the {ty} parameter"),
+                    })
+                }
+                FuncArg::Array(_) => 2,
+                FuncArg::Single(_) => 1,
+            };
+            if size > ty_size {
+                return Err(Error::InvalidArgument {
+                    index,
+                    reason: format!("the array overflows the {ty} parameter of size {ty_size}"),
+                });
+            }
+        }
+    }
+    match args.next() {
+        Some((index, _)) => Err(Error::InvalidArgument {
+            index,
+            reason: "the function has no parameter left for it".to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
 fn get_info<'a>(
     sierra_program_registry: &'a ProgramRegistry<CoreType, CoreLibfunc>,
     ty: &'a cairo_lang_sierra::ids::ConcreteTypeId,
@@ -623,9 +1057,12 @@ fn fetch_return_values(
                 panic_data_start,
                 (panic_data_end - panic_data_start).map_err(VirtualMachineError::Math)?,
             )?;
-            return Err(Error::RunPanic(
-                panic_data.iter().map(|c| *c.as_ref()).collect(),
-            ));
+            return Err(Error::RunPanic {
+                panic_data: panic_data.iter().map(|c| *c.as_ref()).collect(),
+                location: None,
+                last_oracle_call: None,
+                partial_output: None,
+            });
         } else {
             if return_values.len() < 3 {
                 return Err(Error::FailedToExtractReturnValues);
@@ -636,6 +1073,75 @@ fn fetch_return_values(
     Ok(return_values)
 }
 
+/// Finds the first statement of the trace that creates a `PanicResult::Err`, where the panic was
+/// raised before being propagated to the callers.
+fn find_panic_location(
+    trace: &[RelocatedTraceEntry],
+    casm_start: usize,
+    casm_program: &CasmProgram,
+    sierra_program: &SierraProgram,
+) -> Option<PanicLocation> {
+    let statements = &casm_program.statement_offsets;
+    trace
+        .iter()
+        .find_map(|entry| {
+            // The program is loaded at the start of the first segment, which is relocated to 1
+            let offset = entry.pc.checked_sub(1 + casm_start)?;
+            // Statements without instructions start at the offset of the next instruction
+            let first = statements.partition_point(|&code_offset| code_offset < offset);
+            let last = statements.partition_point(|&code_offset| code_offset <= offset);
+            (first..last).find(|idx| {
+                let Some(Statement::Invocation(invocation)) = sierra_program.statements.get(*idx)
+                else {
+                    return false;
+                };
+                invocation
+                    .libfunc_id
+                    .debug_name
+                    .as_deref()
+                    .is_some_and(|name| {
+                        name.starts_with("enum_init<core::panics::PanicResult")
+                            && name.ends_with(", 1>")
+                    })
+            })
+        })
+        .map(|statement_idx| PanicLocation {
+            statement_idx,
+            function: sierra_program
+                .funcs
+                .iter()
+                .filter(|f| f.entry_point.0 <= statement_idx)
+                .max_by_key(|f| f.entry_point.0)
+                .and_then(|f| f.id.debug_name.as_ref())
+                .map(|name| name.to_string()),
+        })
+}
+
+/// Reads the final value of the gas builtin, if the entry function returns one.
+fn fetch_remaining_gas(
+    main_ret_types: &[ConcreteTypeId],
+    type_sizes: &UnorderedHashMap<ConcreteTypeId, i16>,
+    vm: &VirtualMachine,
+) -> Result<Option<usize>, Error> {
+    let sizes = main_ret_types
+        .iter()
+        .map(|id| type_sizes.get(id).cloned().unwrap_or_default() as usize)
+        .collect::<Vec<_>>();
+    let return_values = vm.get_return_values(sizes.iter().sum())?;
+    let mut offset = 0;
+    for (id, size) in main_ret_types.iter().zip(sizes) {
+        if id.debug_name.as_deref() == Some("GasBuiltin") {
+            let gas = return_values
+                .get(offset)
+                .and_then(|value| value.get_int())
+                .ok_or(Error::FailedToExtractReturnValues)?;
+            return Ok(gas.to_biguint().to_usize());
+        }
+        offset += size;
+    }
+    Ok(None)
+}
+
 // Calculates builtins' final_stack setting each stop_ptr
 // Calling this function is a must if either air_public_input or cairo_pie are needed
 fn finalize_builtins(
@@ -681,3 +1187,87 @@ fn finalize_builtins(
     vm.builtins_final_stack_from_stack_pointer_dict(&builtin_name_to_stack_pointer, proof_mode)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::find_panic_location;
+    use crate::casm::CasmProgram;
+    use crate::PanicLocation;
+    use cairo_lang_sierra::ids::{ConcreteLibfuncId, FunctionId};
+    use cairo_lang_sierra::program::{
+        Function, FunctionSignature, Invocation, Program, Statement, StatementIdx,
+    };
+    use cairo_vm::vm::trace::trace_entry::RelocatedTraceEntry;
+    use serde_json::json;
+
+    fn invocation(libfunc: &str) -> Statement {
+        Statement::Invocation(Invocation {
+            libfunc_id: ConcreteLibfuncId::from_string(libfunc),
+            args: vec![],
+            branches: vec![],
+        })
+    }
+
+    fn function(name: &str, entry_point: usize) -> Function {
+        Function {
+            id: FunctionId::from_string(name),
+            signature: FunctionSignature {
+                param_types: vec![],
+                ret_types: vec![],
+            },
+            params: vec![],
+            entry_point: StatementIdx(entry_point),
+        }
+    }
+
+    #[test]
+    fn it_finds_the_statement_raising_the_panic() {
+        let panic = "enum_init<core::panics::PanicResult::<((),)>, 1>";
+        let sierra_program = Program {
+            type_declarations: vec![],
+            libfunc_declarations: vec![],
+            statements: vec![
+                invocation("function_call<user@hello::check>"),
+                invocation(panic),
+                Statement::Return(vec![]),
+                invocation("drop<felt252>"),
+                invocation(panic),
+                Statement::Return(vec![]),
+            ],
+            funcs: vec![function("hello::main", 0), function("hello::check", 3)],
+        };
+        // `drop` has no instructions, it starts at the offset of the next statement
+        let casm_program: CasmProgram = serde_json::from_value(json!({
+            "version": 1,
+            "sierra_hash": "",
+            "bytecode": [],
+            "hints": [],
+            "statement_offsets": [0, 2, 4, 5, 5, 7],
+        }))
+        .unwrap();
+        let trace = |offsets: &[usize]| {
+            offsets
+                .iter()
+                .map(|offset| RelocatedTraceEntry {
+                    pc: 1 + offset,
+                    ap: 0,
+                    fp: 0,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the panic raised in `check` is then propagated by `main`
+        let location = find_panic_location(&trace(&[0, 5, 2]), 0, &casm_program, &sierra_program);
+        assert_eq!(
+            location,
+            Some(PanicLocation {
+                statement_idx: 4,
+                function: Some("hello::check".to_string()),
+            })
+        );
+        assert_eq!(
+            find_panic_location(&trace(&[0, 4]), 0, &casm_program, &sierra_program),
+            None
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use cairo_lang_casm::hints::Hint;
+use cairo_lang_sierra::program::Program as SierraProgram;
+use cairo_lang_sierra_to_casm::compiler::CairoProgram;
+use cairo_vm::Felt252;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+/// Version of the format of the CASM files, bumped when they can no longer be read.
+const CASM_FORMAT_VERSION: u32 = 1;
+
+/// The CASM of a Sierra program, compiled by the runner or read from a `--casm-file`.
+///
+/// The Sierra program is still needed to run it: it gives the signature of the entry function,
+/// the size of its types and the location of a panic.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CasmProgram {
+    version: u32,
+    /// SHA-256 of the Sierra program it was compiled from.
+    sierra_hash: String,
+    /// Encoded instructions, as hexadecimal felts.
+    bytecode: Vec<String>,
+    /// The bytecode, as read by the VM.
+    #[serde(skip)]
+    pub words: Vec<Felt252>,
+    /// Hints of the instructions, by offset in the bytecode.
+    pub hints: Vec<(usize, Vec<Hint>)>,
+    /// Offset in the bytecode of the code of each Sierra statement.
+    pub statement_offsets: Vec<usize>,
+}
+
+impl CasmProgram {
+    pub fn new(sierra_program: &SierraProgram, casm_program: &CairoProgram) -> Self {
+        let mut hints = Vec::new();
+        let mut offset = 0;
+        for instruction in &casm_program.instructions {
+            if !instruction.hints.is_empty() {
+                hints.push((offset, instruction.hints.clone()));
+            }
+            offset += instruction.body.op_size();
+        }
+        let words = casm_program
+            .instructions
+            .iter()
+            .flat_map(|instruction| instruction.assemble().encode())
+            .map(|word| Felt252::from(&word))
+            .collect::<Vec<_>>();
+        Self {
+            version: CASM_FORMAT_VERSION,
+            sierra_hash: sierra_hash(sierra_program),
+            bytecode: words.iter().map(Felt252::to_hex_string).collect(),
+            words,
+            hints,
+            statement_offsets: casm_program
+                .debug_info
+                .sierra_statement_info
+                .iter()
+                .map(|statement| statement.code_offset)
+                .collect(),
+        }
+    }
+
+    /// Reads the CASM file at `path`, checking that it was compiled from `sierra_program`.
+    pub fn load(path: &Path, sierra_program: &SierraProgram) -> Result<Self, Error> {
+        let parse_error = |reason: String| Error::CasmParse {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let content = std::fs::read(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::FileNotFound(path.to_path_buf()),
+            _ => Error::IO(e),
+        })?;
+        let mut casm: Self =
+            serde_json::from_slice(&content).map_err(|e| parse_error(e.to_string()))?;
+        if casm.version != CASM_FORMAT_VERSION {
+            return Err(parse_error(format!(
+                "it has the format version {}, this runner reads version {CASM_FORMAT_VERSION}",
+                casm.version
+            )));
+        }
+        if casm.sierra_hash != sierra_hash(sierra_program) {
+            return Err(parse_error(
+                "it was compiled from another Sierra program, write it again with --casm-output"
+                    .to_string(),
+            ));
+        }
+        if casm.statement_offsets.len() != sierra_program.statements.len() {
+            return Err(parse_error(format!(
+                "it has the offsets of {} statements, the Sierra program has {}",
+                casm.statement_offsets.len(),
+                sierra_program.statements.len()
+            )));
+        }
+        casm.words = casm
+            .bytecode
+            .iter()
+            .map(|word| {
+                Felt252::from_hex(word)
+                    .map_err(|_| parse_error(format!("{word:?} is not an hexadecimal felt")))
+            })
+            .collect::<Result<_, _>>()?;
+        let len = casm.words.len();
+        if let Some((offset, _)) = casm.hints.iter().find(|(offset, _)| *offset >= len) {
+            return Err(parse_error(format!(
+                "a hint is at offset {offset}, past the {len} words of the bytecode"
+            )));
+        }
+        Ok(casm)
+    }
+
+    /// Writes the CASM to `path`, to be run with `--casm-file` without compiling the Sierra again.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut file, self).map_err(|e| Error::IO(e.into()))?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Hash of the Sierra program, including the debug names the panic locations are reported with.
+fn sierra_hash(sierra_program: &SierraProgram) -> String {
+    let digest = Sha256::digest(sierra_program.to_string().as_bytes());
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,244 @@
+//! Conversion of the oracle requests and responses between their Cairo serde representation
+//! and JSON, as done by the hint processor for every oracle call.
+//!
+//! Unlike the functions of `cairo_proto_serde`, which panic on data that doesn't match the
+//! `Oracle.lock`, these functions check their input first and return a [`CodecError`], so they
+//! can be given arbitrary data.
+
+use cairo_proto_serde::configuration::{
+    Configuration, FieldType, MethodDeclaration, PrimitiveType,
+};
+use cairo_proto_serde::schema::{type_name, validate};
+use cairo_proto_serde::{deserialize_cairo_serde, serialize_cairo_serde};
+use cairo_vm::Felt252;
+use num_bigint::BigInt;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("unknown oracle method {0}")]
+    UnknownMethod(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("response is not JSON: {0}")]
+    MalformedResponse(#[from] serde_json::Error),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Converts the Cairo serde `inputs` of a call to the `selector` oracle method into the JSON
+/// request sent to the oracle server.
+pub fn serialize_request(
+    configuration: &Configuration,
+    selector: &str,
+    inputs: &[Felt252],
+) -> Result<Value, CodecError> {
+    let method = method(configuration, selector)?;
+    request_value(configuration, method, inputs).map_err(CodecError::InvalidRequest)
+}
+
+/// Converts the JSON `response` of the `selector` oracle method, without the `result`
+/// envelope, into the Cairo serde values returned to the program.
+pub fn deserialize_response(
+    configuration: &Configuration,
+    selector: &str,
+    response: &[u8],
+) -> Result<Vec<Felt252>, CodecError> {
+    let method = method(configuration, selector)?;
+    let response = serde_json::from_slice(response)?;
+    response_felts(configuration, method, &response).map_err(CodecError::InvalidResponse)
+}
+
+fn method<'a>(
+    configuration: &'a Configuration,
+    selector: &str,
+) -> Result<&'a MethodDeclaration, CodecError> {
+    configuration
+        .services
+        .values()
+        .find_map(|service| service.methods.get(selector))
+        .ok_or_else(|| CodecError::UnknownMethod(selector.to_string()))
+}
+
+pub(crate) fn request_value(
+    configuration: &Configuration,
+    method: &MethodDeclaration,
+    inputs: &[Felt252],
+) -> Result<Value, String> {
+    check(configuration, &method.input, &mut &*inputs)?;
+    Ok(deserialize_cairo_serde(
+        configuration,
+        &method.input,
+        &mut &*inputs,
+    ))
+}
+
+pub(crate) fn response_felts(
+    configuration: &Configuration,
+    method: &MethodDeclaration,
+    response: &Value,
+) -> Result<Vec<Felt252>, String> {
+    validate(configuration, &method.output, response)?;
+    Ok(serialize_cairo_serde(
+        configuration,
+        &method.output,
+        response,
+    ))
+}
+
+/// Walks `inputs` the way `deserialize_cairo_serde` does, failing where it would panic.
+fn check(
+    configuration: &Configuration,
+    ty: &FieldType,
+    inputs: &mut &[Felt252],
+) -> Result<(), String> {
+    match ty {
+        FieldType::Primitive(ty) => check_primitive(ty, inputs),
+        FieldType::Message(name) => {
+            let fields = configuration
+                .messages
+                .get(name)
+                .ok_or_else(|| format!("message {name} is not in the Oracle.lock"))?;
+            fields
+                .iter()
+                .try_for_each(|field| check(configuration, &field.ty, inputs))
+        }
+        FieldType::Enum(name) => {
+            let index = next_u64(inputs, ty)?;
+            let variants = configuration
+                .enums
+                .get(name)
+                .map_or(u64::from(u32::MAX) + 1, |mappings| mappings.len() as u64);
+            if index < variants {
+                Ok(())
+            } else {
+                Err(format!("{index} is not a variant of {name}"))
+            }
+        }
+        FieldType::Option(inner) => match next_u64(inputs, ty)? {
+            0 => check(configuration, inner, inputs),
+            _ => Ok(()),
+        },
+        FieldType::Array(element) => {
+            let len = next_u64(inputs, ty)?;
+            // every element takes at least a value, this bounds the work on a corrupted length
+            if len > inputs.len() as u64 {
+                return Err(format!("array of {len} elements is longer than the input"));
+            }
+            (0..len).try_for_each(|_| check(configuration, element, inputs))
+        }
+    }
+}
+
+fn check_primitive(ty: &PrimitiveType, inputs: &mut &[Felt252]) -> Result<(), String> {
+    let num = next(inputs, &FieldType::Primitive(ty.clone()))?;
+    let valid = match ty {
+        PrimitiveType::FELT252 => true,
+        PrimitiveType::U64 => u64::try_from(&num).is_ok(),
+        PrimitiveType::U32 => u32::try_from(&num).is_ok(),
+        PrimitiveType::I32 => i32::try_from(&num).is_ok(),
+        PrimitiveType::I64 => i64::try_from(&num).is_ok(),
+        PrimitiveType::BOOL => num == BigInt::from(0) || num == BigInt::from(1),
+        // the byte array is read from the rest of the input, without consuming it
+        PrimitiveType::BYTEARRAY => match inputs.split_last() {
+            Some((_, words)) => {
+                let bytes = words.iter().flat_map(|word| word.to_bytes_be()).collect();
+                String::from_utf8(bytes).is_ok()
+            }
+            None => false,
+        },
+    };
+    if valid {
+        Ok(())
+    } else {
+        let ty = FieldType::Primitive(ty.clone());
+        Err(format!("{num} is not a valid {}", type_name(&ty)))
+    }
+}
+
+fn next(inputs: &mut &[Felt252], ty: &FieldType) -> Result<BigInt, String> {
+    let (first, rest) = inputs
+        .split_first()
+        .ok_or_else(|| format!("the input ends before {}", type_name(ty)))?;
+    *inputs = rest;
+    Ok(first.to_bigint())
+}
+
+/// Reads the tag of an option or the length of an array.
+fn next_u64(inputs: &mut &[Felt252], ty: &FieldType) -> Result<u64, String> {
+    let num = next(inputs, ty)?;
+    u64::try_from(&num)
+        .map_err(|_| format!("{num} is not a valid tag or length of {}", type_name(ty)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deserialize_response, serialize_request, CodecError};
+    use cairo_proto_serde::configuration::{
+        Configuration, Field, FieldType, MethodDeclaration, PrimitiveType, Service,
+    };
+    use cairo_vm::Felt252;
+    use std::collections::HashMap;
+
+    fn configuration() -> Configuration {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "oracle::Request".into(),
+            vec![Field {
+                name: "n".into(),
+                ty: FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::U32))),
+                number: Some(1),
+            }],
+        );
+        configuration.services.insert(
+            "SqrtOracle".into(),
+            Service {
+                methods: HashMap::from([(
+                    "sqrt".into(),
+                    MethodDeclaration {
+                        input: FieldType::Message("oracle::Request".into()),
+                        output: FieldType::Message("oracle::Request".into()),
+                        grpc_path: None,
+                    },
+                )]),
+            },
+        );
+        configuration
+    }
+
+    #[test]
+    fn it_round_trips_a_request_and_rejects_invalid_inputs() {
+        let configuration = configuration();
+        let inputs = [2, 16, 25].map(Felt252::from);
+
+        let request = serialize_request(&configuration, "sqrt", &inputs).unwrap();
+        let response = deserialize_response(&configuration, "sqrt", request.to_string().as_bytes());
+        assert_eq!(response.unwrap(), inputs);
+
+        assert!(matches!(
+            serialize_request(&configuration, "sqrt", &inputs[..2]),
+            Err(CodecError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            serialize_request(
+                &configuration,
+                "sqrt",
+                &[Felt252::from(1), -Felt252::from(1)]
+            ),
+            Err(CodecError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            deserialize_response(&configuration, "sqrt", b"{\"n\": [-1]}"),
+            Err(CodecError::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            deserialize_response(&configuration, "sqrt", b"\xff"),
+            Err(CodecError::MalformedResponse(_))
+        ));
+        assert!(matches!(
+            serialize_request(&configuration, "cbrt", &inputs),
+            Err(CodecError::UnknownMethod(_))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Checks that oracle servers speak the protocol the hint processor expects, without running a
+//! program: every method of the `Oracle.lock` is sent representative requests, and its responses
+//! must be accepted as the hint processor would accept them during a run.
+
+use cairo_proto_serde::configuration::{
+    Configuration, FieldType, MethodDeclaration, PrimitiveType,
+};
+use serde_json::{json, Map, Value};
+
+use crate::codec::response_felts;
+use crate::rpc_hint_processor::{input_hash, new_session, run_context_envelope};
+use crate::transport::{self, new_trace_id, OracleServer, RequestTrace};
+use crate::{Error, OracleConfig};
+
+/// Depth below which the optional fields and the arrays of a populated request are left empty,
+/// so a recursive message gives a finite request.
+const MAX_DEPTH: usize = 4;
+
+/// Request sent to a method by [`verify_servers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestCase {
+    /// Every field at its default: zeros, empty strings, `None` and empty arrays.
+    Zeroed,
+    /// Every field set: ones, non-empty strings, the last value of the enums, and the options
+    /// and arrays holding an element.
+    Populated,
+}
+
+impl RequestCase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestCase::Zeroed => "zeroed",
+            RequestCase::Populated => "populated",
+        }
+    }
+}
+
+/// Outcome of a request sent to an oracle method.
+#[derive(Debug, Clone)]
+pub struct MethodCheck {
+    pub service: String,
+    pub method: String,
+    pub case: RequestCase,
+    pub request: Value,
+    /// The response, or why the hint processor would fail the run on it.
+    pub outcome: Result<Value, String>,
+}
+
+impl MethodCheck {
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// Sends the [`RequestCase`]s to every method of `configuration`, or only to `methods` when it is
+/// not empty, given by name or as `Service.method`, and checks the responses.
+///
+/// The servers are resolved and probed as for a run, a failure there is returned as the error,
+/// while the failure of a request is reported in its [`MethodCheck`].
+pub fn verify_servers(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    methods: &[String],
+) -> Result<Vec<MethodCheck>, Error> {
+    let servers = transport::connect(configuration, oracle_config, false)?;
+    let trace_id = new_trace_id();
+    let session = new_session();
+    let mut declared = configuration
+        .services
+        .iter()
+        .flat_map(|(service, declarations)| {
+            declarations
+                .methods
+                .iter()
+                .map(move |(method, declaration)| (service.as_str(), method.as_str(), declaration))
+        })
+        .filter(|(service, method, _)| {
+            methods.is_empty()
+                || methods
+                    .iter()
+                    .any(|name| name == method || *name == format!("{service}.{method}"))
+        })
+        .collect::<Vec<_>>();
+    declared.sort_by_key(|(service, method, _)| (*service, *method));
+
+    let mut checks = Vec::new();
+    for (service, method, declaration) in declared {
+        for case in [RequestCase::Zeroed, RequestCase::Populated] {
+            let request = sample_value(configuration, &declaration.input, case, 0);
+            let trace = RequestTrace::new(&trace_id, checks.len());
+            let outcome = match servers.get(method) {
+                Some(server) => check(
+                    configuration,
+                    server,
+                    service,
+                    method,
+                    declaration,
+                    &request,
+                    &session,
+                    &trace,
+                ),
+                None => Err(format!("no server is configured for {service}.{method}")),
+            };
+            checks.push(MethodCheck {
+                service: service.to_string(),
+                method: method.to_string(),
+                case,
+                request,
+                outcome,
+            });
+        }
+    }
+    Ok(checks)
+}
+
+/// Sends `request` to `method` the way a run would, and checks that its response converts to the
+/// felts of the `Response` of the method.
+#[allow(clippy::too_many_arguments)]
+fn check(
+    configuration: &Configuration,
+    server: &OracleServer,
+    service: &str,
+    method: &str,
+    declaration: &MethodDeclaration,
+    request: &Value,
+    session: &str,
+    trace: &RequestTrace,
+) -> Result<Value, String> {
+    let options = configuration.service_options.get(service);
+    let envelope = options
+        .is_some_and(|options| options.run_context)
+        .then(|| run_context_envelope(request, session, &input_hash(&[])));
+    let sent = envelope.as_ref().unwrap_or(request);
+    let response = if options.is_some_and(|options| options.streaming) {
+        server
+            .open_stream(method, sent, session, trace)
+            .and_then(|mut stream| stream.next())
+    } else {
+        server
+            .call(configuration, declaration, method, sent, trace)
+            .map(|(response, _)| response)
+    };
+    let response = response.map_err(|e| e.to_string())?;
+    response_felts(configuration, declaration, &response)
+        .map_err(|detail| format!("invalid response {response}: {detail}"))?;
+    Ok(response)
+}
+
+/// The JSON value of type `ty` sent in a request of `case`, as the hint processor encodes it.
+fn sample_value(
+    configuration: &Configuration,
+    ty: &FieldType,
+    case: RequestCase,
+    depth: usize,
+) -> Value {
+    let populated = case == RequestCase::Populated;
+    match ty {
+        FieldType::Primitive(ty) => match ty {
+            PrimitiveType::FELT252 => json!(if populated { "1" } else { "0" }),
+            PrimitiveType::U64 | PrimitiveType::U32 | PrimitiveType::I32 | PrimitiveType::I64 => {
+                json!(u8::from(populated))
+            }
+            PrimitiveType::BOOL => json!(populated),
+            PrimitiveType::BYTEARRAY => json!(if populated { "oracle" } else { "" }),
+        },
+        FieldType::Message(name) => {
+            let fields = configuration.messages.get(name).into_iter().flatten();
+            let fields = fields
+                .map(|field| {
+                    let value = sample_value(configuration, &field.ty, case, depth + 1);
+                    (field.name.clone(), value)
+                })
+                .collect::<Map<_, _>>();
+            Value::Object(fields)
+        }
+        FieldType::Enum(name) => {
+            let mappings = configuration.enums.get(name);
+            let mapping = match case {
+                RequestCase::Zeroed => mappings.and_then(|mappings| mappings.first()),
+                RequestCase::Populated => mappings.and_then(|mappings| mappings.last()),
+            };
+            json!(mapping.map_or(0, |mapping| mapping.nb))
+        }
+        FieldType::Option(inner) if populated && depth < MAX_DEPTH => {
+            sample_value(configuration, inner, case, depth + 1)
+        }
+        FieldType::Option(_) => Value::Null,
+        FieldType::Array(element) if populated && depth < MAX_DEPTH => {
+            json!([sample_value(configuration, element, case, depth + 1)])
+        }
+        FieldType::Array(_) => json!([]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample_value, RequestCase};
+    use cairo_proto_serde::configuration::{Configuration, Field, FieldType, PrimitiveType};
+    use cairo_proto_serde::schema::validate;
+    use serde_json::json;
+
+    fn field(name: &str, ty: FieldType) -> Field {
+        Field {
+            name: name.into(),
+            ty,
+            number: None,
+        }
+    }
+
+    #[test]
+    fn it_samples_valid_requests() {
+        let mut configuration = Configuration::default();
+        let node = FieldType::Message("Node".into());
+        let felt252 = FieldType::Primitive(PrimitiveType::FELT252);
+        configuration.messages.insert(
+            "Node".into(),
+            vec![
+                field("value", FieldType::Primitive(PrimitiveType::U64)),
+                field("label", FieldType::Primitive(PrimitiveType::BYTEARRAY)),
+                field("next", FieldType::Option(Box::new(node.clone()))),
+                field("tags", FieldType::Array(Box::new(felt252))),
+            ],
+        );
+
+        let zeroed = sample_value(&configuration, &node, RequestCase::Zeroed, 0);
+        assert_eq!(
+            zeroed,
+            json!({ "value": 0, "label": "", "next": null, "tags": [] })
+        );
+
+        // the recursion stops at the depth limit
+        let populated = sample_value(&configuration, &node, RequestCase::Populated, 0);
+        assert_eq!(populated["next"]["next"]["value"], json!(1));
+        assert_eq!(populated["next"]["next"]["next"], json!(null));
+        assert_eq!(populated["tags"], json!(["1"]));
+        for request in [zeroed, populated] {
+            validate(&configuration, &node, &request).unwrap();
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cairo_proto_serde::configuration::Configuration;
+use cairo_proto_serde::schema::schema_hash;
+use cairo_vm::Felt252;
+use log::warn;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// How the responses cached on disk are used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OracleCacheMode {
+    /// Calls are answered from the cache, new responses are not added to it.
+    Read,
+    /// Calls always reach the oracles, their responses are added to the cache.
+    Write,
+    /// Calls are answered from the cache, the others reach the oracles and are added to it.
+    #[default]
+    ReadWrite,
+    /// The cache is neither read nor written.
+    Off,
+}
+
+/// Responses of the oracle calls kept on disk across runs, one JSON file per request under
+/// `<dir>/<service>/<schema hash>/`.
+///
+/// The schema hash of the service is part of the key, so a response cached before its messages
+/// changed is never read back; the directories of the previous hashes are removed by the runs
+/// that write to the cache.
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    mode: OracleCacheMode,
+}
+
+impl DiskCache {
+    pub fn open(dir: &Path, mode: OracleCacheMode, configuration: &Configuration) -> Option<Self> {
+        if mode == OracleCacheMode::Off {
+            return None;
+        }
+        let cache = Self {
+            dir: dir.to_path_buf(),
+            mode,
+        };
+        if cache.writes() {
+            for service in configuration.services.keys() {
+                cache.remove_stale(configuration, service);
+            }
+        }
+        Some(cache)
+    }
+
+    fn reads(&self) -> bool {
+        matches!(
+            self.mode,
+            OracleCacheMode::Read | OracleCacheMode::ReadWrite
+        )
+    }
+
+    fn writes(&self) -> bool {
+        matches!(
+            self.mode,
+            OracleCacheMode::Write | OracleCacheMode::ReadWrite
+        )
+    }
+
+    /// Returns the cached response of the request, `None` when it is missing or can't be read.
+    pub fn get(
+        &self,
+        configuration: &Configuration,
+        service: &str,
+        selector: &str,
+        inputs: &[Felt252],
+    ) -> Option<Value> {
+        if !self.reads() {
+            return None;
+        }
+        let path = self.entry_path(configuration, service, selector, inputs)?;
+        let content = fs::read(&path).ok()?;
+        let response = serde_json::from_slice::<Value>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|mut entry| {
+                let response = entry.get_mut("response").map(Value::take);
+                response.ok_or_else(|| "no `response` field".to_string())
+            });
+        match response {
+            Ok(response) => Some(response),
+            Err(e) => {
+                warn!(
+                    "ignoring the invalid oracle cache entry {}: {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    /// Adds the response of the request to the cache. A failure is only logged, the run goes on
+    /// without it.
+    pub fn insert(
+        &self,
+        configuration: &Configuration,
+        service: &str,
+        selector: &str,
+        inputs: &[Felt252],
+        request: &Value,
+        response: &Value,
+    ) {
+        if !self.writes() {
+            return;
+        }
+        let Some(path) = self.entry_path(configuration, service, selector, inputs) else {
+            return;
+        };
+        let entry = json!({
+            "service": service,
+            "method": selector,
+            "request": request,
+            "response": response,
+        });
+        // written to a temporary file first, so a concurrent run never reads a partial entry
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&tmp, entry.to_string()))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(e) = written {
+            warn!(
+                "failed to write the oracle cache entry {}: {e}",
+                path.display()
+            );
+        }
+    }
+
+    /// The file of a request: the SHA-256 of its serialized felts, under the schema hash of the
+    /// service.
+    fn entry_path(
+        &self,
+        configuration: &Configuration,
+        service: &str,
+        selector: &str,
+        inputs: &[Felt252],
+    ) -> Option<PathBuf> {
+        let schema = schema_hash(configuration, service)?;
+        let mut hasher = Sha256::new();
+        hasher.update(selector.as_bytes());
+        for felt in inputs {
+            hasher.update(felt.to_bytes_be());
+        }
+        let digest = hasher.finalize();
+        let key = digest
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        Some(
+            self.dir
+                .join(service)
+                .join(schema)
+                .join(format!("{selector}-{key}.json")),
+        )
+    }
+
+    /// Removes the entries of `service` cached under another schema hash than its current one.
+    fn remove_stale(&self, configuration: &Configuration, service: &str) {
+        let Some(schema) = schema_hash(configuration, service) else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(self.dir.join(service)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name() != schema.as_str() && entry.path().is_dir() {
+                if let Err(e) = fs::remove_dir_all(entry.path()) {
+                    warn!(
+                        "failed to remove the stale oracle cache {}: {e}",
+                        entry.path().display()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiskCache, OracleCacheMode};
+    use cairo_proto_serde::configuration::{
+        Configuration, FieldType, MethodDeclaration, PrimitiveType, Service,
+    };
+    use cairo_proto_serde::schema::schema_hash;
+    use cairo_vm::Felt252;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A `SqrtOracle.sqrt` method answering with `output`, whose type gives the schema hash.
+    fn configuration(output: PrimitiveType) -> Configuration {
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(output),
+            grpc_path: None,
+        };
+        let mut configuration = Configuration::default();
+        configuration.services.insert(
+            "SqrtOracle".into(),
+            Service {
+                methods: HashMap::from([("sqrt".to_string(), method)]),
+            },
+        );
+        configuration
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cairo-hints-disk-cache-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn get(cache: &DiskCache, configuration: &Configuration, n: u64) -> Option<Value> {
+        cache.get(configuration, "SqrtOracle", "sqrt", &[Felt252::from(n)])
+    }
+
+    fn insert(cache: &DiskCache, configuration: &Configuration, n: u64) {
+        let (request, response) = (json!({ "n": n }), json!({ "n": n * n }));
+        cache.insert(
+            configuration,
+            "SqrtOracle",
+            "sqrt",
+            &[Felt252::from(n)],
+            &request,
+            &response,
+        );
+    }
+
+    #[test]
+    fn it_reads_and_writes_according_to_the_mode() {
+        let dir = cache_dir("modes");
+        let configuration = configuration(PrimitiveType::U64);
+        let open = |mode| DiskCache::open(&dir, mode, &configuration);
+        assert!(open(OracleCacheMode::Off).is_none());
+
+        let read = open(OracleCacheMode::Read).unwrap();
+        insert(&read, &configuration, 2);
+        assert_eq!(get(&read, &configuration, 2), None);
+
+        let write = open(OracleCacheMode::Write).unwrap();
+        insert(&write, &configuration, 2);
+        assert_eq!(get(&write, &configuration, 2), None);
+        assert_eq!(get(&read, &configuration, 2), Some(json!({ "n": 4 })));
+
+        let read_write = open(OracleCacheMode::ReadWrite).unwrap();
+        assert_eq!(get(&read_write, &configuration, 2), Some(json!({ "n": 4 })));
+        insert(&read_write, &configuration, 3);
+        assert_eq!(get(&read_write, &configuration, 3), Some(json!({ "n": 9 })));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_misses_on_invalid_entries() {
+        let dir = cache_dir("invalid");
+        let configuration = configuration(PrimitiveType::U64);
+        let cache = DiskCache::open(&dir, OracleCacheMode::ReadWrite, &configuration).unwrap();
+        insert(&cache, &configuration, 2);
+        let path = cache
+            .entry_path(&configuration, "SqrtOracle", "sqrt", &[Felt252::from(2)])
+            .unwrap();
+        for content in ["not json", "[]", "\"x\"", r#"{"request": {"n": 2}}"#] {
+            fs::write(&path, content).unwrap();
+            assert_eq!(get(&cache, &configuration, 2), None, "{content}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_removes_the_entries_of_previous_schemas() {
+        let dir = cache_dir("stale");
+        let previous = configuration(PrimitiveType::U64);
+        let current = configuration(PrimitiveType::BOOL);
+        let cache = DiskCache::open(&dir, OracleCacheMode::Write, &previous).unwrap();
+        insert(&cache, &previous, 2);
+        let previous_hash = schema_hash(&previous, "SqrtOracle").unwrap();
+        let previous_dir = dir.join("SqrtOracle").join(previous_hash);
+        assert!(previous_dir.is_dir());
+
+        // only the runs writing to the cache clean it up
+        DiskCache::open(&dir, OracleCacheMode::Read, &current).unwrap();
+        assert!(previous_dir.is_dir());
+        DiskCache::open(&dir, OracleCacheMode::ReadWrite, &current).unwrap();
+        assert!(!previous_dir.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
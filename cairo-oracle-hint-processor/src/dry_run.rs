@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use cairo_proto_serde::configuration::{Configuration, MethodDeclaration};
+use cairo_proto_serde::protobuf::decode_message;
+use serde_json::Value;
+
+/// Hook answering an oracle call in place of the oracle server, given the method and request.
+pub type MockResponse = fn(&Configuration, &MethodDeclaration, &Value) -> Value;
+
+/// Answers every call with the default `Response`: zeros, empty strings, `None` and empty arrays.
+pub fn zeroed_response(
+    configuration: &Configuration,
+    method: &MethodDeclaration,
+    _request: &Value,
+) -> Value {
+    decode_message(configuration, &method.output, &[])
+        .expect("the response type of a declared method is a message")
+}
+
+/// An oracle call answered by a `MockResponse`.
+#[derive(Debug, Clone)]
+pub struct MockedCall {
+    pub service: String,
+    pub selector: String,
+    pub request: Value,
+}
+
+/// Prints the number of calls made to each oracle method, with the first request as a sample.
+pub fn print_call_inventory(calls: &[MockedCall]) {
+    let mut inventory: BTreeMap<(&str, &str), (usize, &Value)> = BTreeMap::new();
+    for call in calls {
+        inventory
+            .entry((&call.service, &call.selector))
+            .or_insert((0, &call.request))
+            .0 += 1;
+    }
+
+    eprintln!("Oracle calls: {}", calls.len());
+    if inventory.is_empty() {
+        return;
+    }
+    let service_width = inventory
+        .keys()
+        .map(|(s, _)| s.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let method_width = inventory
+        .keys()
+        .map(|(_, m)| m.len())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+    eprintln!(
+        "{:service_width$}  {:method_width$}  {:>5}  sample payload",
+        "service", "method", "count"
+    );
+    for ((service, method), (count, sample)) in inventory {
+        eprintln!("{service:service_width$}  {method:method_width$}  {count:>5}  {sample}");
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use cairo_vm::vm::runners::cairo_runner::CairoRunner;
+use cairo_vm::vm::vm_core::VirtualMachine;
+use cairo_vm::Felt252;
+use serde_json::{json, Value};
+
+use crate::transcript::TranscriptEntry;
+use crate::{Error, FileWriter, FuncArg, PanicLocation};
+
+/// Arguments of the run that panicked, in the format of `--input-file`.
+pub const DUMP_INPUT_FILE: &str = "input.json";
+/// Oracle calls made by the run, in the format of `--oracle-replay`.
+pub const DUMP_TRANSCRIPT_FILE: &str = "transcript.jsonl";
+/// Panic data, location, entry point and final registers of the run.
+pub const DUMP_PANIC_FILE: &str = "panic.json";
+/// Relocated memory of the run, in the cairo-lang binary format of `--memory-file`.
+pub const DUMP_MEMORY_FILE: &str = "memory.bin";
+/// Relocated trace of the run, only when it is recorded.
+pub const DUMP_TRACE_FILE: &str = "trace.bin";
+
+/// State of a run that panicked, written once the VM has stopped and its memory is relocated.
+pub(crate) struct PanicDump<'a> {
+    pub entry_func_name: &'a str,
+    pub args: &'a [FuncArg],
+    pub panic_data: &'a [Felt252],
+    pub location: Option<&'a PanicLocation>,
+    pub calls: &'a [TranscriptEntry],
+}
+
+impl PanicDump<'_> {
+    /// Writes the dump to `dir`, created if needed. Its input and transcript replay the run:
+    /// `--input-file <dir>/input.json --oracle-replay <dir>/transcript.jsonl`.
+    pub fn write(
+        &self,
+        dir: &Path,
+        runner: &CairoRunner,
+        vm: &VirtualMachine,
+    ) -> Result<(), Error> {
+        fs::create_dir_all(dir)?;
+
+        let input = self
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FuncArg::Single(felt) => Value::String(felt.to_string()),
+                FuncArg::Array(felts) => felts.iter().map(|felt| felt.to_string()).collect(),
+            })
+            .collect::<Value>();
+        write_json(&dir.join(DUMP_INPUT_FILE), &input)?;
+
+        let mut transcript = BufWriter::new(File::create(dir.join(DUMP_TRANSCRIPT_FILE))?);
+        for call in self.calls {
+            serde_json::to_writer(&mut transcript, call)
+                .map_err(|e| Error::Transcript(e.to_string()))?;
+            transcript.write_all(b"\n")?;
+        }
+        transcript.flush()?;
+
+        let panic = json!({
+            "entrypoint": self.entry_func_name,
+            "panic_data": self.panic_data.iter().map(|felt| felt.to_string()).collect::<Vec<_>>(),
+            "location": self.location.map(|location| location.to_string()),
+            "steps": vm.get_current_step(),
+            "registers": {
+                "pc": vm.get_pc().to_string(),
+                "ap": vm.get_ap().to_string(),
+                "fp": vm.get_fp().to_string(),
+            },
+            "oracle_calls": self.calls.len(),
+        });
+        write_json(&dir.join(DUMP_PANIC_FILE), &panic)?;
+
+        let memory_file = File::create(dir.join(DUMP_MEMORY_FILE))?;
+        let mut memory = FileWriter::new(BufWriter::new(memory_file));
+        cairo_vm::cairo_run::write_encoded_memory(&runner.relocated_memory, &mut memory)?;
+        memory.flush()?;
+        if let Some(relocated_trace) = &runner.relocated_trace {
+            let trace_file = File::create(dir.join(DUMP_TRACE_FILE))?;
+            let mut trace = FileWriter::new(BufWriter::new(trace_file));
+            cairo_vm::cairo_run::write_encoded_trace(relocated_trace, &mut trace)?;
+            trace.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_json(path: &Path, value: &Value) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, value)?;
+    file.write_all(b"\n")?;
+    file.flush()
+}
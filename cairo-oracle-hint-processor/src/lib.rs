@@ -1,15 +1,20 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::io;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use bincode::enc::write::Writer;
 use cairo_lang_sierra::ids::ConcreteTypeId;
-use cairo_lang_sierra::program::Program as SierraProgram;
+use cairo_lang_sierra::program::{Program as SierraProgram, VersionedProgram};
 use cairo_lang_sierra::program_registry::ProgramRegistryError;
 use cairo_lang_sierra_to_casm::compiler::CompilationError;
 use cairo_lang_sierra_to_casm::metadata::MetadataError;
-use cairo_proto_serde::configuration::Configuration;
+use cairo_proto_serde::configuration::{check_lock, Configuration, Encoding};
+use cairo_proto_serde::schema::schema_hash;
 use cairo_run::Cairo1RunConfig;
 use cairo_vm::air_public_input::PublicInputError;
 use cairo_vm::cairo_run::EncodeTraceError;
@@ -21,12 +26,48 @@ use cairo_vm::vm::errors::runner_errors::RunnerError;
 use cairo_vm::vm::errors::trace_errors::TraceError;
 use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
 use cairo_vm::Felt252;
+use log::warn;
+use rpc_hint_processor::OracleCache;
+use serde::Serialize;
 use thiserror::Error;
 
+mod breaker;
 mod cairo_run;
+mod casm;
+pub mod codec;
+pub mod conformance;
+mod disk_cache;
+pub mod dry_run;
+mod dump;
 pub mod rpc_hint_processor;
 
 mod hint_processor_utils;
+mod metrics;
+mod prefetch;
+mod progress;
+mod provider;
+mod signature;
+mod transcript;
+mod transport;
+
+pub use breaker::DEFAULT_BREAKER_COOLDOWN;
+pub use cairo_run::SUGGESTED_LAYOUTS;
+pub use disk_cache::OracleCacheMode;
+pub use dump::{
+    DUMP_INPUT_FILE, DUMP_MEMORY_FILE, DUMP_PANIC_FILE, DUMP_TRACE_FILE, DUMP_TRANSCRIPT_FILE,
+};
+pub use metrics::RunMetrics;
+pub use provider::OracleProvider;
+pub use rpc_hint_processor::PARTIAL_OUTPUT_SELECTOR;
+pub use signature::SIGNATURE_HEADER;
+pub use transcript::TranscriptEntry;
+pub use transport::{
+    OracleCompression, OracleTransport, DEFAULT_ERROR_BODY_BYTES, DEFAULT_MAX_REDIRECTS,
+    DEFAULT_MAX_RESPONSE_BYTES, DEFAULT_TRACE_HEADER, SEED_HEADER,
+};
+
+/// The errors of a run, for the embedders matching on them.
+pub type RunError = Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -34,6 +75,14 @@ pub enum Error {
     Cli(#[from] clap::Error),
     #[error("Failed to interact with the file system")]
     IO(#[from] std::io::Error),
+    #[error("File not found: {}", .0.display())]
+    FileNotFound(PathBuf),
+    #[error("Failed to parse the oracle lock {}: {reason}. Run `scarb hints-generate` to regenerate it.", path.display())]
+    LockParse { path: PathBuf, reason: String },
+    #[error("Failed to load the Sierra program {}: {reason}", path.display())]
+    SierraParse { path: PathBuf, reason: String },
+    #[error("Failed to load the CASM file {}: {reason}", path.display())]
+    CasmParse { path: PathBuf, reason: String },
     #[error(transparent)]
     EncodeTrace(#[from] EncodeTraceError),
     #[error(transparent)]
@@ -56,8 +105,42 @@ pub enum Error {
     Program(#[from] ProgramError),
     #[error(transparent)]
     Memory(#[from] MemoryError),
-    #[error("Program panicked with {0:?}")]
-    RunPanic(Vec<Felt252>),
+    #[error(
+        "Program panicked with {panic_data:?}{}{}",
+        location.as_ref().map(|l| format!(" at {l}")).unwrap_or_default(),
+        last_oracle_call
+            .as_ref()
+            .map(|call| format!(", last oracle call before the panic: {call}"))
+            .unwrap_or_default()
+    )]
+    RunPanic {
+        panic_data: Vec<Felt252>,
+        /// Only known when the trace is recorded.
+        location: Option<PanicLocation>,
+        /// The oracle call whose response was the last one written to the memory, the likeliest
+        /// culprit of a panic caused by unexpected oracle data. `None` when no oracle was called.
+        last_oracle_call: Option<LastOracleCall>,
+        /// Felts the program gave to the `partial_output` cheatcode before it panicked, only
+        /// captured with `OracleConfig::capture_partial`.
+        partial_output: Option<Vec<Felt252>>,
+    },
+    #[error("Entry point {0} not found in the Sierra program")]
+    EntryPointNotFound(String),
+    #[error("Entry point {name} can't be called with the given arguments: {reason}")]
+    IncompatibleEntryPoint { name: String, reason: String },
+    #[error("--available-gas can't be applied: entry point {name} doesn't take a GasBuiltin, as its package is compiled without gas metering. Set `enable-gas = true` in the [cairo] section of Scarb.toml, or bound the run with --max-steps.")]
+    GasNotMetered { name: String },
+    #[error(
+        "Layout {layout} doesn't provide the {} builtins used by the program{}",
+        missing.join(", "),
+        suggestion.as_ref().map(|l| format!(", use --layout {l}")).unwrap_or_default()
+    )]
+    LayoutMissingBuiltins {
+        layout: String,
+        missing: Vec<String>,
+        /// Smallest layout providing every builtin of the program.
+        suggestion: Option<String>,
+    },
     #[error("Function signature has no return types")]
     NoRetTypesInSignature,
     #[error("No size for concrete type id: {0}")]
@@ -70,6 +153,8 @@ pub enum Error {
     FailedToExtractReturnValues,
     #[error("Function expects arguments of size {expected} and received {actual} instead.")]
     ArgumentsSizeMismatch { expected: i16, actual: i16 },
+    #[error("Argument {index} is invalid: {reason}")]
+    InvalidArgument { index: usize, reason: String },
     #[error("Function param {param_index} only partially contains argument {arg_index}.")]
     ArgumentUnaligned {
         param_index: usize,
@@ -79,6 +164,100 @@ pub enum Error {
     ConfigError(String),
     #[error("Servers configuration file error: {0}")]
     ServersConfigFileError(String),
+    #[error("Couldn't connect to oracle server {server}: {reason}. Is the server running?")]
+    OracleConnection { server: String, reason: String },
+    #[error("TLS handshake with oracle server {server} failed: {reason}. Pass --oracle-ca-cert if it uses a private CA.")]
+    OracleTls { server: String, reason: String },
+    #[error("Oracle server {server} redirected the request: {reason}")]
+    OracleRedirect { server: String, reason: String },
+    #[error("Oracle server {server} is not one of the allowed hosts: {allowed}")]
+    OracleHostNotAllowed { server: String, allowed: String },
+    #[error("gRPC oracle call failed: {0}")]
+    Grpc(String),
+    #[error("Calls to oracle server {server} are suspended for {}s after {failures} consecutive failures", retry_in.as_secs_f64().ceil())]
+    OracleCircuitOpen {
+        server: String,
+        failures: u32,
+        retry_in: Duration,
+    },
+    #[error("Oracle service {service} is unavailable after {attempts} attempts: {reason}")]
+    OracleUnavailable {
+        service: String,
+        attempts: u32,
+        reason: String,
+    },
+    #[error("The program calls oracle methods that are not declared in the Oracle.lock: {}. Is the Oracle.lock up to date?", .0.join(", "))]
+    UnknownOracleMethods(Vec<String>),
+    #[error("Oracle service {service} expects the schema {expected}, but the Oracle.lock declares {actual}. Run `scarb hints-generate` to regenerate the bindings.")]
+    SchemaMismatch {
+        service: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("The Cairo bindings are stale, run `scarb hints-generate`: {} changed since {} was generated", protos.join(", "), bindings.display())]
+    BindingsStale {
+        bindings: PathBuf,
+        protos: Vec<String>,
+    },
+    #[error("Cannot read the Scarb metadata: {0}")]
+    ScarbMetadata(String),
+    #[error("{reason}\nSelect one of the packages with --package: {}", members.join(", "))]
+    PackageNotSelected {
+        reason: String,
+        members: Vec<String>,
+    },
+    #[error("Package has not been compiled with the {profile} profile, file does not exist: {}\nhelp: run `scarb --profile {profile} build`, or run without --no-build", path.display())]
+    PackageNotBuilt { profile: String, path: PathBuf },
+    #[error("`scarb build` failed: {0}")]
+    Build(String),
+    #[error("Oracle service {service} rejected the request: {message}")]
+    OracleRejected { service: String, message: String },
+    #[error("Oracle service {service} responded with HTTP {status}: {body}")]
+    OracleHttpError {
+        service: String,
+        status: u16,
+        /// Truncated to `OracleConfig::error_body_bytes`.
+        body: String,
+    },
+    #[error("Oracle method {service}.{method} responded with HTTP {status} and an empty body")]
+    OracleEmptyResponse {
+        service: String,
+        method: String,
+        status: u16,
+    },
+    #[error("The stream of oracle method {service}.{method} ended, no response is left")]
+    OracleStreamEnded { service: String, method: String },
+    #[error("Invalid response from oracle method {service}.{method}: {detail}")]
+    OracleResponseInvalid {
+        service: String,
+        method: String,
+        detail: String,
+    },
+    #[error("Invalid signature of the response of oracle service {service}: {reason}")]
+    OracleSignatureInvalid { service: String, reason: String },
+    #[error("Response of oracle service {service} is larger than {limit} bytes. Raise it with --oracle-max-response-bytes if it is expected.")]
+    OracleResponseTooLarge { service: String, limit: u64 },
+    #[error("Time limit of {}s exceeded after {steps} steps and {oracle_calls} oracle calls", limit.as_secs_f64())]
+    TimeLimitExceeded {
+        limit: Duration,
+        steps: usize,
+        oracle_calls: usize,
+    },
+    #[error("Step limit exceeded after {steps} steps and {oracle_calls} oracle calls")]
+    StepLimitExceeded { steps: usize, oracle_calls: usize },
+    #[error("Stopped at call {ordinal}, the first to oracle method {service}.{method}")]
+    SampleRequest {
+        service: String,
+        method: String,
+        ordinal: usize,
+        request: serde_json::Value,
+    },
+    #[error("Oracle provider failed: {0}")]
+    OracleProvider(String),
+    #[error("Oracle transcript error: {0}")]
+    Transcript(String),
+    #[error("Oracle replay diverged at call {ordinal}: {reason}")]
+    ReplayDivergence { ordinal: usize, reason: String },
 }
 
 #[allow(dead_code)]
@@ -91,6 +270,176 @@ pub enum FuncArg {
 #[derive(Debug, Clone, Default)]
 pub struct FuncArgs(pub Vec<FuncArg>);
 
+/// Sierra statement that raised a panic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicLocation {
+    pub statement_idx: usize,
+    /// Name of the function of the statement, when the program has debug info.
+    pub function: Option<String>,
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sierra statement {}", self.statement_idx)?;
+        if let Some(function) = &self.function {
+            write!(f, " ({function})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The last oracle call of a run that panicked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastOracleCall {
+    pub service: String,
+    pub method: String,
+    /// Position of the call in the run, from 0 as in the transcripts.
+    pub ordinal: usize,
+    /// VM steps executed between the response of the call and the end of the run.
+    pub steps_before_panic: usize,
+}
+
+impl fmt::Display for LastOracleCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{} (call {}, {} steps before the panic)",
+            self.service, self.method, self.ordinal, self.steps_before_panic
+        )
+    }
+}
+
+/// Outcome of a run that didn't panic.
+#[derive(Debug, Clone, Default)]
+pub struct RunResult {
+    pub return_values: Vec<MaybeRelocatable>,
+    /// Gas left when the run ends, `None` unless the run is given some available gas and the
+    /// entry function uses gas.
+    pub remaining_gas: Option<usize>,
+    /// Number of calls made to each oracle method, by selector.
+    pub oracle_calls: BTreeMap<String, usize>,
+    pub stats: RunStats,
+    pub timings: RunTimings,
+}
+
+/// Wall-clock time of a run, from the creation of its runner to the relocation of its memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunTimings {
+    pub total: Duration,
+    /// Time the VM spent executing the program, the oracle calls excluded.
+    pub vm: Duration,
+    /// Time spent answering the oracle calls, from the cache or not.
+    pub oracle: Duration,
+}
+
+/// Counters of the oracle calls of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RunStats {
+    /// Calls made by the program, however they were answered.
+    pub oracle_calls: usize,
+    /// Calls answered from the cache, prefetched and cached on disk responses included.
+    pub cache_hits: usize,
+    /// Calls answered by a server after more than one attempt.
+    pub retried_calls: usize,
+    /// Size of the JSON requests sent to the servers or the provider, before they are encoded
+    /// for the wire.
+    pub json_bytes_sent: u64,
+    /// Size of the JSON results received from the servers or the provider, once decoded.
+    pub json_bytes_received: u64,
+}
+
+/// Settings of the oracle client given on the command line, they take precedence over the
+/// `service_options` of the `Oracle.lock`.
+#[derive(Debug, Clone, Default)]
+pub struct OracleConfig {
+    /// Timeout of a single oracle request.
+    pub timeout: Option<Duration>,
+    /// Server of every service, overriding the configured URLs.
+    pub server: Option<String>,
+    /// Server used by the services that have no URL configured, read from `ORACLE_SERVER`.
+    pub fallback_server: Option<String>,
+    /// PEM certificate trusted in addition to the system roots, for oracles behind a private CA.
+    pub ca_cert: Option<PathBuf>,
+    /// Accepts invalid TLS certificates, only meant for development servers.
+    pub insecure: bool,
+    /// Headers added to the requests of every service, overriding the `headers` of the lock.
+    pub headers: Vec<(String, String)>,
+    /// Maximum number of idle connections kept open to each oracle host, unlimited when `None`.
+    pub pool_size: Option<usize>,
+    /// Disables the cache of the oracle responses, for oracles whose answers change between calls.
+    pub no_cache: bool,
+    /// Directory the oracle responses are cached to across runs, keyed by request and schema
+    /// hash of the service.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether the calls are answered from `cache_dir` and their responses added to it.
+    pub cache_mode: OracleCacheMode,
+    /// File the oracle calls are recorded to.
+    pub transcript: Option<PathBuf>,
+    /// Transcript the oracle responses are served from, instead of the servers.
+    pub replay: Option<PathBuf>,
+    /// Directory the state of a run is written to when it panics: its arguments, oracle calls,
+    /// panic data, registers and memory. Not written by the runs of a batch.
+    pub dump_on_panic: Option<PathBuf>,
+    /// Keeps the felts the program gives to the [`PARTIAL_OUTPUT_SELECTOR`] cheatcode, reported
+    /// with the panic data when it panics.
+    pub capture_partial: bool,
+    /// Hook answering the oracle calls instead of the servers, the calls are listed at the end of
+    /// the run.
+    pub mock_response: Option<dry_run::MockResponse>,
+    /// Oracle method, by name or as `Service.method`, whose first call stops the run with
+    /// `SampleRequest` holding its request, before it is answered.
+    pub sample_request: Option<String>,
+    /// Compression of the HTTP requests and responses.
+    pub compression: OracleCompression,
+    /// Encoding of the HTTP requests and responses, overriding the `encoding` of the lock.
+    pub encoding: Option<Encoding>,
+    /// Size above which an oracle response is rejected, [`DEFAULT_MAX_RESPONSE_BYTES`] when
+    /// `None`.
+    pub max_response_bytes: Option<u64>,
+    /// Length the body of a non-2xx response is truncated to in an `OracleHttpError`,
+    /// [`DEFAULT_ERROR_BODY_BYTES`] when `None`.
+    pub error_body_bytes: Option<usize>,
+    /// Parses the HTTP responses while they are received instead of buffering their body first.
+    pub stream_responses: bool,
+    /// Number of HTTP redirects an oracle request follows, [`DEFAULT_MAX_REDIRECTS`] when `None`.
+    pub max_redirects: Option<usize>,
+    /// Hosts the oracle requests may reach, as `host` or `host:port`, redirects included; any
+    /// host when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Header carrying the trace id of the run and the span id of each oracle request,
+    /// [`DEFAULT_TRACE_HEADER`] when `None`.
+    pub trace_header: Option<String>,
+    /// Seed sent to the oracle servers in the `x-oracle-seed` header, so they can derive their
+    /// random values from it.
+    pub seed: Option<u64>,
+    /// Counters updated during the run.
+    pub metrics: Option<Arc<RunMetrics>>,
+    /// Wall-clock budget of the run, checked between VM steps and around the oracle calls.
+    pub time_limit: Option<Duration>,
+    /// Number of VM steps after which the run stops, whether it uses gas or not.
+    pub max_steps: Option<usize>,
+    /// CASM of the program, written by `casm_output`, run instead of compiling its Sierra.
+    pub casm_file: Option<PathBuf>,
+    /// File the CASM of the program is written to once compiled, to be run with `casm_file`.
+    pub casm_output: Option<PathBuf>,
+    /// Skips the probe of the `health_path` of the services before the run.
+    pub skip_health_check: bool,
+    /// Number of consecutive transport failures of a server after which its calls fail without
+    /// reaching it for `breaker_cooldown`, never when `None`.
+    pub breaker_threshold: Option<u32>,
+    /// How long the calls to a failing server are short-circuited, [`DEFAULT_BREAKER_COOLDOWN`]
+    /// when `None`.
+    pub breaker_cooldown: Option<Duration>,
+    /// Fetches the responses of the `prefetch` requests of the `Oracle.lock` while the program
+    /// compiles, to answer the calls of the run from the cache.
+    pub prefetch: bool,
+    /// Interval of the progress lines printed to stderr during the run, disabled when `None`.
+    pub progress_interval: Option<Duration>,
+    /// Does not print the requests and responses of the oracle calls nor the failed attempts of
+    /// the retried calls to stderr, the errors are still returned.
+    pub quiet: bool,
+}
+
 pub struct FileWriter {
     buf_writer: io::BufWriter<std::fs::File>,
     bytes_written: usize,
@@ -124,16 +473,217 @@ impl FileWriter {
     }
 }
 
+/// Reads the `Oracle.lock` at `path`, checking its structure first so an error names the key at
+/// fault. The unknown keys are ignored with a warning.
+pub fn load_configuration(path: &Path) -> Result<Configuration, Error> {
+    let content = read_file(path)?;
+    let parse_error = |reason: String| Error::LockParse {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let lock: serde_json::Value =
+        serde_json::from_slice(&content).map_err(|e| parse_error(e.to_string()))?;
+    for key in check_lock(&lock).map_err(parse_error)? {
+        warn!(
+            "unknown key {key} in the oracle lock {}, it is ignored",
+            path.display()
+        );
+    }
+    // the errors of the values are located by their line and column
+    serde_json::from_slice(&content).map_err(|e| parse_error(e.to_string()))
+}
+
+/// Reads the Sierra program of a `.sierra.json` artifact built by Scarb.
+pub fn load_sierra_program(path: &Path) -> Result<SierraProgram, Error> {
+    let parse_error = |reason: String| Error::SierraParse {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let program = serde_json::from_slice::<VersionedProgram>(&read_file(path)?)
+        .map_err(|e| parse_error(e.to_string()))?
+        .into_v1()
+        .map_err(|e| parse_error(e.to_string()))?;
+    Ok(program.program)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::FileNotFound(path.to_path_buf()),
+        _ => Error::IO(e),
+    })
+}
+
+/// Runs the `entry_func_name` function of a Sierra program, calling the oracle servers.
+#[allow(clippy::too_many_arguments)]
 pub fn run_1(
     configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    layout: &LayoutName,
+    trace_file: &Option<PathBuf>,
+    memory_file: &Option<PathBuf>,
+    args: &FuncArgs,
+    available_gas: Option<usize>,
+    sierra_program: &SierraProgram,
+    entry_func_name: &str,
+    proof_mode: bool,
+) -> Result<RunResult, Error> {
+    run(
+        configuration,
+        oracle_config,
+        None,
+        layout,
+        trace_file,
+        memory_file,
+        args,
+        available_gas,
+        sierra_program,
+        entry_func_name,
+        proof_mode,
+    )
+}
+
+/// Same as [`run_1`], with the oracle calls answered by `provider` instead of the servers.
+#[allow(clippy::too_many_arguments)]
+pub fn run_1_with_provider(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    provider: &impl OracleProvider,
     layout: &LayoutName,
     trace_file: &Option<PathBuf>,
     memory_file: &Option<PathBuf>,
     args: &FuncArgs,
+    available_gas: Option<usize>,
     sierra_program: &SierraProgram,
     entry_func_name: &str,
     proof_mode: bool,
-) -> Result<Vec<MaybeRelocatable>, Error> {
+) -> Result<RunResult, Error> {
+    run(
+        configuration,
+        oracle_config,
+        Some(provider),
+        layout,
+        trace_file,
+        memory_file,
+        args,
+        available_gas,
+        sierra_program,
+        entry_func_name,
+        proof_mode,
+    )
+}
+
+/// Runs the `entry_func_name` function once for each set of `args`, compiling the program and
+/// connecting to the oracle servers once for the whole batch. The trace, memory, transcript and
+/// panic dump of the runs are not recorded.
+#[allow(clippy::too_many_arguments)]
+pub fn run_1_batch(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    layout: &LayoutName,
+    args: &[FuncArgs],
+    available_gas: Option<usize>,
+    sierra_program: &SierraProgram,
+    entry_func_name: &str,
+    proof_mode: bool,
+) -> Result<Vec<Result<RunResult, Error>>, Error> {
+    let oracle_config = OracleConfig {
+        transcript: None,
+        dump_on_panic: None,
+        ..oracle_config.clone()
+    };
+    check_schema_hashes(configuration)?;
+    let servers = transport::connect(configuration, &oracle_config, false)?;
+    let (compiled, prefetched) =
+        compile_and_prefetch(configuration, &oracle_config, &servers, sierra_program)?;
+
+    Ok(args
+        .iter()
+        .map(|args| {
+            let cairo_run_config = Cairo1RunConfig {
+                proof_mode,
+                layout,
+                args: &args.0,
+                available_gas,
+                ..Default::default()
+            };
+            cairo_run::cairo_run_program(
+                &compiled,
+                cairo_run_config,
+                configuration,
+                &oracle_config,
+                None,
+                Some(&servers),
+                prefetched.as_ref(),
+                entry_func_name,
+            )
+            .map(|(_, _, result)| result)
+        })
+        .collect())
+}
+
+/// Checks the `schema_hash` pinned in the `service_options` of the `Oracle.lock` against the
+/// declarations of their service, which are those of the Cairo bindings.
+fn check_schema_hashes(configuration: &Configuration) -> Result<(), Error> {
+    for (service, options) in &configuration.service_options {
+        let Some(expected) = &options.schema_hash else {
+            continue;
+        };
+        let actual = schema_hash(configuration, service).ok_or_else(|| {
+            Error::ConfigError(format!("schema_hash pinned for unknown service {service}"))
+        })?;
+        if *expected != actual {
+            return Err(Error::SchemaMismatch {
+                service: service.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Compiles the program while the `prefetch` requests of the `Oracle.lock` are sent, when
+/// `oracle_config.prefetch` is set and the responses can be cached.
+fn compile_and_prefetch<'a>(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    servers: &HashMap<String, transport::OracleServer>,
+    sierra_program: &'a SierraProgram,
+) -> Result<(cairo_run::CompiledProgram<'a>, Option<OracleCache>), Error> {
+    let compile = || {
+        cairo_run::compile_program(
+            sierra_program,
+            oracle_config.casm_file.as_deref(),
+            oracle_config.casm_output.as_deref(),
+        )
+    };
+    // the servers are only connected when the calls are not answered by a replay, mock or provider
+    if !oracle_config.prefetch || oracle_config.no_cache || servers.is_empty() {
+        return Ok((compile()?, None));
+    }
+    let requests = prefetch::prefetch_requests(configuration)?;
+    std::thread::scope(|scope| {
+        let prefetching = scope.spawn(|| prefetch::prefetch(configuration, servers, requests));
+        let compiled = compile()?;
+        let prefetched = prefetching.join().expect("the prefetch thread panicked");
+        Ok((compiled, Some(prefetched)))
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    provider: Option<&dyn OracleProvider>,
+    layout: &LayoutName,
+    trace_file: &Option<PathBuf>,
+    memory_file: &Option<PathBuf>,
+    args: &FuncArgs,
+    available_gas: Option<usize>,
+    sierra_program: &SierraProgram,
+    entry_func_name: &str,
+    proof_mode: bool,
+) -> Result<RunResult, Error> {
     // let compiler_config = CompilerConfig {
     //     replace_ids: true,
     //     ..CompilerConfig::default()
@@ -150,12 +700,22 @@ pub fn run_1(
         trace_enabled: trace_file.is_some(), //|| args.air_public_input.is_some(),
         args: &args.0,
         finalize_builtins: false, //args.air_private_input.is_some() || args.cairo_pie_output.is_some(),
+        available_gas,
     };
 
-    let (runner, _vm, return_values) = cairo_run::cairo_run_program(
-        &sierra_program,
+    check_schema_hashes(configuration)?;
+    // fail fast on a server that is down, before the program is compiled
+    let servers = transport::connect(configuration, oracle_config, provider.is_some())?;
+    let (compiled, prefetched) =
+        compile_and_prefetch(configuration, oracle_config, &servers, sierra_program)?;
+    let (runner, _vm, result) = cairo_run::cairo_run_program(
+        &compiled,
         cairo_run_config,
         configuration,
+        oracle_config,
+        provider,
+        Some(&servers),
+        prefetched.as_ref(),
         entry_func_name,
     )?;
 
@@ -179,5 +739,47 @@ pub fn run_1(
         memory_writer.flush()?;
     }
 
-    Ok(return_values)
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use bincode::enc::write::Writer;
+    use cairo_vm::vm::trace::trace_entry::RelocatedTraceEntry;
+
+    struct VecWriter(Vec<u8>);
+
+    impl Writer for VecWriter {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), bincode::error::EncodeError> {
+            self.0.extend_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    /// Reads a trace in the layout given in the reference: `ap`, `fp` and `pc` of each step, as
+    /// little endian 64-bit integers.
+    fn read_binary_trace(bytes: &[u8]) -> Vec<(u64, u64, u64)> {
+        bytes
+            .chunks_exact(24)
+            .map(|entry| {
+                let word =
+                    |i: usize| u64::from_le_bytes(entry[i * 8..i * 8 + 8].try_into().unwrap());
+                (word(0), word(1), word(2))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_encodes_the_registers_of_each_step_in_order() {
+        let trace = [(1, 24, 24), (3, 25, 24), (5, 27, 24), (2, 30, 28)]
+            .map(|(pc, ap, fp)| RelocatedTraceEntry { pc, ap, fp });
+        let mut writer = VecWriter(Vec::new());
+        cairo_vm::cairo_run::write_encoded_trace(&trace, &mut writer).unwrap();
+
+        assert_eq!(writer.0.len(), 24 * trace.len());
+        assert_eq!(
+            read_binary_trace(&writer.0),
+            vec![(24, 24, 1), (25, 24, 3), (27, 24, 5), (30, 28, 2)]
+        );
+    }
 }
@@ -0,0 +1,14 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+
+/// Counters of a run, updated while it executes so they can be exported before it ends.
+#[derive(Debug, Default)]
+pub struct RunMetrics {
+    pub oracle_calls: AtomicU64,
+    /// Oracle calls that stopped the run.
+    pub oracle_failures: AtomicU64,
+    /// Requests sent again after a transient failure.
+    pub oracle_retries: AtomicU64,
+    /// Service of the last oracle call.
+    pub last_service: Mutex<Option<String>>,
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use cairo_proto_serde::configuration::{Configuration, MethodDeclaration};
+use cairo_proto_serde::schema::validate;
+use cairo_proto_serde::{deserialize_cairo_serde, serialize_cairo_serde};
+use cairo_vm::Felt252;
+use log::{info, warn};
+use serde_json::Value;
+
+use crate::rpc_hint_processor::OracleCache;
+use crate::transport::{new_trace_id, OracleServer, RequestTrace};
+use crate::Error;
+
+/// A `prefetch` request of the `Oracle.lock`, checked against its method.
+pub(crate) struct Prefetch<'a> {
+    selector: &'a str,
+    method: &'a MethodDeclaration,
+    inputs: Vec<Felt252>,
+    /// The request as the hint processor sends it, decoded from the Cairo serde values.
+    request: Value,
+}
+
+/// Checks the `prefetch` requests of the `Oracle.lock`, so an invalid one fails the run before
+/// anything is fetched.
+pub(crate) fn prefetch_requests(configuration: &Configuration) -> Result<Vec<Prefetch>, Error> {
+    configuration
+        .prefetch
+        .iter()
+        .map(|entry| {
+            let (selector, method) = configuration
+                .services
+                .values()
+                .find_map(|service| service.methods.get_key_value(&entry.method))
+                .ok_or_else(|| {
+                    Error::ConfigError(format!("cannot prefetch unknown method {}", entry.method))
+                })?;
+            validate(configuration, &method.input, &entry.request).map_err(|e| {
+                Error::ConfigError(format!("invalid {selector} request to prefetch: {e}"))
+            })?;
+            let inputs = serialize_cairo_serde(configuration, &method.input, &entry.request);
+            let request = deserialize_cairo_serde(configuration, &method.input, &mut &*inputs);
+            Ok(Prefetch {
+                selector,
+                method,
+                inputs,
+                request,
+            })
+        })
+        .collect()
+}
+
+/// Fetches the responses of `requests`, keyed as the cache of the hint processor so the calls
+/// of the run with the same request are answered from it.
+///
+/// A request the server fails to answer is left to the run, which calls the server again.
+pub(crate) fn prefetch(
+    configuration: &Configuration,
+    servers: &HashMap<String, OracleServer>,
+    requests: Vec<Prefetch>,
+) -> OracleCache {
+    let started = Instant::now();
+    let mut cache = OracleCache::new();
+    // the prefetch is traced on its own, before the runs
+    let trace_id = new_trace_id();
+    for (
+        ordinal,
+        Prefetch {
+            selector,
+            method,
+            inputs,
+            request,
+        },
+    ) in requests.into_iter().enumerate()
+    {
+        let Some(server) = servers.get(selector) else {
+            continue;
+        };
+        // the responses of these services depend on the run, which hasn't started yet
+        let options = configuration.service_options.get(&server.service);
+        if options.is_some_and(|options| options.run_context) {
+            info!("not prefetching {selector}, its service is sent the run context");
+            continue;
+        }
+        let trace = RequestTrace::new(&trace_id, ordinal);
+        match server.call(configuration, method, selector, &request, &trace) {
+            // as in the run, a response that needed retries is not cached
+            Ok((output, 1)) => {
+                cache.insert((selector.to_string(), inputs), output);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("failed to prefetch {selector} {request}: {e}"),
+        }
+    }
+    info!(
+        "prefetched {} oracle responses in {:.1}ms, traced as {trace_id}",
+        cache.len(),
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+    cache
+}
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct State {
+    steps: AtomicUsize,
+    calls: AtomicUsize,
+    /// Service of the oracle request in flight, and when it was sent.
+    waiting: Mutex<Option<(String, Instant)>>,
+}
+
+/// Prints the progress of the run to stderr at a fixed interval, until it is dropped.
+pub(crate) struct Progress {
+    state: Arc<State>,
+    stop: Option<Sender<()>>,
+    reporter: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    pub fn start(interval: Duration) -> Self {
+        let state = Arc::new(State::default());
+        let (stop, stopped) = mpsc::channel::<()>();
+        let reporter = {
+            let state = state.clone();
+            let started = Instant::now();
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    state.report(started.elapsed());
+                }
+            })
+        };
+        Self {
+            state,
+            stop: Some(stop),
+            reporter: Some(reporter),
+        }
+    }
+
+    pub fn step(&self) {
+        self.state.steps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn call(&self) {
+        self.state.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks the run as blocked on a request to `service` until the guard is dropped.
+    pub fn waiting_for(&self, service: &str) -> WaitingGuard<'_> {
+        *self.state.waiting.lock().unwrap() = Some((service.to_string(), Instant::now()));
+        WaitingGuard(&self.state)
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(reporter) = self.reporter.take() {
+            let _ = reporter.join();
+        }
+    }
+}
+
+pub(crate) struct WaitingGuard<'a>(&'a State);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.waiting.lock().unwrap() = None;
+    }
+}
+
+impl State {
+    fn report(&self, elapsed: Duration) {
+        let steps = self.steps.load(Ordering::Relaxed);
+        let calls = self.calls.load(Ordering::Relaxed);
+        let waiting = match &*self.waiting.lock().unwrap() {
+            Some((service, since)) => {
+                format!(
+                    ", waiting {:.1}s for {service}",
+                    since.elapsed().as_secs_f64()
+                )
+            }
+            None => String::new(),
+        };
+        eprintln!(
+            "Progress: {:.0}s elapsed, {steps} steps, {calls} oracle calls{waiting}",
+            elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use crate::Error;
+
+/// Answers the oracle calls of a run in-process, in place of the oracle servers.
+///
+/// `request` is the JSON encoded `Request` of the method, and the JSON encoded `Response` is
+/// expected back, that is the body of an HTTP oracle response without the `{"result": ...}`
+/// envelope. Closures with the same signature as `call` are providers.
+pub trait OracleProvider {
+    fn call(&self, service: &str, method: &str, request: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> OracleProvider for F
+where
+    F: Fn(&str, &str, &[u8]) -> Result<Vec<u8>, Error>,
+{
+    fn call(&self, service: &str, method: &str, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self(service, method, request)
+    }
+}
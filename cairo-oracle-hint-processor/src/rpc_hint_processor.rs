@@ -1,13 +1,19 @@
 use super::Error;
+use crate::codec::{request_value, response_felts};
+use crate::disk_cache::DiskCache;
+use crate::dry_run::{MockResponse, MockedCall};
 use crate::hint_processor_utils::{cell_ref_to_relocatable, extract_buffer, get_ptr};
 use crate::insert_value_to_cellref;
+use crate::progress::Progress;
+use crate::transcript::{Replay, TranscriptEntry, TranscriptWriter};
+use crate::transport::{connect, new_trace_id, OracleServer, OracleStream, RequestTrace};
+use crate::{FuncArg, LastOracleCall, OracleConfig, OracleProvider, RunMetrics, RunStats};
 use cairo_lang_casm::{
     hints::{Hint, StarknetHint},
     operand::{CellRef, ResOperand},
 };
 use cairo_lang_utils::bigint::BigIntAsHex;
-use cairo_proto_serde::configuration::Configuration;
-use cairo_proto_serde::{deserialize_cairo_serde, serialize_cairo_serde};
+use cairo_proto_serde::configuration::{Configuration, MethodDeclaration};
 use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor::Cairo1HintProcessor;
 use cairo_vm::hint_processor::hint_processor_definition::HintProcessorLogic;
 use cairo_vm::hint_processor::hint_processor_definition::HintReference;
@@ -25,30 +31,448 @@ use cairo_vm::{
     },
 };
 use core::any::Any;
-use indoc::formatdoc;
-use itertools::Itertools;
-use reqwest::Url;
-use serde_json::Value;
-use std::collections::HashMap;
+use log::{info, log_enabled, Level};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Selector of the cheatcode whose inputs are kept as the partial output of the run, reported
+/// if it panics, see `OracleConfig::capture_partial`. It is answered with no data and is not an
+/// oracle method.
+pub const PARTIAL_OUTPUT_SELECTOR: &str = "partial_output";
+
+/// Responses of the oracle calls, keyed by selector and serialized request.
+pub(crate) type OracleCache = HashMap<(String, Vec<Felt252>), Value>;
 
 /// HintProcessor for Cairo 1 compiler hints.
 pub struct Rpc1HintProcessor<'a> {
     inner_processor: Cairo1HintProcessor,
     configuration: &'a Configuration,
+    servers: HashMap<String, OracleServer>,
+    /// Answers the oracle calls in place of the servers.
+    provider: Option<&'a dyn OracleProvider>,
+    /// Responses of the oracle calls of this run and of the prefetched requests.
+    cache: Option<OracleCache>,
+    /// Responses cached across runs in the `cache_dir`.
+    disk_cache: Option<DiskCache>,
+    transcript: Option<TranscriptWriter>,
+    replay: Option<Replay>,
+    mock_response: Option<MockResponse>,
+    mocked_calls: Vec<MockedCall>,
+    /// Method whose first call stops the run, see `OracleConfig::sample_request`.
+    sample_request: Option<String>,
+    /// Number of oracle calls made so far.
+    calls: usize,
+    oracle_calls: BTreeMap<String, usize>,
+    stats: RunStats,
+    /// Time spent answering the oracle calls.
+    oracle_time: Duration,
+    metrics: Option<Arc<RunMetrics>>,
+    progress: Option<Progress>,
+    /// Time limit of the run and the instant it expires.
+    deadline: Option<(Duration, Instant)>,
+    /// Number of VM steps executed so far.
+    steps: usize,
+    /// Step count at which the run stops with `StepLimitExceeded`.
+    max_steps: Option<usize>,
+    /// Does not print the requests and responses of the oracle calls.
+    quiet: bool,
+    /// Identifies the run to the servers of the streaming services, and in the run context.
+    session: String,
+    /// Hash of the arguments of the run, sent in the run context.
+    input_hash: String,
+    /// Trace id of the run, sent with each oracle request along with the span id of the call.
+    trace_id: String,
+    /// Streams opened by the calls to the streaming services, by selector and serialized request.
+    streams: HashMap<(String, Vec<Felt252>), OracleStream>,
+    /// Oracle calls of the run, kept for the panic dump when it is enabled.
+    recorded_calls: Option<Vec<TranscriptEntry>>,
+    /// Service, method, ordinal and step of the last response written to the memory.
+    last_call: Option<(String, String, usize, usize)>,
+    /// Inputs of the `partial_output` cheatcode calls, kept when `capture_partial` is set.
+    partial_output: Option<Vec<Felt252>>,
+    error: Option<Error>,
+}
+
+/// Wraps the request of a service that is sent the run context, identified by `run_id` and the
+/// hash of its arguments.
+pub(crate) fn run_context_envelope(data: &Value, run_id: &str, input_hash: &str) -> Value {
+    json!({
+        "request": data,
+        "context": { "run_id": run_id, "input_hash": input_hash },
+    })
+}
+
+/// A session id unique to the run, from the process id and the time the run started.
+pub(crate) fn new_session() -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:x}-{:x}", std::process::id(), started.as_nanos())
+}
+
+/// SHA-256 of the arguments of a run, as hexadecimal: each felt as 32 big-endian bytes, an array
+/// preceded by its length.
+pub(crate) fn input_hash(args: &[FuncArg]) -> String {
+    let mut hasher = Sha256::new();
+    for arg in args {
+        match arg {
+            FuncArg::Single(felt) => hasher.update(felt.to_bytes_be()),
+            FuncArg::Array(felts) => {
+                hasher.update(Felt252::from(felts.len()).to_bytes_be());
+                for felt in felts {
+                    hasher.update(felt.to_bytes_be());
+                }
+            }
+        }
+    }
+    let digest = hasher.finalize();
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
 impl<'a> Rpc1HintProcessor<'a> {
     pub fn new(
         inner_processor: Cairo1HintProcessor,
         configuration: &'a Configuration,
+        oracle_config: &OracleConfig,
+        provider: Option<&'a dyn OracleProvider>,
+    ) -> Result<Self, Error> {
+        let servers = connect(configuration, oracle_config, provider.is_some())?;
+        Self::with_servers(
+            inner_processor,
+            configuration,
+            oracle_config,
+            provider,
+            servers,
+            None,
+        )
+    }
+
+    /// Same as [`Rpc1HintProcessor::new`], with servers resolved beforehand so successive runs
+    /// share their connections, and the cache seeded with the `prefetched` responses.
+    pub(crate) fn with_servers(
+        inner_processor: Cairo1HintProcessor,
+        configuration: &'a Configuration,
+        oracle_config: &OracleConfig,
+        provider: Option<&'a dyn OracleProvider>,
+        servers: HashMap<String, OracleServer>,
+        prefetched: Option<&OracleCache>,
     ) -> Result<Self, Error> {
+        let replay = oracle_config
+            .replay
+            .as_deref()
+            .map(Replay::load)
+            .transpose()?;
+        let transcript = oracle_config
+            .transcript
+            .as_deref()
+            .map(TranscriptWriter::create)
+            .transpose()?;
+        let disk_cache = oracle_config
+            .cache_dir
+            .as_deref()
+            .filter(|_| !oracle_config.no_cache && replay.is_none())
+            .and_then(|dir| DiskCache::open(dir, oracle_config.cache_mode, configuration));
+        let trace_id = new_trace_id();
+        info!("Oracle requests of the run are traced as {trace_id}");
+
         Ok(Self {
             inner_processor,
             configuration,
+            servers,
+            provider,
+            cache: (!oracle_config.no_cache && replay.is_none())
+                .then(|| prefetched.cloned().unwrap_or_default()),
+            disk_cache,
+            transcript,
+            replay,
+            mock_response: oracle_config.mock_response,
+            sample_request: oracle_config.sample_request.clone(),
+            mocked_calls: Vec::new(),
+            calls: 0,
+            oracle_calls: BTreeMap::new(),
+            stats: RunStats::default(),
+            oracle_time: Duration::ZERO,
+            metrics: oracle_config.metrics.clone(),
+            progress: oracle_config.progress_interval.map(Progress::start),
+            deadline: oracle_config
+                .time_limit
+                .map(|limit| (limit, Instant::now() + limit)),
+            steps: 0,
+            max_steps: oracle_config.max_steps,
+            quiet: oracle_config.quiet,
+            session: new_session(),
+            input_hash: input_hash(&[]),
+            trace_id,
+            streams: HashMap::new(),
+            recorded_calls: oracle_config.dump_on_panic.is_some().then(Vec::new),
+            last_call: None,
+            partial_output: oracle_config.capture_partial.then(Vec::new),
+            error: None,
         })
     }
 
+    /// Sets the arguments of the run, whose hash is sent in the run context.
+    pub(crate) fn set_input(&mut self, args: &[FuncArg]) {
+        self.input_hash = input_hash(args);
+    }
+
+    /// Takes the oracle error that aborted the last hint, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Returns the number of calls made to each oracle method, by selector.
+    pub fn oracle_calls(&self) -> &BTreeMap<String, usize> {
+        &self.oracle_calls
+    }
+
+    /// Returns the counters of the oracle calls made so far.
+    pub fn stats(&self) -> RunStats {
+        RunStats {
+            oracle_calls: self.calls,
+            ..self.stats
+        }
+    }
+
+    /// Returns the time spent answering the oracle calls so far.
+    pub fn oracle_time(&self) -> Duration {
+        self.oracle_time
+    }
+
+    /// Returns the oracle calls of the run, only recorded when a panic dump is enabled.
+    pub(crate) fn recorded_calls(&self) -> &[TranscriptEntry] {
+        self.recorded_calls.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the felts given to the `partial_output` cheatcode so far, `None` unless
+    /// `capture_partial` is set.
+    pub(crate) fn partial_output(&self) -> Option<Vec<Felt252>> {
+        self.partial_output.clone()
+    }
+
+    /// Returns the last oracle call whose response was written to the memory, with the steps
+    /// executed since.
+    pub(crate) fn last_call(&self) -> Option<LastOracleCall> {
+        let (service, method, ordinal, step) = self.last_call.clone()?;
+        Some(LastOracleCall {
+            service,
+            method,
+            ordinal,
+            steps_before_panic: self.steps - step,
+        })
+    }
+
+    /// Returns the oracle calls answered by the `mock_response` hook.
+    pub fn mocked_calls(&self) -> &[MockedCall] {
+        &self.mocked_calls
+    }
+
+    /// Records the `TimeLimitExceeded` error once the deadline of the run has passed.
+    fn check_deadline(&mut self) -> Result<(), HintError> {
+        match self.deadline {
+            Some((limit, deadline)) if self.error.is_none() && Instant::now() >= deadline => {
+                Err(self.fail(Error::TimeLimitExceeded {
+                    limit,
+                    steps: self.steps,
+                    oracle_calls: self.calls,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records `error` so it can be reported by the runner, and converts it to a hint error.
+    fn fail(&mut self, error: Error) -> HintError {
+        let hint_error = HintError::CustomHint(Box::from(error.to_string()));
+        if let Some(metrics) = &self.metrics {
+            metrics.oracle_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.error = Some(error);
+        hint_error
+    }
+
+    /// Answers an oracle call from the mock, the replayed transcript, the cache, the provider or
+    /// the oracle server, and records it to the transcript.
+    ///
+    /// The caches only decide where the response comes from: every call of the program gets its
+    /// own response, in program order and with its own ordinal, whether the cache answers it or
+    /// not, so a run sees the same sequence of responses as one with `no_cache`.
+    fn oracle_response(
+        &mut self,
+        ordinal: usize,
+        service: &str,
+        method: &MethodDeclaration,
+        selector: &str,
+        inputs: Vec<Felt252>,
+        data: &Value,
+    ) -> Result<Value, Error> {
+        if let Some(mock_response) = self.mock_response {
+            self.mocked_calls.push(MockedCall {
+                service: service.to_string(),
+                selector: selector.to_string(),
+                request: data.clone(),
+            });
+            return Ok(mock_response(self.configuration, method, data));
+        }
+        if let Some(replay) = &self.replay {
+            return replay.response(ordinal, selector, data);
+        }
+
+        let options = self.configuration.service_options.get(service);
+        let streaming = options.is_some_and(|options| options.streaming);
+        // the request is only wrapped when it is sent, the cache and transcript keep it as is
+        let envelope = options
+            .is_some_and(|options| options.run_context)
+            .then(|| self.envelope(data));
+        let request = envelope.as_ref().unwrap_or(data);
+        let trace = RequestTrace::new(&self.trace_id, ordinal);
+        if streaming && self.provider.is_none() {
+            let output = self.stream_response(service, selector, inputs, request, &trace)?;
+            if let Some(transcript) = self.transcript.as_mut() {
+                transcript.record(&TranscriptEntry::new(
+                    ordinal, service, selector, data, &output,
+                ))?;
+            }
+            return Ok(output);
+        }
+
+        let cache_key = (selector.to_string(), inputs);
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(&cache_key))
+            .cloned()
+            .or_else(|| {
+                // a response depending on the run context can't be reused by another run
+                let disk_cache = self.disk_cache.as_ref().filter(|_| envelope.is_none())?;
+                disk_cache.get(self.configuration, service, selector, &cache_key.1)
+            });
+        if let (Some(output), Some(cache)) = (&cached, self.cache.as_mut()) {
+            cache
+                .entry(cache_key.clone())
+                .or_insert_with(|| output.clone());
+        }
+        let output = match cached {
+            Some(output) => {
+                self.stats.cache_hits += 1;
+                output
+            }
+            None => match self.provider {
+                Some(provider) => {
+                    let _waiting = self.progress.as_ref().map(|p| p.waiting_for(service));
+                    let request = serde_json::to_vec(request)
+                        .map_err(|e| Error::OracleProvider(e.to_string()))?;
+                    let response = provider.call(service, selector, &request)?;
+                    self.stats.json_bytes_sent += request.len() as u64;
+                    self.stats.json_bytes_received += response.len() as u64;
+                    let output: Value = serde_json::from_slice(&response).map_err(|e| {
+                        Error::OracleProvider(format!("invalid JSON response for {selector}: {e}"))
+                    })?;
+                    self.cache_response(service, cache_key, data, &output, envelope.is_none());
+                    output
+                }
+                None => {
+                    let server = self.servers.get(selector).ok_or_else(|| {
+                        Error::ConfigError(format!(
+                            "No server URL configured for selector: {selector}"
+                        ))
+                    })?;
+                    let _waiting = self.progress.as_ref().map(|p| p.waiting_for(service));
+                    let (output, attempts) =
+                        server.call(self.configuration, method, selector, request, &trace)?;
+                    self.stats.json_bytes_sent += request.to_string().len() as u64;
+                    self.stats.json_bytes_received += output.to_string().len() as u64;
+                    if attempts > 1 {
+                        self.stats.retried_calls += 1;
+                    }
+                    if let Some(metrics) = &self.metrics {
+                        let retries = u64::from(attempts.saturating_sub(1));
+                        metrics.oracle_retries.fetch_add(retries, Ordering::Relaxed);
+                    }
+                    // a response that needed retries may come from a server in a degraded state
+                    if attempts == 1 {
+                        self.cache_response(service, cache_key, data, &output, envelope.is_none());
+                    }
+                    output
+                }
+            },
+        };
+
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.record(&TranscriptEntry::new(
+                ordinal, service, selector, data, &output,
+            ))?;
+        }
+        Ok(output)
+    }
+
+    /// Adds the response of a call to the cache of the run, and to the disk cache if `reusable`
+    /// by the other runs.
+    fn cache_response(
+        &mut self,
+        service: &str,
+        (selector, inputs): (String, Vec<Felt252>),
+        data: &Value,
+        output: &Value,
+        reusable: bool,
+    ) {
+        if let Some(disk_cache) = self.disk_cache.as_ref().filter(|_| reusable) {
+            disk_cache.insert(
+                self.configuration,
+                service,
+                &selector,
+                &inputs,
+                data,
+                output,
+            );
+        }
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert((selector, inputs), output.clone());
+        }
+    }
+
+    /// Wraps the request of a service that is sent the run context.
+    fn envelope(&self, data: &Value) -> Value {
+        run_context_envelope(data, &self.session, &self.input_hash)
+    }
+
+    /// Reads the next response of the stream of the request, opening the stream on its first
+    /// call. Streamed responses are never cached, each call reads a new one.
+    fn stream_response(
+        &mut self,
+        service: &str,
+        selector: &str,
+        inputs: Vec<Felt252>,
+        data: &Value,
+        trace: &RequestTrace,
+    ) -> Result<Value, Error> {
+        let _waiting = self.progress.as_ref().map(|p| p.waiting_for(service));
+        let key = (selector.to_string(), inputs);
+        let stream = match self.streams.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let server = self.servers.get(selector).ok_or_else(|| {
+                    Error::ConfigError(format!("No server URL configured for selector: {selector}"))
+                })?;
+                let stream = server.open_stream(selector, data, &self.session, trace)?;
+                self.stats.json_bytes_sent += data.to_string().len() as u64;
+                entry.insert(stream)
+            }
+        };
+        let output = stream.next()?;
+        self.stats.json_bytes_received += output.to_string().len() as u64;
+        Ok(output)
+    }
+
     /// Executes a cheatcode.
+    ///
+    /// The VM waits for the response before running the next instruction, so oracle calls are
+    /// never in flight concurrently.
     fn execute_cheatcode(
         &mut self,
         selector: &BigIntAsHex,
@@ -71,107 +495,123 @@ impl<'a> Rpc1HintProcessor<'a> {
         let mut res_segment = MemBuffer::new_segment(vm);
         let res_segment_start = res_segment.ptr;
 
-        let Some(configuration) = self
+        if selector == PARTIAL_OUTPUT_SELECTOR {
+            if let Some(partial_output) = &mut self.partial_output {
+                partial_output.extend(inputs);
+            }
+            insert_value_to_cellref!(vm, output_start, res_segment_start)?;
+            insert_value_to_cellref!(vm, output_end, res_segment_start)?;
+            return Ok(());
+        }
+
+        let Some((service, configuration)) = self
             .configuration
             .services
             .iter()
-            .find_map(|(_, methods)| methods.methods.get(selector))
+            .find_map(|(service, methods)| methods.methods.get(selector).map(|m| (service, m)))
         else {
             return Err(HintError::CustomHint(Box::from(format!(
                 "Unknown cheatcode selector: {selector}"
             ))));
         };
 
-        let server_url = self
-            .configuration
-            .servers_config
-            .get(selector)
-            .ok_or_else(|| {
-                HintError::CustomHint(Box::from(format!(
-                    "No server URL configured for selector: {selector}"
-                )))
-            })?;
-
-        let mut server_url = Url::parse(server_url).map_err(|e| {
-            HintError::CustomHint(Box::from(format!(
-                "Invalid URL for selector {selector}: {e}"
-            )))
+        let data = request_value(self.configuration, configuration, &inputs).map_err(|e| {
+            HintError::CustomHint(Box::from(format!("invalid {selector} request: {e}")))
         })?;
-        server_url
-            .path_segments_mut()
-            .expect("cannot be a base URL")
-            .push(selector);
-
-        let data = deserialize_cairo_serde(
-            self.configuration,
-            &configuration.input,
-            &mut inputs.as_ref(),
-        );
-        println!("let the oracle decide... Inputs: {data:?}");
-
-        let client = reqwest::blocking::Client::new();
-
-        let req = client.post(server_url.clone()).json(&data).send().expect(
-            format!("Couldn't connect to oracle server {server_url}. Is the server running?")
-                .as_str(),
-        );
-
-        let status_code = req.error_for_status_ref().map(|_| ());
-        let body = req.text().expect(
-            formatdoc! {
-                r#"
-                Response from oracle server can't be parsed as string."#
-            }
-            .as_str(),
-        );
+        let sampled = self.sample_request.as_deref().is_some_and(|method| {
+            method == selector || method.split_once('.') == Some((service.as_str(), selector))
+        });
+        if sampled {
+            let error = Error::SampleRequest {
+                service: service.to_string(),
+                method: selector.to_string(),
+                ordinal: self.calls,
+                request: data,
+            };
+            let hint_error = HintError::CustomHint(Box::from(error.to_string()));
+            self.error = Some(error);
+            return Err(hint_error);
+        }
+        if !self.quiet {
+            eprintln!("let the oracle decide... Inputs: {data:?}");
+        }
 
-        status_code.expect(
-            formatdoc! {
-                r#"
-                Received {body:?}.
-                Response status from oracle server not successful."#
-            }
-            .as_str(),
-        );
+        self.check_deadline()?;
+        let ordinal = self.calls;
+        self.calls += 1;
+        *self.oracle_calls.entry(selector.to_string()).or_default() += 1;
+        if let Some(progress) = &self.progress {
+            progress.call();
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.oracle_calls.fetch_add(1, Ordering::Relaxed);
+            *metrics.last_service.lock().unwrap() = Some(service.to_string());
+        }
+        let started = Instant::now();
+        let output =
+            match self.oracle_response(ordinal, service, configuration, selector, inputs, &data) {
+                Ok(output) => output,
+                Err(e) => {
+                    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+                    let trace = RequestTrace::new(&self.trace_id, ordinal);
+                    info!(
+                        service = service.as_str(),
+                        method = selector,
+                        latency_ms = latency_ms,
+                        status = "error",
+                        trace_id = trace.trace_id.as_str(),
+                        span_id = trace.span_id.as_str();
+                        "{service}.{selector} failed after {latency_ms:.1}ms: {e}"
+                    );
+                    return Err(self.fail(e));
+                }
+            };
+        if let Some(calls) = self.recorded_calls.as_mut() {
+            calls.push(TranscriptEntry::new(
+                ordinal, service, selector, &data, &output,
+            ));
+        }
+        let latency = started.elapsed();
+        self.oracle_time += latency;
+        self.check_deadline()?;
+        if log_enabled!(Level::Info) {
+            let json_bytes_sent = data.to_string().len();
+            let json_bytes_received = output.to_string().len();
+            let latency_ms = latency.as_secs_f64() * 1000.0;
+            let trace = RequestTrace::new(&self.trace_id, ordinal);
+            info!(
+                service = service.as_str(),
+                method = selector,
+                latency_ms = latency_ms,
+                status = "ok",
+                json_bytes_sent = json_bytes_sent,
+                json_bytes_received = json_bytes_received,
+                trace_id = trace.trace_id.as_str(),
+                span_id = trace.span_id.as_str();
+                "{service}.{selector}: {json_bytes_sent} JSON bytes sent, {json_bytes_received} JSON bytes received in {latency_ms:.1}ms"
+            );
+        }
 
-        let body = serde_json::from_str::<Value>(body.as_str()).expect(
-            formatdoc! {
-                r#"
-                Received {body:?}.
-                Error converting response from oracle server {server_url} to JSON."#
+        let data = match response_felts(self.configuration, configuration, &output) {
+            Ok(data) => data,
+            Err(detail) => {
+                return Err(self.fail(Error::OracleResponseInvalid {
+                    service: service.to_string(),
+                    method: selector.to_string(),
+                    detail,
+                }))
             }
-            .as_str(),
-        );
-
-        let body = body.as_object().expect(
-            formatdoc! {r#"
-                Received {body:?}.
-                Error serialising response as object from oracle server.
-            "#}
-            .as_str(),
-        );
-
-        body.keys()
-            .exactly_one()
-            .map_err(|_| {
-                formatdoc! {r#"
-                    Received {body:?}.
-                    Expected response format from oracle server is {{"result": <response_object>}}.
-                "#}
-            })
-            .unwrap();
-
-        let output = body.get("result").expect(
-            formatdoc! {r#"
-                Received {body:?}.
-                Expected response format from oracle server is {{"result": <response_object>}}.
-            "#}
-            .as_str(),
-        );
-
-        let data = serialize_cairo_serde(self.configuration, &configuration.output, output);
-        println!("Output: {output}");
+        };
+        if !self.quiet {
+            eprintln!("Output: {output}");
+        }
         res_segment.write_data(data.iter())?;
+        self.last_call = Some((
+            service.to_string(),
+            selector.to_string(),
+            ordinal,
+            self.steps,
+        ));
 
         let res_segment_end = res_segment.ptr;
         insert_value_to_cellref!(vm, output_start, res_segment_start)?;
@@ -237,10 +677,26 @@ impl<'a> HintProcessorLogic for Rpc1HintProcessor<'a> {
 
 impl<'a> ResourceTracker for Rpc1HintProcessor<'a> {
     fn consumed(&self) -> bool {
-        self.inner_processor.consumed()
+        // the run stops once the time or step limit is exceeded, the error is reported by
+        // `take_error`
+        self.error.is_some() || self.inner_processor.consumed()
     }
 
     fn consume_step(&mut self) {
+        if let Some(progress) = &self.progress {
+            progress.step();
+        }
+        self.steps += 1;
+        if self.max_steps.is_some_and(|max| self.steps >= max) && self.error.is_none() {
+            self.error = Some(Error::StepLimitExceeded {
+                steps: self.steps,
+                oracle_calls: self.calls,
+            });
+        }
+        // reading the clock on every step would slow the VM down noticeably
+        if self.steps % 1024 == 0 {
+            let _ = self.check_deadline();
+        }
         self.inner_processor.consume_step()
     }
 
@@ -332,3 +788,79 @@ impl<'a> MemBuffer<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Rpc1HintProcessor;
+    use crate::{Error, OracleConfig};
+    use cairo_proto_serde::configuration::{
+        Configuration, FieldType, MethodDeclaration, PrimitiveType,
+    };
+    use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor::Cairo1HintProcessor;
+    use cairo_vm::vm::runners::cairo_runner::RunResources;
+    use cairo_vm::Felt252;
+    use serde_json::{json, Value};
+    use std::cell::RefCell;
+
+    /// Makes the calls of `requests` in order to a provider answering each request with itself,
+    /// and returns the responses the program sees along with the requests the provider got.
+    fn call_all(requests: &[u64], no_cache: bool) -> (Vec<Value>, Vec<Value>) {
+        let received = RefCell::new(Vec::new());
+        let provider = |_: &str, _: &str, request: &[u8]| -> Result<Vec<u8>, Error> {
+            received
+                .borrow_mut()
+                .push(serde_json::from_slice::<Value>(request).unwrap());
+            Ok(request.to_vec())
+        };
+        let configuration = Configuration::default();
+        let oracle_config = OracleConfig {
+            no_cache,
+            quiet: true,
+            ..Default::default()
+        };
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(PrimitiveType::U64),
+            grpc_path: None,
+        };
+        let inner_processor = Cairo1HintProcessor::new(&[], RunResources::default());
+        let mut processor = Rpc1HintProcessor::new(
+            inner_processor,
+            &configuration,
+            &oracle_config,
+            Some(&provider),
+        )
+        .unwrap();
+        let mut responses = Vec::new();
+        for (ordinal, &n) in requests.iter().enumerate() {
+            let inputs = vec![Felt252::from(n)];
+            let data = json!({ "n": n });
+            let response = processor
+                .oracle_response(ordinal, "SqrtOracle", &method, "sqrt", inputs, &data)
+                .unwrap();
+            responses.push(response);
+        }
+        drop(processor);
+        (responses, received.into_inner())
+    }
+
+    #[test]
+    fn it_answers_the_calls_in_the_same_order_with_and_without_the_cache() {
+        let requests = [4, 9, 4, 16, 9];
+        let (live, live_requests) = call_all(&requests, true);
+        let (cached, cached_requests) = call_all(&requests, false);
+
+        assert_eq!(cached, live);
+        let ns = cached
+            .iter()
+            .map(|response| response["n"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ns, requests);
+        // the cache only spares the repeated requests the round trip
+        assert_eq!(live_requests.len(), requests.len());
+        assert_eq!(
+            cached_requests,
+            [json!({ "n": 4 }), json!({ "n": 9 }), json!({ "n": 16 })]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::Error;
+
+/// Header carrying the Ed25519 signature of the response body, as 128 hexadecimal digits.
+pub const SIGNATURE_HEADER: &str = "x-oracle-signature";
+
+/// Verifies the responses of a service declaring a `public_key` in the `Oracle.lock`. Without
+/// the `signatures` feature it can't be built, so such a service is refused rather than trusted.
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseVerifier {
+    service: String,
+    #[cfg(feature = "signatures")]
+    key: ed25519_dalek::VerifyingKey,
+    #[cfg(not(feature = "signatures"))]
+    key: std::convert::Infallible,
+}
+
+impl ResponseVerifier {
+    /// Parses `public_key`, an Ed25519 public key as 64 hexadecimal digits.
+    pub fn new(service: &str, public_key: &str) -> Result<Self, Error> {
+        let invalid =
+            |reason: &str| Error::ConfigError(format!("Invalid public_key of {service}: {reason}"));
+        let bytes: [u8; 32] = decode_hex(public_key)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("expected 64 hexadecimal digits"))?;
+        #[cfg(feature = "signatures")]
+        {
+            let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                .map_err(|_| invalid("not an Ed25519 public key"))?;
+            Ok(Self {
+                service: service.to_string(),
+                key,
+            })
+        }
+        #[cfg(not(feature = "signatures"))]
+        {
+            let _ = bytes;
+            Err(Error::ConfigError(format!(
+                "Oracle service {service} signs its responses, but the runner is built without the `signatures` feature"
+            )))
+        }
+    }
+
+    /// Checks that `signature`, the value of the [`SIGNATURE_HEADER`] of the response, signs
+    /// `body` as received.
+    pub fn verify(&self, body: &[u8], signature: Option<&str>) -> Result<(), Error> {
+        let invalid = |reason: &str| Error::OracleSignatureInvalid {
+            service: self.service.clone(),
+            reason: reason.to_string(),
+        };
+        let signature = signature.ok_or_else(|| invalid("the response is not signed"))?;
+        let signature: [u8; 64] = decode_hex(signature)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("expected 128 hexadecimal digits"))?;
+        #[cfg(feature = "signatures")]
+        {
+            let signature = ed25519_dalek::Signature::from_bytes(&signature);
+            self.key
+                .verify_strict(body, &signature)
+                .map_err(|_| invalid("the signature doesn't match the response"))
+        }
+        #[cfg(not(feature = "signatures"))]
+        {
+            let _ = (body, signature);
+            match self.key {}
+        }
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(all(test, feature = "signatures"))]
+mod tests {
+    use super::ResponseVerifier;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn it_verifies_the_signature_of_the_response_body() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = hex(signing_key.verifying_key().as_bytes());
+        let verifier = ResponseVerifier::new("SqrtOracle", &public_key).unwrap();
+
+        let body = br#"{"result":{"n":"2"}}"#;
+        let signature = hex(&signing_key.sign(body).to_bytes());
+        assert!(verifier.verify(body, Some(&signature)).is_ok());
+
+        let tampered = br#"{"result":{"n":"3"}}"#;
+        assert!(verifier.verify(tampered, Some(&signature)).is_err());
+        assert!(verifier.verify(body, None).is_err());
+        assert!(verifier.verify(body, Some("00")).is_err());
+        assert!(ResponseVerifier::new("SqrtOracle", "1234").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Error;
+
+/// An oracle call, stored as one line of a transcript file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptEntry {
+    /// Position of the call in the run, starting from 0.
+    pub ordinal: usize,
+    /// Time the response was received, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub service: String,
+    pub selector: String,
+    pub request: Value,
+    pub response: Value,
+}
+
+impl TranscriptEntry {
+    pub fn new(
+        ordinal: usize,
+        service: &str,
+        selector: &str,
+        request: &Value,
+        response: &Value,
+    ) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            ordinal,
+            timestamp_ms,
+            service: service.to_string(),
+            selector: selector.to_string(),
+            request: request.clone(),
+            response: response.clone(),
+        }
+    }
+}
+
+/// Appends the oracle calls of a run to a newline-delimited JSON file.
+pub(crate) struct TranscriptWriter {
+    writer: BufWriter<File>,
+}
+
+impl TranscriptWriter {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let file = File::create(path)
+            .map_err(|e| Error::Transcript(format!("Failed to create {}: {e}", path.display())))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Writes `entry` and flushes it, so the calls preceding a failure are kept.
+    pub fn record(&mut self, entry: &TranscriptEntry) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.writer, entry)
+            .map_err(|e| Error::Transcript(e.to_string()))?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Serves the responses of a transcript, in the order they were recorded.
+pub(crate) struct Replay {
+    entries: Vec<TranscriptEntry>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| Error::Transcript(format!("Failed to read {}: {e}", path.display())))?;
+        let entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| Error::Transcript(format!("{}:{}: {e}", path.display(), i + 1)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Returns the recorded response of the call `ordinal`, which must be the same request as
+    /// the one recorded.
+    pub fn response(
+        &self,
+        ordinal: usize,
+        selector: &str,
+        request: &Value,
+    ) -> Result<Value, Error> {
+        let diverged = |reason: String| Error::ReplayDivergence { ordinal, reason };
+        let entry = self
+            .entries
+            .get(ordinal)
+            .ok_or_else(|| diverged("the transcript has no more calls".to_string()))?;
+        if entry.ordinal != ordinal {
+            return Err(diverged(format!(
+                "the transcript entry has ordinal {}",
+                entry.ordinal
+            )));
+        }
+        if entry.selector != selector || &entry.request != request {
+            return Err(diverged(format!(
+                "expected {} with {}, got {selector} with {request}",
+                entry.selector, entry.request
+            )));
+        }
+        Ok(entry.response.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, TranscriptEntry, TranscriptWriter};
+    use crate::Error;
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn transcript_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "cairo-hints-transcript-{}-{name}.jsonl",
+            std::process::id()
+        ))
+    }
+
+    /// Writes a call to `SqrtOracle.sqrt` for each ordinal, answering `n` with `n * n`.
+    fn write_transcript(name: &str, ordinals: &[usize]) -> PathBuf {
+        let path = transcript_path(name);
+        let mut writer = TranscriptWriter::create(&path).unwrap();
+        for (n, &ordinal) in ordinals.iter().enumerate() {
+            let entry = TranscriptEntry::new(
+                ordinal,
+                "SqrtOracle",
+                "sqrt",
+                &json!({ "n": n }),
+                &json!({ "result": n * n }),
+            );
+            writer.record(&entry).unwrap();
+        }
+        path
+    }
+
+    fn divergence(replay: &Replay, ordinal: usize, selector: &str, request: Value) -> String {
+        match replay.response(ordinal, selector, &request) {
+            Err(Error::ReplayDivergence {
+                ordinal: at,
+                reason,
+            }) => {
+                assert_eq!(at, ordinal);
+                reason
+            }
+            other => panic!("expected a divergence, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn it_replays_the_recorded_responses() {
+        let path = write_transcript("round-trip", &[0, 1, 2]);
+        let replay = Replay::load(&path).unwrap();
+        for n in 0..3usize {
+            let response = replay.response(n, "sqrt", &json!({ "n": n })).unwrap();
+            assert_eq!(response, json!({ "result": n * n }));
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_diverges_on_a_missing_call() {
+        let path = write_transcript("missing-call", &[0]);
+        let replay = Replay::load(&path).unwrap();
+        let reason = divergence(&replay, 1, "sqrt", json!({ "n": 1 }));
+        assert_eq!(reason, "the transcript has no more calls");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_diverges_on_an_entry_of_another_ordinal() {
+        let path = write_transcript("ordinal", &[0, 2]);
+        let replay = Replay::load(&path).unwrap();
+        let reason = divergence(&replay, 1, "sqrt", json!({ "n": 1 }));
+        assert_eq!(reason, "the transcript entry has ordinal 2");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_diverges_on_another_selector() {
+        let path = write_transcript("selector", &[0]);
+        let replay = Replay::load(&path).unwrap();
+        let reason = divergence(&replay, 0, "cbrt", json!({ "n": 0 }));
+        assert_eq!(
+            reason,
+            r#"expected sqrt with {"n":0}, got cbrt with {"n":0}"#
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_diverges_on_another_request() {
+        let path = write_transcript("request", &[0]);
+        let replay = Replay::load(&path).unwrap();
+        let reason = divergence(&replay, 0, "sqrt", json!({ "n": 7 }));
+        assert_eq!(
+            reason,
+            r#"expected sqrt with {"n":0}, got sqrt with {"n":7}"#
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
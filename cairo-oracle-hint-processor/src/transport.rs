@@ -0,0 +1,2058 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cairo_proto_serde::configuration::{
+    Configuration, Encoding, HttpMethod, MethodDeclaration, Protocol, ServiceOptions,
+};
+use cairo_proto_serde::msgpack::{self, encode_value};
+use cairo_proto_serde::protobuf::{decode_message, encode_message};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hyper::body::HttpBody;
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper_tls::HttpsConnector;
+use indoc::formatdoc;
+use itertools::Itertools;
+use log::info;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_ENCODING, CONTENT_TYPE, TE,
+};
+use reqwest::redirect::Policy;
+use reqwest::Certificate;
+use reqwest::Method;
+use reqwest::StatusCode;
+use reqwest::Url;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::breaker::{CircuitBreaker, DEFAULT_BREAKER_COOLDOWN};
+use crate::signature::{ResponseVerifier, SIGNATURE_HEADER};
+use crate::{Error, OracleConfig};
+
+/// Delay before the first retry, doubled after every failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Size above which an oracle response is rejected, unless `--oracle-max-response-bytes` is given.
+pub const DEFAULT_MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+/// Length of the response body kept in an [`Error::OracleHttpError`] unless configured otherwise.
+pub const DEFAULT_ERROR_BODY_BYTES: usize = 1024;
+/// Number of redirects an oracle request follows unless `--oracle-max-redirects` is given.
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+/// Path of the requests of a method relative to the server URL, unless the lock gives one.
+const DEFAULT_PATH_TEMPLATE: &str = "{method}";
+
+/// Wire protocol used to reach an oracle server, selected once at startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OracleTransport {
+    /// The request is POSTed as JSON and the response is read from `{"result": ...}`.
+    #[default]
+    Http,
+    /// The request is sent as a unary gRPC call carrying the protobuf encoded message.
+    Grpc,
+    /// The same request as `Http`, sent over the Unix domain socket of a `unix://` URL.
+    Unix,
+}
+
+impl OracleTransport {
+    /// Picks the transport for a server URL, the `unix://` and `grpc://` schemes take precedence
+    /// over the `protocol` declared in the `Oracle.lock`.
+    pub fn select(url: &Url, protocol: Option<Protocol>) -> Self {
+        match (url.scheme(), protocol) {
+            ("unix", _) => OracleTransport::Unix,
+            ("grpc", _) | (_, Some(Protocol::Grpc)) => OracleTransport::Grpc,
+            _ => OracleTransport::Http,
+        }
+    }
+}
+
+/// Compression of the bodies exchanged with the HTTP oracle servers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OracleCompression {
+    #[default]
+    None,
+    /// Responses are requested with `Accept-Encoding: gzip` and requests are sent with
+    /// `Content-Encoding: gzip`, until the server refuses a compressed request.
+    Gzip,
+}
+
+/// Failure of a single attempt to call an oracle.
+enum CallError {
+    /// The server could not be reached, the call may be retried.
+    Transient(String),
+    /// The server failed with a 5xx, the call may be retried. The error is returned once no
+    /// attempt is left.
+    ServerError(Error),
+    /// The call can't succeed by retrying it.
+    Fatal(Error),
+}
+
+impl From<Error> for CallError {
+    fn from(error: Error) -> Self {
+        CallError::Fatal(error)
+    }
+}
+
+/// An oracle server resolved for a selector, with its transport and retry policy.
+#[derive(Debug, Clone)]
+pub(crate) struct OracleServer {
+    pub service: String,
+    pub url: Url,
+    pub transport: OracleTransport,
+    pub timeout: Option<Duration>,
+    pub max_retries: u32,
+    max_response_bytes: u64,
+    /// Length the body of a non-2xx response is truncated to in its error.
+    error_body_bytes: usize,
+    stream_responses: bool,
+    /// Does not print the failed attempts of the retried calls.
+    quiet: bool,
+    /// Encoding of the HTTP bodies, the other transports have their own.
+    encoding: Encoding,
+    /// Cleared once the server refuses a compressed request, shared by the clones of the server.
+    compress_requests: Arc<AtomicBool>,
+    /// Shared by the servers of the same URL, for the whole run or batch.
+    breaker: Arc<CircuitBreaker>,
+    client: Client,
+    grpc: Arc<GrpcClient>,
+    /// Headers of every request, their values are marked sensitive so they are never printed.
+    headers: HeaderMap,
+    /// Set when the service signs its responses with the `public_key` of the lock.
+    verifier: Option<ResponseVerifier>,
+    /// Replicas of the server, from the `failover_urls` of the lock, tried when it fails.
+    failover: Vec<OracleServer>,
+    /// Path of the requests relative to `url`, from the `path_template` of the lock.
+    path_template: String,
+    /// Templates overriding `path_template` for some methods, by selector.
+    method_paths: BTreeMap<String, String>,
+    /// HTTP method of the requests, from the `http_method` of the lock.
+    http_method: Method,
+    /// `Content-Type` of the requests given by the lock, the one of the encoding otherwise.
+    content_type: Option<HeaderValue>,
+    /// Header carrying the [`RequestTrace`] of each request.
+    trace_header: HeaderName,
+}
+
+/// Header carrying the session of a stream, unique to the run that opens it.
+pub const SESSION_HEADER: &str = "x-oracle-session";
+
+/// Header carrying the trace id of the run and the span id of each oracle request, unless
+/// `--oracle-trace-header` names another one.
+pub const DEFAULT_TRACE_HEADER: &str = "traceparent";
+
+/// Correlation ids of an oracle request, so the logs of the servers can be joined to those of
+/// the run: the trace id shared by the requests of a run, and the span id of the call.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestTrace {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+impl RequestTrace {
+    /// The ids of the call `ordinal` of the trace, the same for all its attempts.
+    pub fn new(trace_id: &str, ordinal: usize) -> Self {
+        let digest = Sha256::new()
+            .chain_update(trace_id)
+            .chain_update(ordinal.to_be_bytes())
+            .finalize();
+        Self {
+            trace_id: trace_id.to_string(),
+            span_id: hex(&digest[..8]),
+        }
+    }
+
+    /// The value of the trace header: a W3C `traceparent` for that header, and the trace and
+    /// span ids joined by a dash for another one, such as `x-request-id`.
+    fn header_value(&self, header: &HeaderName) -> HeaderValue {
+        let value = if header.as_str() == DEFAULT_TRACE_HEADER {
+            format!("00-{}-{}-01", self.trace_id, self.span_id)
+        } else {
+            format!("{}-{}", self.trace_id, self.span_id)
+        };
+        HeaderValue::from_str(&value).expect("hexadecimal ids are valid header values")
+    }
+}
+
+/// A new trace id, 16 bytes as hexadecimal, unique to the run or prefetch that uses it.
+pub(crate) fn new_trace_id() -> String {
+    static TRACES: AtomicU64 = AtomicU64::new(0);
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let digest = Sha256::new()
+        .chain_update(std::process::id().to_be_bytes())
+        .chain_update(started.as_nanos().to_be_bytes())
+        .chain_update(TRACES.fetch_add(1, Ordering::Relaxed).to_be_bytes())
+        .finalize();
+    hex(&digest[..16])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Stream of the responses of a `streaming` service to one request, read as it is received:
+/// the body holds one `{"result": ...}` or `{"error": ...}` object per line.
+pub(crate) struct OracleStream {
+    service: String,
+    method: String,
+    server_url: Url,
+    max_response_bytes: u64,
+    reader: BufReader<Response>,
+}
+
+impl OracleStream {
+    /// Reads the next response of the stream, waiting for the server to send it. Blank lines are
+    /// skipped so the server can keep an idle stream alive.
+    pub fn next(&mut self) -> Result<Value, Error> {
+        let mut line = String::new();
+        while line.trim().is_empty() {
+            line.clear();
+            let read = (&mut self.reader)
+                .take(self.max_response_bytes + 1)
+                .read_line(&mut line)
+                .map_err(|e| Error::OracleConnection {
+                    server: self.server_url.to_string(),
+                    reason: e.to_string(),
+                })?;
+            if read == 0 {
+                return Err(Error::OracleStreamEnded {
+                    service: self.service.clone(),
+                    method: self.method.clone(),
+                });
+            }
+            if read as u64 > self.max_response_bytes {
+                return Err(Error::OracleResponseTooLarge {
+                    service: self.service.clone(),
+                    limit: self.max_response_bytes,
+                });
+            }
+        }
+        let body = serde_json::from_str::<Value>(&line).map_err(|e| Error::OracleConnection {
+            server: self.server_url.to_string(),
+            reason: format!("invalid JSON response in stream: {e}"),
+        })?;
+        if let Some(rejection) = rejection_from_value(&body, &self.service) {
+            return Err(rejection);
+        }
+        result_from_value(body, &self.service, &self.method)
+    }
+}
+
+/// HTTP clients shared by the oracle servers, configured with the TLS settings of the run, and
+/// the circuit breaker of each server URL.
+pub(crate) struct OracleClients {
+    http: Client,
+    grpc: Arc<GrpcClient>,
+    breaker_threshold: Option<u32>,
+    breaker_cooldown: Duration,
+    breakers: Mutex<HashMap<Url, Arc<CircuitBreaker>>>,
+}
+
+impl OracleClients {
+    pub fn new(oracle_config: &OracleConfig) -> Result<Self, Error> {
+        // the PEM is also given to the gRPC client, which builds its own TLS connector
+        let ca_cert = match &oracle_config.ca_cert {
+            Some(ca_cert) => {
+                let pem = std::fs::read(ca_cert)?;
+                let certificate = Certificate::from_pem(&pem).map_err(|e| {
+                    Error::ConfigError(format!("Invalid CA certificate {}: {e}", ca_cert.display()))
+                })?;
+                Some((pem, certificate))
+            }
+            None => None,
+        };
+        let builder = || {
+            let mut builder = Client::builder()
+                .danger_accept_invalid_certs(oracle_config.insecure)
+                .gzip(oracle_config.compression == OracleCompression::Gzip)
+                .redirect(redirect_policy(
+                    oracle_config.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+                    oracle_config.allowed_hosts.clone(),
+                ));
+            if let Some(pool_size) = oracle_config.pool_size {
+                builder = builder.pool_max_idle_per_host(pool_size);
+            }
+            if let Some((_, certificate)) = &ca_cert {
+                builder = builder.add_root_certificate(certificate.clone());
+            }
+            builder
+        };
+        let build = |builder: ClientBuilder| {
+            builder
+                .build()
+                .map_err(|e| Error::ConfigError(format!("Failed to build the HTTP client: {e}")))
+        };
+        let grpc = GrpcClient::new(oracle_config, ca_cert.as_ref().map(|(pem, _)| &pem[..]))?;
+        Ok(Self {
+            http: build(builder())?,
+            grpc: Arc::new(grpc),
+            breaker_threshold: oracle_config.breaker_threshold,
+            breaker_cooldown: oracle_config
+                .breaker_cooldown
+                .unwrap_or(DEFAULT_BREAKER_COOLDOWN),
+            breakers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn breaker(&self, url: &Url) -> Arc<CircuitBreaker> {
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(url.clone()).or_insert_with(|| {
+            Arc::new(CircuitBreaker::new(
+                self.breaker_threshold,
+                self.breaker_cooldown,
+            ))
+        });
+        breaker.clone()
+    }
+}
+
+/// HTTP/2 client of the gRPC calls. Unlike the blocking `reqwest` client it reads the trailers of
+/// a response, where the server sends the status of a call answered with a message.
+pub(crate) struct GrpcClient {
+    runtime: tokio::runtime::Runtime,
+    client: hyper::Client<HttpsConnector<HttpConnector>>,
+}
+
+/// A gRPC response, its body read as a whole.
+struct GrpcResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    trailers: HeaderMap,
+}
+
+/// Failure of a gRPC call before its response is read.
+enum GrpcFailure {
+    Http(hyper::Error),
+    Timeout,
+    TooLarge,
+}
+
+impl GrpcClient {
+    fn new(oracle_config: &OracleConfig, ca_cert: Option<&[u8]>) -> Result<Self, Error> {
+        let config_error = |e: &dyn std::fmt::Display| {
+            Error::ConfigError(format!("Failed to build the gRPC client: {e}"))
+        };
+        let mut tls = native_tls::TlsConnector::builder();
+        tls.danger_accept_invalid_certs(oracle_config.insecure)
+            .request_alpns(&["h2"]);
+        if let Some(pem) = ca_cert {
+            let certificate =
+                native_tls::Certificate::from_pem(pem).map_err(|e| config_error(&e))?;
+            tls.add_root_certificate(certificate);
+        }
+        let tls = tls.build().map_err(|e| config_error(&e))?;
+        let mut http = HttpConnector::new();
+        // `https://` servers of the `grpc` protocol go through the TLS connector
+        http.enforce_http(false);
+        let mut builder = hyper::Client::builder();
+        builder.http2_only(true);
+        if let Some(pool_size) = oracle_config.pool_size {
+            builder.pool_max_idle_per_host(pool_size);
+        }
+        // the calls are made one at a time, a single thread drives them and their connections
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| config_error(&e))?;
+        Ok(Self {
+            runtime,
+            client: builder.build(HttpsConnector::from((http, tls.into()))),
+        })
+    }
+
+    /// POSTs `frame` to `url` and reads the response up to its trailers, failing once the body
+    /// exceeds `max_bytes`.
+    fn unary(
+        &self,
+        url: &Url,
+        headers: HeaderMap,
+        frame: Vec<u8>,
+        timeout: Option<Duration>,
+        max_bytes: u64,
+    ) -> Result<GrpcResponse, GrpcFailure> {
+        let mut request = hyper::Request::new(Body::from(frame));
+        *request.method_mut() = Method::POST;
+        *request.uri_mut() = url.as_str().parse().expect("a URL is a valid URI");
+        *request.headers_mut() = headers;
+        let call = async {
+            let response = self
+                .client
+                .request(request)
+                .await
+                .map_err(GrpcFailure::Http)?;
+            let (parts, mut body) = response.into_parts();
+            let mut bytes = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.map_err(GrpcFailure::Http)?;
+                if (bytes.len() + chunk.len()) as u64 > max_bytes {
+                    return Err(GrpcFailure::TooLarge);
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            let trailers = body.trailers().await.map_err(GrpcFailure::Http)?;
+            Ok(GrpcResponse {
+                status: parts.status,
+                headers: parts.headers,
+                body: bytes,
+                trailers: trailers.unwrap_or_default(),
+            })
+        };
+        self.runtime.block_on(async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, call)
+                    .await
+                    .unwrap_or(Err(GrpcFailure::Timeout)),
+                None => call.await,
+            }
+        })
+    }
+}
+
+/// Follows up to `max_redirects` redirects, 307 and 308 ones sending the request body again.
+/// A redirect to a URL already requested fails at once instead of going round in circles until
+/// the limit, and so does a redirect out of the `allowed_hosts`.
+fn redirect_policy(max_redirects: usize, allowed_hosts: Vec<String>) -> Policy {
+    Policy::custom(move |attempt| {
+        if !is_allowed_host(attempt.url(), &allowed_hosts) {
+            let error = HostNotAllowed {
+                url: attempt.url().clone(),
+                allowed: allowed_hosts.join(", "),
+            };
+            attempt.error(error)
+        } else if attempt.previous().contains(attempt.url()) {
+            let loop_url = attempt.url().to_string();
+            attempt.error(format!("redirect loop back to {loop_url}"))
+        } else if attempt.previous().len() > max_redirects {
+            attempt.error(format!(
+                "more than {max_redirects} redirects, raise --oracle-max-redirects if they are expected"
+            ))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// The error of a redirect to a host out of the allowed ones.
+#[derive(Debug)]
+struct HostNotAllowed {
+    url: Url,
+    allowed: String,
+}
+
+impl std::fmt::Display for HostNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "redirect to {}, which is not an allowed host", self.url)
+    }
+}
+
+impl std::error::Error for HostNotAllowed {}
+
+/// Whether `url` is one of the `allowed_hosts`, given as `host` for any port or `host:port`.
+/// Every URL is allowed when the list is empty, and none of the Unix sockets otherwise.
+fn is_allowed_host(url: &Url, allowed_hosts: &[String]) -> bool {
+    if allowed_hosts.is_empty() {
+        return true;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    allowed_hosts.iter().any(|allowed| {
+        let (allowed_host, allowed_port) = match allowed.rsplit_once(':') {
+            Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+                (host, port.parse::<u16>().ok())
+            }
+            _ => (allowed.as_str(), None),
+        };
+        allowed_host.eq_ignore_ascii_case(host)
+            && (allowed_port.is_none() || url.port_or_known_default() == allowed_port)
+    })
+}
+
+/// Header carrying the `--oracle-seed` of the run, for oracles returning random values.
+pub const SEED_HEADER: &str = "x-oracle-seed";
+
+impl OracleServer {
+    pub fn new(
+        service: &str,
+        url: Url,
+        options: Option<&ServiceOptions>,
+        oracle_config: &OracleConfig,
+        clients: &OracleClients,
+    ) -> Result<Self, Error> {
+        if !is_allowed_host(&url, &oracle_config.allowed_hosts) {
+            return Err(Error::OracleHostNotAllowed {
+                server: url.to_string(),
+                allowed: oracle_config.allowed_hosts.join(", "),
+            });
+        }
+        let transport = OracleTransport::select(&url, options.and_then(|o| o.protocol));
+        let timeout = oracle_config.timeout.or_else(|| {
+            options
+                .and_then(|o| o.timeout_ms)
+                .map(Duration::from_millis)
+        });
+        let lock_headers = options
+            .into_iter()
+            .flat_map(|o| &o.headers)
+            .map(|(name, value)| expand_env(value).map(|value| (name, value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                Error::ConfigError(format!("Invalid header for service {service}: {e}"))
+            })?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in lock_headers.into_iter().chain(
+            oracle_config
+                .headers
+                .iter()
+                .map(|(name, value)| (name, value.clone())),
+        ) {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::ConfigError(format!("Invalid header name {name}: {e}")))?;
+            let mut value = HeaderValue::from_str(&value)
+                .map_err(|e| Error::ConfigError(format!("Invalid value for header {name}: {e}")))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        if let Some(seed) = oracle_config.seed {
+            headers.insert(SEED_HEADER, HeaderValue::from(seed));
+        }
+        let verifier = options
+            .and_then(|o| o.public_key.as_deref())
+            .map(|public_key| ResponseVerifier::new(service, public_key))
+            .transpose()?;
+        let path_template = options
+            .and_then(|o| o.path_template.clone())
+            .unwrap_or_else(|| DEFAULT_PATH_TEMPLATE.to_string());
+        let method_paths = options.map(|o| o.method_paths.clone()).unwrap_or_default();
+        for template in method_paths.values().chain([&path_template]) {
+            expand_path(template, service, "method").map_err(|e| {
+                Error::ConfigError(format!("Invalid path template of service {service}: {e}"))
+            })?;
+        }
+        let encoding = oracle_config
+            .encoding
+            .or_else(|| options.and_then(|o| o.encoding))
+            .unwrap_or_default();
+        let http_method = match options.and_then(|o| o.http_method).unwrap_or_default() {
+            HttpMethod::Post => Method::POST,
+            HttpMethod::Put => Method::PUT,
+        };
+        let content_type = options
+            .and_then(|o| o.content_type.as_deref())
+            .map(|content_type| {
+                HeaderValue::from_str(content_type).map_err(|e| {
+                    Error::ConfigError(format!("Invalid content_type of service {service}: {e}"))
+                })
+            })
+            .transpose()?;
+        let trace_header = HeaderName::from_bytes(
+            oracle_config
+                .trace_header
+                .as_deref()
+                .unwrap_or(DEFAULT_TRACE_HEADER)
+                .as_bytes(),
+        )
+        .map_err(|e| Error::ConfigError(format!("Invalid trace header name: {e}")))?;
+        let run_context = options.is_some_and(|o| o.run_context);
+        if run_context && (transport == OracleTransport::Grpc || encoding != Encoding::Json) {
+            return Err(Error::ConfigError(format!(
+                "Oracle service {service} is sent the run context, which needs JSON requests over HTTP"
+            )));
+        }
+        if verifier.is_some() && transport != OracleTransport::Http {
+            return Err(Error::ConfigError(format!(
+                "Oracle service {service} signs its responses, which needs an HTTP server"
+            )));
+        }
+
+        Ok(Self {
+            service: service.to_string(),
+            breaker: clients.breaker(&url),
+            url,
+            transport,
+            timeout,
+            max_retries: options.and_then(|o| o.max_retries).unwrap_or_default(),
+            max_response_bytes: oracle_config
+                .max_response_bytes
+                .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES),
+            error_body_bytes: oracle_config
+                .error_body_bytes
+                .unwrap_or(DEFAULT_ERROR_BODY_BYTES),
+            stream_responses: oracle_config.stream_responses,
+            quiet: oracle_config.quiet,
+            encoding,
+            compress_requests: Arc::new(AtomicBool::new(
+                oracle_config.compression == OracleCompression::Gzip,
+            )),
+            client: clients.http.clone(),
+            grpc: clients.grpc.clone(),
+            headers,
+            verifier,
+            failover: Vec::new(),
+            path_template,
+            method_paths,
+            http_method,
+            content_type,
+            trace_header,
+        })
+    }
+
+    /// The URL of the requests of `selector`, its path expanded from the template of the method
+    /// and appended to the path of the server URL.
+    fn method_url(&self, selector: &str) -> Url {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .expect("cannot be a base URL")
+            .pop_if_empty()
+            .extend(
+                self.method_path(selector)
+                    .split('/')
+                    .filter(|s| !s.is_empty()),
+            );
+        url
+    }
+
+    /// The `Content-Type` of the requests, `default` being the one of their encoding.
+    fn content_type(&self, default: &'static str) -> HeaderValue {
+        self.content_type
+            .clone()
+            .unwrap_or_else(|| HeaderValue::from_static(default))
+    }
+
+    fn method_path(&self, selector: &str) -> String {
+        let template = self
+            .method_paths
+            .get(selector)
+            .unwrap_or(&self.path_template);
+        // the templates are checked when the server is created
+        expand_path(template, &self.service, selector).unwrap_or_else(|_| selector.to_string())
+    }
+
+    /// Sends `data` to the oracle and returns the JSON representation of its response, along with
+    /// the number of attempts it took.
+    ///
+    /// Connection failures and 5xx responses are retried with an exponential backoff, up to
+    /// `max_retries` times. A 5xx on the last attempt fails with its status and body.
+    ///
+    /// The attempts go to the server and its `failover` replicas in turn, each of them being tried
+    /// at least once, and the backoff only applies once all of them have failed. While the circuit
+    /// breaker of a replica is open it is skipped, the call fails without reaching any server once
+    /// all of them are open.
+    ///
+    /// Every attempt carries the same `trace` in the trace header.
+    pub fn call(
+        &self,
+        configuration: &Configuration,
+        method: &MethodDeclaration,
+        selector: &str,
+        data: &Value,
+        trace: &RequestTrace,
+    ) -> Result<(Value, u32), Error> {
+        let replicas = std::iter::once(self)
+            .chain(&self.failover)
+            .collect::<Vec<_>>();
+        let attempts = (self.max_retries + 1).max(replicas.len() as u32);
+        let mut backoff = INITIAL_BACKOFF;
+        let mut last_reason = String::new();
+        for attempt in 1..=attempts {
+            let first = (attempt as usize - 1) % replicas.len();
+            let Some(server) = (0..replicas.len())
+                .map(|i| replicas[(first + i) % replicas.len()])
+                .find(|replica| replica.breaker.open().is_none())
+            else {
+                let (retry_in, failures) = self.breaker.open().unwrap_or_default();
+                return Err(Error::OracleCircuitOpen {
+                    server: self.url.to_string(),
+                    failures,
+                    retry_in,
+                });
+            };
+            let result = match server.transport {
+                OracleTransport::Http => {
+                    server.call_http(configuration, method, selector, data, trace)
+                }
+                OracleTransport::Grpc => server.call_grpc(configuration, method, data, trace),
+                OracleTransport::Unix => server.call_unix(selector, data, trace),
+            };
+            match &result {
+                Ok(_) => server.breaker.record_success(),
+                Err(CallError::Transient(_) | CallError::ServerError(_)) => {
+                    server.breaker.record_failure()
+                }
+                Err(CallError::Fatal(_)) => {}
+            }
+            let reason = match result {
+                Ok(output) => {
+                    if !self.failover.is_empty() {
+                        info!("{}.{selector} answered by {}", self.service, server.url);
+                    }
+                    return Ok((output, attempt));
+                }
+                Err(CallError::Fatal(error)) => return Err(error),
+                Err(CallError::ServerError(error)) if attempt == attempts => return Err(error),
+                Err(CallError::Transient(reason)) if attempts == 1 => {
+                    return Err(Error::OracleConnection {
+                        server: server.url.to_string(),
+                        reason,
+                    })
+                }
+                Err(CallError::Transient(reason)) => reason,
+                Err(CallError::ServerError(error)) => error.to_string(),
+            };
+            if !self.quiet {
+                eprintln!(
+                    "oracle call to {} failed (attempt {attempt}/{attempts}): {reason}",
+                    server.url
+                );
+            }
+            // the next replica is tried right away; an open breaker fails the next attempt, there
+            // is no point in waiting for it
+            if attempt < attempts
+                && attempt as usize % replicas.len() == 0
+                && replicas
+                    .iter()
+                    .any(|replica| replica.breaker.open().is_none())
+            {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            last_reason = reason;
+        }
+        Err(Error::OracleUnavailable {
+            service: self.service.clone(),
+            attempts,
+            reason: last_reason,
+        })
+    }
+
+    /// POSTs `data` to a `streaming` service and returns the stream of its responses, sent with
+    /// the `session` of the run in the `x-oracle-session` header and the `trace` of the call.
+    ///
+    /// The stream stays open for the rest of the run, so it is not bounded by the timeout of the
+    /// server, and it is not retried: the responses already read can't be sent again.
+    pub fn open_stream(
+        &self,
+        selector: &str,
+        data: &Value,
+        session: &str,
+        trace: &RequestTrace,
+    ) -> Result<OracleStream, Error> {
+        if self.transport != OracleTransport::Http || self.encoding != Encoding::Json {
+            return Err(Error::ConfigError(format!(
+                "Oracle service {} is streaming, which needs an HTTP server with the JSON encoding",
+                self.service
+            )));
+        }
+        if self.verifier.is_some() {
+            return Err(Error::ConfigError(format!(
+                "Oracle service {} is streaming, its responses can't be signed",
+                self.service
+            )));
+        }
+        if let Some((retry_in, failures)) = self.breaker.open() {
+            return Err(Error::OracleCircuitOpen {
+                server: self.url.to_string(),
+                failures,
+                retry_in,
+            });
+        }
+        let server_url = self.method_url(selector);
+        let response = self
+            .client
+            .request(self.http_method.clone(), server_url.clone())
+            .headers(self.headers.clone())
+            .header(SESSION_HEADER, session)
+            .header(
+                self.trace_header.clone(),
+                trace.header_value(&self.trace_header),
+            )
+            .header(CONTENT_TYPE, self.content_type("application/json"))
+            .header(ACCEPT, "application/x-ndjson")
+            .body(data.to_string())
+            .send()
+            .map_err(|e| match transient_or_fatal(e, &server_url) {
+                CallError::Transient(reason) => {
+                    self.breaker.record_failure();
+                    Error::OracleConnection {
+                        server: server_url.to_string(),
+                        reason,
+                    }
+                }
+                CallError::ServerError(error) | CallError::Fatal(error) => error,
+            })?;
+        let status = response.status();
+        if status.is_server_error() {
+            self.breaker.record_failure();
+        } else {
+            self.breaker.record_success();
+        }
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(rejection(&body, &self.service)
+                .unwrap_or_else(|| self.http_error(status.as_u16(), &body)));
+        }
+        Ok(OracleStream {
+            service: self.service.clone(),
+            method: selector.to_string(),
+            server_url,
+            max_response_bytes: self.max_response_bytes,
+            reader: BufReader::new(response),
+        })
+    }
+
+    /// Sends a GET to `health_path`, relative to the URL of the server. Only HTTP servers are
+    /// probed, the others are assumed healthy.
+    fn probe(&self, health_path: &str) -> Result<(), String> {
+        if self.transport != OracleTransport::Http {
+            return Ok(());
+        }
+        let url = self
+            .url
+            .join(health_path)
+            .map_err(|e| format!("invalid health path {health_path}: {e}"))?;
+        let response = self
+            .with_timeout(self.client.get(url.clone()).headers(self.headers.clone()))
+            .send()
+            .map_err(|e| format!("{url}: {e}"))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("{url} responded with {}", response.status()))
+        }
+    }
+
+    fn with_timeout(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+
+    fn call_http(
+        &self,
+        configuration: &Configuration,
+        method: &MethodDeclaration,
+        selector: &str,
+        data: &Value,
+        trace: &RequestTrace,
+    ) -> Result<Value, CallError> {
+        let server_url = self.method_url(selector);
+
+        let (content_type, body) = match self.encoding {
+            Encoding::Json => ("application/json", data.to_string().into_bytes()),
+            Encoding::Msgpack => (
+                "application/msgpack",
+                encode_value(configuration, &method.input, data).map_err(|e| {
+                    Error::ConfigError(format!("cannot encode the {selector} request: {e}"))
+                })?,
+            ),
+        };
+        let compressed = self.compress_requests.load(Ordering::Relaxed);
+        let request = self
+            .client
+            .request(self.http_method.clone(), server_url.clone())
+            .headers(self.headers.clone())
+            .header(
+                self.trace_header.clone(),
+                trace.header_value(&self.trace_header),
+            )
+            .header(CONTENT_TYPE, self.content_type(content_type))
+            .header(ACCEPT, content_type);
+        let request = if compressed {
+            request
+                .header(CONTENT_ENCODING, "gzip")
+                .body(gzip(&body).map_err(Error::IO)?)
+        } else {
+            request.body(body)
+        };
+        let req = self
+            .with_timeout(request)
+            .send()
+            .map_err(|e| transient_or_fatal(e, &server_url))?;
+        if req.url() != &server_url {
+            info!("{}.{selector} redirected to {}", self.service, req.url());
+        }
+
+        // servers that don't decompress the requests refuse them as unsupported, another 4xx is
+        // about the request itself and fails the call as it is
+        if compressed && req.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            self.compress_requests.store(false, Ordering::Relaxed);
+            return self.call_http(configuration, method, selector, data, trace);
+        }
+
+        if req.status().is_server_error() {
+            let status = req.status();
+            let body = req.text().unwrap_or_default();
+            return Err(CallError::ServerError(
+                self.http_error(status.as_u16(), &body),
+            ));
+        }
+
+        let signature = req
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        // the signature covers the body as received, it can't be parsed while it is read
+        let verify = |body: &[u8]| match &self.verifier {
+            Some(verifier) => verifier.verify(body, signature.as_deref()),
+            None => Ok(()),
+        };
+
+        if self.encoding == Encoding::Msgpack {
+            let status = req.status();
+            let body = self.read_body(req, &server_url)?;
+            if status.is_success() {
+                if body.is_empty() {
+                    return Err(self.empty_response(selector, status.as_u16()).into());
+                }
+                verify(&body)?;
+            }
+            let http_error = || {
+                let body = String::from_utf8_lossy(&body);
+                self.http_error(status.as_u16(), &body).into()
+            };
+            return match msgpack::decode_response(configuration, &method.output, &body) {
+                Ok(msgpack::Response::Error(message)) => Err(Error::OracleRejected {
+                    service: self.service.clone(),
+                    message,
+                }
+                .into()),
+                Ok(msgpack::Response::Result(output)) if status.is_success() => Ok(output),
+                Ok(msgpack::Response::Result(_)) => Err(http_error()),
+                Err(e) if status.is_success() => Err(Error::OracleConnection {
+                    server: server_url.to_string(),
+                    reason: format!("invalid MessagePack response: {e}"),
+                }
+                .into()),
+                Err(_) => Err(http_error()),
+            };
+        }
+
+        let status = req.status();
+        if self.stream_responses && status.is_success() && self.verifier.is_none() {
+            let body = self.parse_body(req, &server_url, selector)?;
+            if let Some(rejection) = rejection_from_value(&body, &self.service) {
+                return Err(rejection.into());
+            }
+            return result_from_value(body, &self.service, selector).map_err(Into::into);
+        }
+
+        let body = self.read_body(req, &server_url)?;
+        if !status.is_success() {
+            let body = String::from_utf8_lossy(&body);
+            let error = rejection(&body, &self.service)
+                .unwrap_or_else(|| self.http_error(status.as_u16(), &body));
+            return Err(error.into());
+        }
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Err(self.empty_response(selector, status.as_u16()).into());
+        }
+        verify(&body)?;
+        let body = String::from_utf8(body).map_err(|e| {
+            invalid_response(
+                &self.service,
+                selector,
+                format!("the body is not UTF-8: {e}"),
+            )
+        })?;
+        if let Some(rejection) = rejection(&body, &self.service) {
+            return Err(rejection.into());
+        }
+
+        result_from_body(&body, &self.service, selector).map_err(Into::into)
+    }
+
+    /// Reads the body of `response`, failing as soon as it is larger than `max_response_bytes`.
+    fn read_body(&self, response: Response, server_url: &Url) -> Result<Vec<u8>, CallError> {
+        self.check_content_length(&response)?;
+        let mut body = Vec::new();
+        response
+            .take(self.max_response_bytes + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| Error::OracleConnection {
+                server: server_url.to_string(),
+                reason: e.to_string(),
+            })?;
+        if body.len() as u64 > self.max_response_bytes {
+            return Err(self.too_large().into());
+        }
+        Ok(body)
+    }
+
+    /// Parses the JSON body of `response` as it is received, so it is never held in memory as a
+    /// whole besides its parsed value.
+    fn parse_body(
+        &self,
+        response: Response,
+        server_url: &Url,
+        selector: &str,
+    ) -> Result<Value, CallError> {
+        self.check_content_length(&response)?;
+        let status = response.status().as_u16();
+        let mut body = BufReader::new(response.take(self.max_response_bytes + 1));
+        let blank = is_blank(&mut body).map_err(|e| Error::OracleConnection {
+            server: server_url.to_string(),
+            reason: e.to_string(),
+        })?;
+        if blank {
+            return Err(self.empty_response(selector, status).into());
+        }
+        let value = serde_json::from_reader(&mut body);
+        if body.get_ref().limit() == 0 {
+            return Err(self.too_large().into());
+        }
+        value.map_err(|e| {
+            Error::OracleConnection {
+                server: server_url.to_string(),
+                reason: format!("invalid JSON response: {e}"),
+            }
+            .into()
+        })
+    }
+
+    /// Fails early on a response announcing a body larger than `max_response_bytes`.
+    fn check_content_length(&self, response: &Response) -> Result<(), CallError> {
+        match response.content_length() {
+            Some(length) if length > self.max_response_bytes => Err(self.too_large().into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The error of a non-2xx response, with its body truncated to `error_body_bytes`.
+    fn http_error(&self, status: u16, body: &str) -> Error {
+        Error::OracleHttpError {
+            service: self.service.clone(),
+            status,
+            body: truncate_body(body, self.error_body_bytes),
+        }
+    }
+
+    /// The error of a 2xx response without a body, or with only whitespace.
+    fn empty_response(&self, selector: &str, status: u16) -> Error {
+        Error::OracleEmptyResponse {
+            service: self.service.clone(),
+            method: selector.to_string(),
+            status,
+        }
+    }
+
+    fn too_large(&self) -> Error {
+        Error::OracleResponseTooLarge {
+            service: self.service.clone(),
+            limit: self.max_response_bytes,
+        }
+    }
+
+    #[cfg(unix)]
+    fn call_unix(
+        &self,
+        selector: &str,
+        data: &Value,
+        trace: &RequestTrace,
+    ) -> Result<Value, CallError> {
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+
+        let socket = self.url.path();
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|e| CallError::Transient(format!("{socket}: {e}")))?;
+        stream
+            .set_read_timeout(self.timeout)
+            .and_then(|_| stream.set_write_timeout(self.timeout))
+            .map_err(Error::IO)?;
+
+        let body = data.to_string();
+        let trace_value = trace.header_value(&self.trace_header);
+        let headers = self
+            .headers
+            .iter()
+            .chain([(&self.trace_header, &trace_value)])
+            .map(|(name, value)| {
+                format!("{name}: {}\r\n", String::from_utf8_lossy(value.as_bytes()))
+            })
+            .join("");
+        let path = self.method_path(selector);
+        let path = path.trim_start_matches('/');
+        let content_type = self.content_type("application/json");
+        let request = format!(
+            "{} /{path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+            self.http_method,
+            String::from_utf8_lossy(content_type.as_bytes()),
+            body.len()
+        );
+        let mut response = Vec::new();
+        stream
+            .write_all(request.as_bytes())
+            .and_then(|_| {
+                (&stream)
+                    .take(self.max_response_bytes + 1)
+                    .read_to_end(&mut response)
+            })
+            .map_err(|e| CallError::Transient(format!("{socket}: {e}")))?;
+        // the headers count in the limit, they are small next to the bodies it guards against
+        if response.len() as u64 > self.max_response_bytes {
+            return Err(self.too_large().into());
+        }
+
+        let malformed = || Error::OracleConnection {
+            server: self.url.to_string(),
+            reason: "malformed HTTP response".to_string(),
+        };
+        let response = String::from_utf8_lossy(&response);
+        let (head, body) = response.split_once("\r\n\r\n").ok_or_else(malformed)?;
+        let mut headers = head.lines();
+        let status: u16 = headers
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(malformed)?;
+        let chunked = headers.any(|header| {
+            let header = header.to_ascii_lowercase();
+            header.starts_with("transfer-encoding:") && header.contains("chunked")
+        });
+        let body = if chunked {
+            decode_chunked(body).ok_or_else(malformed)?
+        } else {
+            body.to_string()
+        };
+
+        if (500..600).contains(&status) {
+            return Err(CallError::ServerError(self.http_error(status, &body)));
+        }
+        if let Some(rejection) = rejection(&body, &self.service) {
+            return Err(rejection.into());
+        }
+        if !(200..300).contains(&status) {
+            return Err(self.http_error(status, &body).into());
+        }
+        if body.trim().is_empty() {
+            return Err(self.empty_response(selector, status).into());
+        }
+        result_from_body(&body, &self.service, selector).map_err(Into::into)
+    }
+
+    #[cfg(not(unix))]
+    fn call_unix(
+        &self,
+        _selector: &str,
+        _data: &Value,
+        _trace: &RequestTrace,
+    ) -> Result<Value, CallError> {
+        Err(unix_sockets_unsupported(&self.url).into())
+    }
+
+    fn call_grpc(
+        &self,
+        configuration: &Configuration,
+        method: &MethodDeclaration,
+        data: &Value,
+        trace: &RequestTrace,
+    ) -> Result<Value, CallError> {
+        let path = method.grpc_path.as_ref().ok_or_else(|| {
+            Error::Grpc("method has no gRPC path, regenerate the Oracle.lock".to_string())
+        })?;
+        // `grpc://` is not a scheme HTTP clients understand, the call goes over plain HTTP/2.
+        let base = match self.url.scheme() {
+            "grpc" => format!("http{}", &self.url.as_str()["grpc".len()..]),
+            _ => self.url.to_string(),
+        };
+        let server_url = Url::parse(&base)
+            .and_then(|url| url.join(path))
+            .map_err(|e| Error::ConfigError(format!("Invalid gRPC URL {base}: {e}")))?;
+
+        let message = encode_message(configuration, &method.input, data).map_err(Error::Grpc)?;
+        // Length-prefixed message: compression flag followed by the big endian message size.
+        let mut frame = Vec::with_capacity(5 + message.len());
+        frame.push(0);
+        frame.extend((message.len() as u32).to_be_bytes());
+        frame.extend(message);
+
+        let mut headers = self.headers.clone();
+        headers.insert(
+            self.trace_header.clone(),
+            trace.header_value(&self.trace_header),
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+        let response = self
+            .grpc
+            .unary(
+                &server_url,
+                headers,
+                frame,
+                self.timeout,
+                self.max_response_bytes,
+            )
+            .map_err(|failure| match failure {
+                GrpcFailure::Http(e) => hyper_transient_or_fatal(e, &server_url),
+                GrpcFailure::Timeout => {
+                    CallError::Transient(format!("request to {server_url} timed out"))
+                }
+                GrpcFailure::TooLarge => self.too_large().into(),
+            })?;
+
+        // A server failing the call before sending a message answers with a trailers-only
+        // response, its status in the headers, and one failing after it in the trailers.
+        let grpc_status = [&response.trailers, &response.headers]
+            .into_iter()
+            .find_map(|headers| Some((headers.get("grpc-status")?, headers)));
+        match grpc_status {
+            Some((status, _)) if status == "0" => {}
+            Some((status, headers)) => {
+                let message = headers
+                    .get("grpc-message")
+                    .and_then(|m| m.to_str().ok())
+                    .unwrap_or_default();
+                let error = Error::Grpc(format!(
+                    "call to {server_url} failed with status {status:?}: {message}"
+                ));
+                return Err(if response.status.is_server_error() {
+                    CallError::ServerError(error)
+                } else {
+                    error.into()
+                });
+            }
+            None if response.status.is_server_error() => {
+                return Err(CallError::Transient(format!(
+                    "server responded with {}",
+                    response.status
+                )));
+            }
+            None => {
+                return Err(
+                    Error::Grpc(format!("response from {server_url} has no grpc-status")).into(),
+                );
+            }
+        }
+
+        let body = response.body;
+        let [compressed, l0, l1, l2, l3, ref message @ ..] = body[..] else {
+            return Err(
+                Error::Grpc(format!("response from {server_url} is not a gRPC message")).into(),
+            );
+        };
+        if compressed != 0 {
+            return Err(Error::Grpc("compressed responses are not supported".to_string()).into());
+        }
+        let len = u32::from_be_bytes([l0, l1, l2, l3]) as usize;
+        let message = message
+            .get(..len)
+            .ok_or_else(|| Error::Grpc(format!("truncated response from {server_url}")))?;
+
+        Ok(decode_message(configuration, &method.output, message).map_err(Error::Grpc)?)
+    }
+}
+
+/// Resolves the servers of a run and probes their health, unless the oracle calls are answered
+/// without them.
+pub(crate) fn connect(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+    has_provider: bool,
+) -> Result<HashMap<String, OracleServer>, Error> {
+    // these runs never reach the servers, they don't need to be configured
+    if oracle_config.replay.is_some() || oracle_config.mock_response.is_some() || has_provider {
+        return Ok(HashMap::new());
+    }
+    let servers = resolve_servers(configuration, oracle_config)?;
+    if !oracle_config.skip_health_check {
+        check_health(configuration, &servers)?;
+    }
+    Ok(servers)
+}
+
+/// Sends a GET to the `health_path` of every service declaring one, failing with
+/// `OracleUnavailable` on the first that doesn't answer with a 2xx.
+fn check_health(
+    configuration: &Configuration,
+    servers: &HashMap<String, OracleServer>,
+) -> Result<(), Error> {
+    let probes = servers
+        .values()
+        .filter_map(|server| {
+            let options = configuration.service_options.get(&server.service)?;
+            Some((server, options.health_path.as_ref()?))
+        })
+        // the methods of a service usually share its server, it is probed once
+        .unique_by(|(server, _)| (server.service.clone(), server.url.clone()))
+        .sorted_by_key(|(server, _)| server.service.clone());
+    for (server, health_path) in probes {
+        // a service is healthy as long as one of its replicas answers
+        let mut replicas = std::iter::once(server).chain(&server.failover);
+        let mut reasons = Vec::new();
+        let healthy = replicas.any(|replica| match replica.probe(health_path) {
+            Ok(()) => true,
+            Err(reason) => {
+                reasons.push(reason);
+                false
+            }
+        });
+        if !healthy {
+            return Err(Error::OracleUnavailable {
+                service: server.service.clone(),
+                attempts: 1 + server.failover.len() as u32,
+                reason: reasons.join("; "),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the server of every selector declared in the configuration.
+///
+/// The `--oracle-server` flag takes precedence over the URL of a selector in `servers.json`, then
+/// over the `url` of its service in the `Oracle.lock`, then over the `ORACLE_SERVER` variable.
+pub(crate) fn resolve_servers(
+    configuration: &Configuration,
+    oracle_config: &OracleConfig,
+) -> Result<HashMap<String, OracleServer>, Error> {
+    let clients = OracleClients::new(oracle_config)?;
+    let mut servers = HashMap::new();
+    let mut unmapped = Vec::new();
+    let mut resolutions: BTreeMap<_, Vec<&str>> = BTreeMap::new();
+    for (service, methods) in &configuration.services {
+        let options = configuration.service_options.get(service);
+        for selector in methods.methods.keys() {
+            let Some((server_url, source)) = oracle_config
+                .server
+                .as_ref()
+                .map(|url| (url, "the --oracle-server flag"))
+                .or_else(|| {
+                    let url = configuration.servers_config.get(selector)?;
+                    Some((url, "servers.json"))
+                })
+                .or_else(|| Some((options?.url.as_ref()?, "the Oracle.lock")))
+                .or_else(|| {
+                    let url = oracle_config.fallback_server.as_ref()?;
+                    Some((url, "ORACLE_SERVER"))
+                })
+            else {
+                unmapped.push(format!("{service}.{selector}"));
+                continue;
+            };
+            resolutions
+                .entry((service, server_url, source))
+                .or_default()
+                .push(selector);
+            let server_url = Url::parse(server_url).map_err(|e| {
+                Error::ConfigError(format!("Invalid URL for selector {selector}: {e}"))
+            })?;
+            if server_url.scheme() == "unix" && !cfg!(unix) {
+                return Err(unix_sockets_unsupported(&server_url));
+            }
+            let mut server =
+                OracleServer::new(service, server_url, options, oracle_config, &clients)?;
+            // the replicas only back the `url` of the lock, a server given elsewhere is used alone
+            if source == "the Oracle.lock" {
+                let failover_urls = options
+                    .map(|o| o.failover_urls.as_slice())
+                    .unwrap_or_default();
+                for url in failover_urls {
+                    let url = Url::parse(url).map_err(|e| {
+                        Error::ConfigError(format!("Invalid failover URL of {service}: {e}"))
+                    })?;
+                    if url.scheme() == "unix" && !cfg!(unix) {
+                        return Err(unix_sockets_unsupported(&url));
+                    }
+                    let replica =
+                        OracleServer::new(service, url, options, oracle_config, &clients)?;
+                    server.failover.push(replica);
+                }
+            }
+            servers.insert(selector.clone(), server);
+        }
+    }
+
+    for ((service, url, source), mut selectors) in resolutions {
+        selectors.sort_unstable();
+        info!(
+            "Oracle service {service} uses {url}, given by {source}, for {}",
+            selectors.join(", ")
+        );
+    }
+
+    if !unmapped.is_empty() {
+        return Err(Error::ConfigError(formatdoc! {r#"
+            No oracle server configured for: {}.
+            The server of a method is, in order of precedence: the --oracle-server flag, its entry in servers.json, the `url` of its service in the `service_options` of the Oracle.lock, the ORACLE_SERVER environment variable."#,
+            unmapped.join(", ")
+        }));
+    }
+    Ok(servers)
+}
+
+/// Keeps the first `limit` bytes of `body`, cut at a character boundary.
+fn truncate_body(body: &str, limit: usize) -> String {
+    if body.len() <= limit {
+        return body.to_string();
+    }
+    let end = (0..=limit)
+        .rev()
+        .find(|&i| body.is_char_boundary(i))
+        .unwrap_or(0);
+    format!("{}... ({} more bytes)", &body[..end], body.len() - end)
+}
+
+/// Reads the `OracleRejected` error of an `{"error": <message>}` response body, sent by oracles
+/// that refuse to answer, such as for an input out of their policy.
+fn rejection(body: &str, service: &str) -> Option<Error> {
+    rejection_from_value(&serde_json::from_str::<Value>(body).ok()?, service)
+}
+
+fn rejection_from_value(body: &Value, service: &str) -> Option<Error> {
+    let message = match body.as_object()?.get("error")? {
+        Value::String(message) => message.clone(),
+        message => message.to_string(),
+    };
+    Some(Error::OracleRejected {
+        service: service.to_string(),
+        message,
+    })
+}
+
+/// Extracts `<response_object>` from a `{"result": <response_object>}` response body.
+fn result_from_body(body: &str, service: &str, method: &str) -> Result<Value, Error> {
+    let body = serde_json::from_str::<Value>(body)
+        .map_err(|e| invalid_response(service, method, format!("the body is not JSON: {e}")))?;
+    result_from_value(body, service, method)
+}
+
+/// Same as [`result_from_body`], for a body already parsed.
+fn result_from_value(body: Value, service: &str, method: &str) -> Result<Value, Error> {
+    let invalid = |detail: String| invalid_response(service, method, detail);
+    let not_object =
+        |kind: &str| invalid(format!(r#"expected {{"result": <response>}}, got {kind}"#));
+    let mut body = match body {
+        Value::Object(body) => body,
+        Value::Array(_) => return Err(not_object("an array")),
+        Value::String(_) => return Err(not_object("a string")),
+        Value::Number(_) => return Err(not_object("a number")),
+        Value::Bool(_) => return Err(not_object("a boolean")),
+        Value::Null => return Err(not_object("null")),
+    };
+    let output = body
+        .remove("result")
+        .ok_or_else(|| invalid(r#"expected {"result": <response>}, `result` is missing"#.into()))?;
+    if let Some(key) = body.keys().next() {
+        return Err(invalid(format!(
+            "unexpected field `{key}` next to `result`"
+        )));
+    }
+    Ok(output)
+}
+
+fn invalid_response(service: &str, method: &str, detail: String) -> Error {
+    Error::OracleResponseInvalid {
+        service: service.to_string(),
+        method: method.to_string(),
+        detail,
+    }
+}
+
+fn gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Skips the leading whitespace of `reader`, and returns whether nothing else is left in it.
+fn is_blank(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(true);
+        }
+        let whitespace = buffer
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        let done = whitespace < buffer.len();
+        reader.consume(whitespace);
+        if done {
+            return Ok(false);
+        }
+    }
+}
+
+/// Replaces the `{service}` and `{method}` variables of a `path_template`.
+fn expand_path(template: &str, service: &str, method: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated `{{` in {template:?}"))?;
+        let value = match &rest[start + 1..start + end] {
+            "service" => service,
+            "method" => method,
+            name => {
+                return Err(format!(
+                "unknown variable {{{name}}} in {template:?}, expected {{service}} or {{method}}"
+            ))
+            }
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Replaces the `${NAME}` references of `value` with the environment variable `NAME`.
+fn expand_env(value: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated `${{` in {value:?}"))?;
+        let name = &rest[start + 2..start + end];
+        let var =
+            std::env::var(name).map_err(|_| format!("environment variable {name} is not set"))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&var);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`.
+#[cfg(unix)]
+fn decode_chunked(mut body: &str) -> Option<String> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
+
+fn unix_sockets_unsupported(url: &Url) -> Error {
+    Error::ConfigError(format!(
+        "{url} is a Unix domain socket, which are not supported on this platform"
+    ))
+}
+
+/// Tells whether `error` comes from the TLS layer, such as an untrusted certificate.
+fn is_tls_error(error: &dyn std::error::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(error) = source {
+        let message = error.to_string().to_lowercase();
+        if ["certificate", "tls", "ssl", "handshake"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+        {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+fn transient_or_fatal(error: reqwest::Error, server_url: &Url) -> CallError {
+    // retrying won't make the server certificate trusted
+    if is_tls_error(&error) {
+        CallError::Fatal(Error::OracleTls {
+            server: server_url.to_string(),
+            reason: error.to_string(),
+        })
+    } else if error.is_redirect() {
+        // the reason given by the redirect policy, the error itself only names the URL
+        let reason = std::error::Error::source(&error);
+        if let Some(refused) = reason.and_then(|reason| reason.downcast_ref::<HostNotAllowed>()) {
+            return CallError::Fatal(Error::OracleHostNotAllowed {
+                server: refused.url.to_string(),
+                allowed: refused.allowed.clone(),
+            });
+        }
+        CallError::Fatal(Error::OracleRedirect {
+            server: server_url.to_string(),
+            reason: reason.map_or_else(|| error.to_string(), |reason| reason.to_string()),
+        })
+    } else if error.is_connect() || error.is_timeout() {
+        CallError::Transient(error.to_string())
+    } else {
+        CallError::Fatal(Error::OracleConnection {
+            server: server_url.to_string(),
+            reason: error.to_string(),
+        })
+    }
+}
+
+/// Same as [`transient_or_fatal`] for the errors of the gRPC client, which follows no redirect.
+fn hyper_transient_or_fatal(error: hyper::Error, server_url: &Url) -> CallError {
+    if is_tls_error(&error) {
+        CallError::Fatal(Error::OracleTls {
+            server: server_url.to_string(),
+            reason: error.to_string(),
+        })
+    } else if error.is_connect() || error.is_timeout() {
+        CallError::Transient(error.to_string())
+    } else {
+        CallError::Fatal(Error::OracleConnection {
+            server: server_url.to_string(),
+            reason: error.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        expand_env, expand_path, is_allowed_host, rejection, result_from_body, truncate_body,
+        OracleClients, OracleCompression, OracleServer, RequestTrace,
+    };
+    use crate::{Error, OracleConfig};
+    use cairo_proto_serde::configuration::{
+        Configuration, Field, FieldType, MethodDeclaration, PrimitiveType,
+    };
+    use flate2::read::GzDecoder;
+    use hyper::Body;
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use std::convert::Infallible;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn trace() -> RequestTrace {
+        RequestTrace::new("4bf92f3577b34da6a3ce929d0e0e4736", 0)
+    }
+
+    /// A request read by [`serve_once`], its header lines lowercased.
+    struct Request {
+        headers: Vec<String>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key == name).then_some(value.trim())
+            })
+        }
+    }
+
+    /// Answers the next `calls` connections with `respond`, one request each, and returns the URL
+    /// of the server with the requests it read.
+    fn serve_once(
+        calls: usize,
+        respond: impl Fn(&Request) -> String + Send + 'static,
+    ) -> (Url, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..calls {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    headers.push(line);
+                }
+                let mut request = Request {
+                    headers,
+                    body: Vec::new(),
+                };
+                let length = request
+                    .header("content-length")
+                    .map_or(0, |value| value.parse().unwrap());
+                request.body.resize(length, 0);
+                reader.read_exact(&mut request.body).unwrap();
+                stream.write_all(respond(&request).as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    /// Answers `calls` requests with their own body as result. Compressed requests are refused
+    /// unless `accepts_gzip`.
+    fn echo_server(calls: usize, accepts_gzip: bool) -> (Url, JoinHandle<Vec<Request>>) {
+        serve_once(calls, move |request| {
+            let gzip = request.header("content-encoding") == Some("gzip");
+            if gzip && !accepts_gzip {
+                return "HTTP/1.1 415 Unsupported Media Type\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string();
+            }
+            let mut body = request.body.clone();
+            if gzip {
+                body.clear();
+                GzDecoder::new(&request.body[..])
+                    .read_to_end(&mut body)
+                    .unwrap();
+            }
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            let body = json!({ "result": request }).to_string();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+    }
+
+    /// Returns whether each of the requests the echo server read was compressed.
+    fn call_twice(accepts_gzip: bool) -> Vec<bool> {
+        let calls = if accepts_gzip { 2 } else { 3 };
+        let (url, echo) = echo_server(calls, accepts_gzip);
+
+        let oracle_config = OracleConfig {
+            compression: OracleCompression::Gzip,
+            ..Default::default()
+        };
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let server = OracleServer::new("SqrtOracle", url, None, &oracle_config, &clients).unwrap();
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(PrimitiveType::U64),
+            grpc_path: None,
+        };
+        for n in [4, 9] {
+            let (output, _) = server
+                .call(
+                    &Configuration::default(),
+                    &method,
+                    "sqrt",
+                    &json!({ "n": n }),
+                    &trace(),
+                )
+                .unwrap();
+            assert_eq!(output, json!({ "n": n }));
+        }
+        echo.join()
+            .unwrap()
+            .iter()
+            .map(|request| request.header("content-encoding") == Some("gzip"))
+            .collect()
+    }
+
+    #[test]
+    fn it_compresses_requests_unless_the_server_refuses_them() {
+        assert_eq!(call_twice(true), [true, true]);
+        // the refused request is sent again uncompressed, and so are the next ones
+        assert_eq!(call_twice(false), [true, false, false]);
+    }
+
+    #[test]
+    fn it_fails_over_to_the_next_replica() {
+        // a port nothing listens on anymore refuses the connections
+        let down = TcpListener::bind("127.0.0.1:0").unwrap();
+        let down_url = Url::parse(&format!("http://{}", down.local_addr().unwrap())).unwrap();
+        drop(down);
+        let (url, echo) = echo_server(1, true);
+
+        let oracle_config = OracleConfig::default();
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let mut server =
+            OracleServer::new("SqrtOracle", down_url, None, &oracle_config, &clients).unwrap();
+        let replica = OracleServer::new("SqrtOracle", url, None, &oracle_config, &clients).unwrap();
+        server.failover.push(replica);
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(PrimitiveType::U64),
+            grpc_path: None,
+        };
+        // without retries, the replica still gets its attempt
+        let (output, attempts) = server
+            .call(
+                &Configuration::default(),
+                &method,
+                "sqrt",
+                &json!({ "n": 4 }),
+                &trace(),
+            )
+            .unwrap();
+        assert_eq!((output, attempts), (json!({ "n": 4 }), 2));
+        echo.join().unwrap();
+    }
+
+    #[test]
+    fn it_formats_the_trace_header() {
+        let trace = trace();
+        assert_eq!(trace.span_id.len(), 16);
+        assert_ne!(trace.span_id, RequestTrace::new(&trace.trace_id, 1).span_id);
+        let traceparent = trace.header_value(&HeaderName::from_static("traceparent"));
+        assert_eq!(
+            traceparent.to_str().unwrap(),
+            format!("00-4bf92f3577b34da6a3ce929d0e0e4736-{}-01", trace.span_id)
+        );
+        let request_id = trace.header_value(&HeaderName::from_static("x-request-id"));
+        assert_eq!(
+            request_id.to_str().unwrap(),
+            format!("4bf92f3577b34da6a3ce929d0e0e4736-{}", trace.span_id)
+        );
+    }
+
+    #[test]
+    fn it_matches_the_allowed_hosts() {
+        let allowed = ["oracle.internal".to_string(), "127.0.0.1:3000".to_string()];
+        let is_allowed = |url: &str| is_allowed_host(&Url::parse(url).unwrap(), &allowed);
+        assert!(is_allowed("https://oracle.internal/sqrt"));
+        assert!(is_allowed("http://Oracle.Internal:8080"));
+        assert!(is_allowed("http://127.0.0.1:3000/sqrt"));
+        assert!(!is_allowed("http://127.0.0.1:3001/sqrt"));
+        assert!(!is_allowed("http://oracle.internal.evil.com"));
+        assert!(!is_allowed("unix:///tmp/oracle.sock"));
+        assert!(is_allowed_host(
+            &Url::parse("http://anywhere").unwrap(),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn it_reports_redirect_loops() {
+        let (url, server) = serve_once(1, |_| {
+            "HTTP/1.1 307 Temporary Redirect\r\nLocation: /sqrt\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        });
+
+        let oracle_config = OracleConfig::default();
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let oracle = OracleServer::new("SqrtOracle", url, None, &oracle_config, &clients).unwrap();
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(PrimitiveType::U64),
+            grpc_path: None,
+        };
+        let data = json!({ "n": 4 });
+        let result = oracle.call(&Configuration::default(), &method, "sqrt", &data, &trace());
+        assert!(matches!(
+            result,
+            Err(Error::OracleRedirect { reason, .. }) if reason.starts_with("redirect loop")
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn it_reports_empty_responses() {
+        let (url, server) = serve_once(1, |_| {
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 1\r\nConnection: close\r\n\r\n\n".to_string()
+        });
+
+        let oracle_config = OracleConfig::default();
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let oracle = OracleServer::new("SqrtOracle", url, None, &oracle_config, &clients).unwrap();
+        let method = MethodDeclaration {
+            input: FieldType::Primitive(PrimitiveType::U64),
+            output: FieldType::Primitive(PrimitiveType::U64),
+            grpc_path: None,
+        };
+        let data = json!({ "n": 4 });
+        let result = oracle.call(&Configuration::default(), &method, "sqrt", &data, &trace());
+        assert!(matches!(
+            result,
+            Err(Error::OracleEmptyResponse { method, status: 200, .. }) if method == "sqrt"
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn it_reads_the_responses_of_a_stream_until_it_ends() {
+        // without a length the body lasts until the connection is closed
+        let (url, server) = serve_once(1, |_| {
+            "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n{\"result\": 1}\n\n{\"result\": 2}\n".to_string()
+        });
+
+        let oracle_config = OracleConfig::default();
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let oracle = OracleServer::new("Fib", url, None, &oracle_config, &clients).unwrap();
+        let mut stream = oracle
+            .open_stream("next", &json!({}), "run-1", &trace())
+            .unwrap();
+        assert_eq!(stream.next().unwrap(), json!(1));
+        assert_eq!(stream.next().unwrap(), json!(2));
+        assert!(matches!(
+            stream.next(),
+            Err(Error::OracleStreamEnded { method, .. }) if method == "next"
+        ));
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].header("x-oracle-session"), Some("run-1"));
+    }
+
+    #[test]
+    fn it_expands_env_variables_in_headers() {
+        std::env::set_var("CAIRO_HINTS_TEST_TOKEN", "secret");
+        assert_eq!(
+            expand_env("Bearer ${CAIRO_HINTS_TEST_TOKEN}").unwrap(),
+            "Bearer secret"
+        );
+        assert_eq!(expand_env("plain").unwrap(), "plain");
+        assert!(expand_env("${CAIRO_HINTS_TEST_UNSET}").is_err());
+        assert!(expand_env("${CAIRO_HINTS_TEST_TOKEN").is_err());
+    }
+
+    #[test]
+    fn it_expands_path_templates() {
+        assert_eq!(
+            expand_path("/v2/{method}", "SqrtOracle", "sqrt").unwrap(),
+            "/v2/sqrt"
+        );
+        assert_eq!(
+            expand_path("{service}/{method}", "SqrtOracle", "sqrt").unwrap(),
+            "SqrtOracle/sqrt"
+        );
+        assert!(expand_path("/v2/{selector}", "SqrtOracle", "sqrt").is_err());
+        assert!(expand_path("/v2/{method", "SqrtOracle", "sqrt").is_err());
+    }
+
+    #[test]
+    fn it_truncates_error_bodies_at_a_character_boundary() {
+        assert_eq!(
+            truncate_body("missing field color", 64),
+            "missing field color"
+        );
+        assert_eq!(
+            truncate_body("missing field color", 7),
+            "missing... (12 more bytes)"
+        );
+        // 'é' takes two bytes, it is dropped rather than split
+        assert_eq!(truncate_body("café", 4), "caf... (2 more bytes)");
+    }
+
+    #[test]
+    fn it_reads_rejections_from_the_error_field() {
+        let rejected = rejection(r#"{"error": "n is too large"}"#, "SqrtOracle");
+        assert!(matches!(
+            rejected,
+            Some(Error::OracleRejected { service, message })
+                if service == "SqrtOracle" && message == "n is too large"
+        ));
+        assert!(rejection(r#"{"result": {"n": 2}}"#, "SqrtOracle").is_none());
+        assert!(rejection("not json", "SqrtOracle").is_none());
+    }
+
+    #[test]
+    fn it_reports_malformed_result_bodies() {
+        let detail = |body: &str| match result_from_body(body, "SqrtOracle", "sqrt") {
+            Err(Error::OracleResponseInvalid {
+                service,
+                method,
+                detail,
+            }) if service == "SqrtOracle" && method == "sqrt" => detail,
+            result => panic!("unexpected result {result:?}"),
+        };
+        assert!(detail("<html>").starts_with("the body is not JSON"));
+        assert_eq!(
+            detail("[1, 2]"),
+            r#"expected {"result": <response>}, got an array"#
+        );
+        assert_eq!(
+            detail(r#"{"n": 2}"#),
+            r#"expected {"result": <response>}, `result` is missing"#
+        );
+        assert_eq!(
+            detail(r#"{"result": {"n": 2}, "n": 2}"#),
+            "unexpected field `n` next to `result`"
+        );
+        assert_eq!(
+            result_from_body(r#"{"result": {"n": 2}}"#, "SqrtOracle", "sqrt").unwrap(),
+            json!({ "n": 2 })
+        );
+    }
+
+    /// Answers every gRPC call with the message `n = 2`, followed by `trailers`.
+    fn grpc_server(trailers: HeaderMap) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("grpc://{}", listener.local_addr().unwrap())).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let (stream, _) = listener.accept().await.unwrap();
+                let service = hyper::service::service_fn(move |_| {
+                    let (mut sender, body) = Body::channel();
+                    let trailers = trailers.clone();
+                    tokio::spawn(async move {
+                        sender
+                            .send_data(vec![0, 0, 0, 0, 2, 8, 2].into())
+                            .await
+                            .unwrap();
+                        sender.send_trailers(trailers).await.unwrap();
+                    });
+                    async move {
+                        let response = hyper::Response::builder()
+                            .header(CONTENT_TYPE, "application/grpc")
+                            .body(body);
+                        Ok::<_, Infallible>(response.unwrap())
+                    }
+                });
+                let _ = hyper::server::conn::Http::new()
+                    .http2_only(true)
+                    .serve_connection(stream, service)
+                    .await;
+            });
+        });
+        url
+    }
+
+    fn call_grpc(status: &'static str) -> Result<Value, Error> {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static(status));
+        trailers.insert("grpc-message", HeaderValue::from_static("sqrt failed"));
+        let url = grpc_server(trailers);
+
+        let oracle_config = OracleConfig::default();
+        let clients = OracleClients::new(&oracle_config).unwrap();
+        let server = OracleServer::new("SqrtOracle", url, None, &oracle_config, &clients).unwrap();
+        let mut configuration = Configuration::default();
+        let field = Field {
+            name: "n".into(),
+            ty: FieldType::Primitive(PrimitiveType::U64),
+            number: Some(1),
+        };
+        configuration.messages.insert("Request".into(), vec![field]);
+        let method = MethodDeclaration {
+            input: FieldType::Message("Request".into()),
+            output: FieldType::Message("Request".into()),
+            grpc_path: Some("/oracle.SqrtOracle/Sqrt".into()),
+        };
+        server
+            .call(
+                &configuration,
+                &method,
+                "sqrt",
+                &json!({ "n": 4 }),
+                &trace(),
+            )
+            .map(|(output, _)| output)
+    }
+
+    #[test]
+    fn it_reads_the_grpc_status_of_the_trailers() {
+        assert_eq!(call_grpc("0").unwrap(), json!({ "n": 2 }));
+        // the message was sent before the server failed the call
+        match call_grpc("13") {
+            Err(Error::Grpc(reason)) => assert!(
+                reason.ends_with(r#"failed with status "13": sqrt failed"#),
+                "{reason}"
+            ),
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+}
@@ -145,16 +145,25 @@ impl<'a> CodeGenerator<'a> {
             return;
         }
 
+        let variant_mappings = build_enum_value_mappings(&enum_name, true, enum_values);
+        // the zero value is the default of a proto enum, as of an absent field
+        let has_zero = variant_mappings
+            .iter()
+            .any(|variant| variant.proto_number == 0);
+
         self.push_indent();
-        self.code_buf
-            .push_str("#[derive(Drop, Serde, PartialEq)]\n");
+        if has_zero {
+            self.code_buf
+                .push_str("#[derive(Drop, Copy, Serde, PartialEq, Default)]\n");
+        } else {
+            self.code_buf
+                .push_str("#[derive(Drop, Copy, Serde, PartialEq)]\n");
+        }
         self.push_indent();
         self.code_buf.push_str("enum ");
         self.code_buf.push_str(&enum_name);
         self.code_buf.push_str(" {\n");
 
-        let variant_mappings = build_enum_value_mappings(&enum_name, true, enum_values);
-
         self.depth += 1;
         self.path.push(2);
         let mut mappings_def = Vec::new();
@@ -166,6 +175,10 @@ impl<'a> CodeGenerator<'a> {
             mappings_def.push(m);
             self.path.push(variant.path_idx as i32);
 
+            if variant.proto_number == 0 {
+                self.push_indent();
+                self.code_buf.push_str("#[default]\n");
+            }
             self.push_indent();
             self.code_buf.push_str(&variant.generated_variant_name);
             self.code_buf.push_str(",\n");
@@ -174,7 +187,18 @@ impl<'a> CodeGenerator<'a> {
         }
 
         self.path.pop();
-        self.serde_config.enums.insert(enum_name, mappings_def);
+        // keyed by its path, as the messages and the enum fields referencing it
+        let enum_key = if self.type_path.is_empty() {
+            format!("{}::{}", self.package, enum_name)
+        } else {
+            format!(
+                "{}::{}::{}",
+                self.package,
+                self.type_path.join("::").to_lowercase(),
+                enum_name
+            )
+        };
+        self.serde_config.enums.insert(enum_key, mappings_def);
 
         self.depth -= 1;
 
@@ -412,25 +436,29 @@ impl<'a> CodeGenerator<'a> {
         self.code_buf.push_str(",\n");
 
         let ty_without_super = self.remove_super(&ty);
+        let element = if type_ == Type::Enum {
+            FieldType::Enum(ty_without_super)
+        } else {
+            ty_without_super.into()
+        };
+        let number = Some(field.number());
         if repeated {
             Field {
                 name: field_name,
-                ty: FieldType::Array(Box::new(ty_without_super.into())),
+                ty: FieldType::Array(Box::new(element)),
+                number,
             }
         } else if optional {
             Field {
                 name: field_name,
-                ty: FieldType::Option(Box::new(ty_without_super.into())),
-            }
-        } else if type_ == Type::Enum {
-            Field {
-                name: field_name,
-                ty: FieldType::Enum(ty_without_super),
+                ty: FieldType::Option(Box::new(element)),
+                number,
             }
         } else {
             Field {
                 name: field_name,
-                ty: ty_without_super.into(),
+                ty: element,
+                number,
             }
         }
     }
@@ -544,11 +572,20 @@ impl<'a> CodeGenerator<'a> {
             self.code_buf.push_str("    }\n");
             let input_without_super = self.remove_super(&method.input_type);
             let output_without_super = self.remove_super(&method.output_type);
+            let grpc_path = if service.package.is_empty() {
+                format!("/{}/{}", service.proto_name, method.proto_name)
+            } else {
+                format!(
+                    "/{}.{}/{}",
+                    service.package, service.proto_name, method.proto_name
+                )
+            };
             methods.insert(
                 method.name,
                 MethodDeclaration {
                     input: FieldType::Message(input_without_super),
                     output: FieldType::Message(output_without_super),
+                    grpc_path: Some(grpc_path),
                 },
             );
         }
@@ -849,6 +886,9 @@ fn build_enum_value_mappings<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cairo_proto_serde::{deserialize_cairo_serde, serialize_cairo_serde};
+    use cairo_vm::Felt252;
+    use serde_json::json;
 
     #[test]
     fn test_unescape_c_escape_string() {
@@ -877,6 +917,93 @@ mod tests {
         unescape_c_escape_string(r#"\x1"#);
     }
 
+    fn field(
+        name: &str,
+        number: i32,
+        label: Label,
+        ty: Type,
+        type_name: &str,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(ty as i32),
+            type_name: (!type_name.is_empty()).then(|| type_name.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_repeated_fields() {
+        let inner = DescriptorProto {
+            name: Some("Inner".into()),
+            field: vec![field("v", 1, Label::Optional, Type::Uint64, "")],
+            ..Default::default()
+        };
+        let response = DescriptorProto {
+            name: Some("Response".into()),
+            field: vec![
+                field("inners", 1, Label::Repeated, Type::Message, ".oracle.Inner"),
+                field("sizes", 2, Label::Repeated, Type::Enum, ".oracle.Size"),
+            ],
+            ..Default::default()
+        };
+        let size = EnumDescriptorProto {
+            name: Some("Size".into()),
+            value: vec![EnumValueDescriptorProto {
+                name: Some("SMALL".into()),
+                number: Some(0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("oracle.proto".into()),
+            package: Some("oracle".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![inner, response],
+            enum_type: vec![size],
+            ..Default::default()
+        };
+
+        let message_graph = MessageGraph::new(iter::once(&file)).unwrap();
+        let extern_paths = ExternPaths::new(&[], true).unwrap();
+        let (mut code, mut configuration) = (String::new(), Configuration::default());
+        CodeGenerator::generate(
+            &mut Config::new(),
+            &message_graph,
+            &extern_paths,
+            file,
+            &mut code,
+            &mut configuration,
+        );
+        assert!(code.contains("    inners: Array<super::oracle::Inner>,\n"));
+        assert!(code.contains("    sizes: Array<super::oracle::Size>,\n"));
+
+        let inners = FieldType::Array(Box::new(FieldType::Message("oracle::Inner".into())));
+        let sizes = FieldType::Array(Box::new(FieldType::Enum("oracle::Size".into())));
+        assert_eq!(configuration.messages["oracle::Response"][0].ty, inners);
+        assert_eq!(configuration.messages["oracle::Response"][1].ty, sizes);
+
+        // the Cairo serde of an array is its length followed by its elements
+        let ty = FieldType::Message("oracle::Response".into());
+        let many = json!([{ "v": 1 }, { "v": 2 }, { "v": 3 }]);
+        for (value, felts) in [
+            (json!({ "inners": [], "sizes": [] }), vec![0, 0]),
+            (
+                json!({ "inners": [{ "v": 7 }], "sizes": [0] }),
+                vec![1, 7, 1, 0],
+            ),
+            (json!({ "inners": many, "sizes": [] }), vec![3, 1, 2, 3, 0]),
+        ] {
+            let felts = felts.into_iter().map(Felt252::from).collect::<Vec<_>>();
+            assert_eq!(serialize_cairo_serde(&configuration, &ty, &value), felts);
+            let deserialized = deserialize_cairo_serde(&configuration, &ty, &mut felts.as_ref());
+            assert_eq!(deserialized, value);
+        }
+    }
+
     #[test]
     fn test_strip_enum_prefix() {
         assert_eq!(strip_enum_prefix("Foo", "FooBar"), "Bar");
@@ -13,7 +13,8 @@ use path::PathMap;
 use prost::Message;
 use prost_types::FileDescriptorProto;
 use prost_types::FileDescriptorSet;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::default;
 use std::env;
 use std::fmt;
@@ -28,6 +29,8 @@ mod extern_paths;
 mod ident;
 mod message_graph;
 mod path;
+pub mod python_stub;
+pub mod server_stub;
 
 /// The map collection type to output for Protobuf `map` fields.
 #[non_exhaustive]
@@ -130,6 +133,27 @@ impl fmt::Display for Module {
     }
 }
 
+/// Everything generated from a proto, as returned by [`generate_cairo_bindings`] and
+/// [`Config::generate_bindings`] rather than written to disk.
+#[derive(Clone, Debug, Default)]
+pub struct GeneratedBindings {
+    /// Cairo module of the proto, with its messages, enums and oracle calls.
+    pub cairo: String,
+    /// Oracle.lock describing the services and the messages to the hint processor.
+    pub configuration: Configuration,
+    /// Axum server skeleton with one handler per oracle method, see [`server_stub`].
+    pub rust: String,
+    /// Cairo modules of the protos it imports, by file name, to put next to `cairo`.
+    pub imports: HashMap<String, String>,
+}
+
+impl GeneratedBindings {
+    /// The Oracle.lock as written by `scarb hints-generate`.
+    pub fn oracle_lock(&self) -> String {
+        serde_json::to_string(&self.configuration).unwrap()
+    }
+}
+
 pub struct Config {
     boxed: PathMap<()>,
     out_dir: Option<PathBuf>,
@@ -187,6 +211,18 @@ impl Config {
         protos: &[impl AsRef<Path>],
         includes: &[impl AsRef<Path>],
     ) -> std::io::Result<()> {
+        let file_descriptor_set = self.file_descriptor_set(protos, includes)?;
+        let marker = proto_hash_marker(&file_descriptor_set, includes);
+        self.write_fds(protos, file_descriptor_set, &marker)
+    }
+
+    /// Runs `protoc` on `protos`, searching the imported files in `includes`, and returns the
+    /// descriptors of the protos and of everything they import.
+    fn file_descriptor_set(
+        &self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+    ) -> std::io::Result<FileDescriptorSet> {
         // TODO: This should probably emit 'rerun-if-changed=PATH' directives for cargo, however
         // according to [1] if any are output then those paths replace the default crate root,
         // which is undesirable. Figure out how to do it in an additive way; perhaps gcc-rs has
@@ -251,14 +287,51 @@ impl Config {
                 ),
             )
         })?;
-        let file_descriptor_set = FileDescriptorSet::decode(&*buf).map_err(|error| {
+        FileDescriptorSet::decode(&*buf).map_err(|error| {
             Error::new(
                 ErrorKind::InvalidInput,
                 format!("invalid FileDescriptorSet: {}", error),
             )
-        })?;
+        })
+    }
 
-        self.compile_fds(protos, file_descriptor_set)
+    /// Compiles `proto` into its Cairo module, its Oracle.lock and a server skeleton, returned
+    /// instead of being written to the `out_dir` and the `oracle_lock`.
+    ///
+    /// The protos it imports are searched in `includes`, their Cairo modules are returned in
+    /// [`GeneratedBindings::imports`].
+    pub fn generate_bindings(
+        &mut self,
+        proto: impl AsRef<Path>,
+        includes: &[impl AsRef<Path>],
+    ) -> std::io::Result<GeneratedBindings> {
+        let protos = [proto.as_ref()];
+        let fds = self.file_descriptor_set(&protos, includes)?;
+        let marker = proto_hash_marker(&fds, includes);
+        let requests = fds
+            .file
+            .into_iter()
+            .map(|descriptor| {
+                (
+                    Module::from_protobuf_package_name(descriptor.package()),
+                    descriptor,
+                )
+            })
+            .collect::<Vec<_>>();
+        let requested = requested_modules(&protos, &requests);
+
+        let mut bindings = GeneratedBindings::default();
+        for (module, (code, configuration)) in self.generate(&protos, requests)? {
+            if requested.contains(&module) {
+                bindings.cairo = marker.clone() + &code;
+                bindings.configuration = configuration;
+            } else {
+                let file_name = module.to_file_name_or(&self.default_package_filename);
+                bindings.imports.insert(file_name, code);
+            }
+        }
+        bindings.rust = server_stub::generate_server_stub(&bindings.configuration);
+        Ok(bindings)
     }
 
     /// Compile a [`FileDescriptorSet`] into Rust files during a Cargo build with
@@ -270,6 +343,16 @@ impl Config {
         &mut self,
         protos: &[impl AsRef<Path>],
         fds: FileDescriptorSet,
+    ) -> std::io::Result<()> {
+        self.write_fds(protos, fds, "")
+    }
+
+    /// Same as [`Config::compile_fds`], starting the module of `protos` with `marker`.
+    fn write_fds(
+        &mut self,
+        protos: &[impl AsRef<Path>],
+        fds: FileDescriptorSet,
+        marker: &str,
     ) -> std::io::Result<()> {
         let target: PathBuf = self.out_dir.clone().ok_or_else(|| {
             Error::new(ErrorKind::Other, "out_dir configuration option is not set")
@@ -286,6 +369,8 @@ impl Config {
             })
             .collect::<Vec<_>>();
 
+        let requested = requested_modules(protos, &requests);
+
         let file_names = requests
             .iter()
             .map(|req| {
@@ -302,37 +387,46 @@ impl Config {
             let file_name = file_names
                 .get(module)
                 .expect("every module should have a filename");
-            // assuming one component == one package per module
-            let component = &module.components[0];
-            let list_paths: Vec<String> = protos
-                .iter()
-                .map(|p| p.as_ref().to_str().unwrap().to_string())
-                .collect();
-
             // Extract only the json matching the protos
             fs::create_dir_all(&target)?;
             let code_output_path = target.join(file_name);
+            let code = if requested.contains(module) {
+                marker.to_string() + &content.0
+            } else {
+                content.0.clone()
+            };
 
             let unchanged_code = fs::read(&code_output_path)
-                .map(|previous_content| previous_content == content.0.as_bytes())
+                .map(|previous_content| previous_content == code.as_bytes())
                 .unwrap_or(false);
 
             if unchanged_code {
                 trace!("unchanged code: {:?}", file_name);
             } else {
                 trace!("writing code: {:?}", file_name);
-                fs::write(code_output_path, &content.0)?;
+                fs::write(code_output_path, &code)?;
             }
 
             // Writing the JSON only for files belonging to `protos`
-            if list_paths.iter().any(|p| p.contains(component)) {
+            if requested.contains(module) {
                 let config_output_path = self.oracle_lock.as_ref().ok_or_else(|| {
                     Error::new(
                         ErrorKind::Other,
                         "oracle_lock configuration option is not set",
                     )
                 })?;
-                let config_json = serde_json::to_string(&content.1).unwrap();
+                // Keep the hand written settings of the services, their profiles and the
+                // prefetched requests across regenerations
+                let mut config = content.1.clone();
+                if let Some(previous) = fs::read(config_output_path)
+                    .ok()
+                    .and_then(|previous| serde_json::from_slice::<Configuration>(&previous).ok())
+                {
+                    config.service_options = previous.service_options;
+                    config.prefetch = previous.prefetch;
+                    config.profiles = previous.profiles;
+                }
+                let config_json = serde_json::to_string(&config).unwrap();
                 let unchanged_config = fs::read(config_output_path)
                     .map(|previous_content| previous_content == config_json.as_bytes())
                     .unwrap_or(false);
@@ -362,6 +456,7 @@ impl Config {
     ) -> std::io::Result<HashMap<Module, (String, Configuration)>> {
         let mut modules = HashMap::new();
         let mut packages = HashMap::new();
+        let requested = requested_modules(protos, &requests);
 
         let message_graph = MessageGraph::new(requests.iter().map(|x| &x.1))
             .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
@@ -393,49 +488,40 @@ impl Config {
             }
         }
 
-        for p in protos {
-            let path = p.as_ref().to_str().unwrap().to_string();
-            let mut super_enums: HashMap<String, Vec<Mapping>> = HashMap::new();
-            let mut super_messages: HashMap<String, Vec<Field>> = HashMap::new();
-            let mut super_services: HashMap<String, Service> = HashMap::new();
+        // The lock of a requested file also declares everything of the files it imports
+        let mut super_enums: HashMap<String, Vec<Mapping>> = HashMap::new();
+        let mut super_messages: HashMap<String, Vec<Field>> = HashMap::new();
+        let mut super_services: HashMap<String, Service> = HashMap::new();
 
-            // assuming one component == one package per module
-            for (module, content) in &modules {
-                if path.contains(&module.components[0]) {
-                    continue;
-                }
-
-                for (name, v) in &content.1.enums {
-                    let k = format!("{}::{}", module.components[0], name);
-                    super_enums.insert(k, v.to_owned());
-                    // super_enums.insert(name.clone(), v.to_owned());
-                }
+        for content in modules.values() {
+            for (name, v) in &content.1.enums {
+                super_enums.insert(name.clone(), v.to_owned());
+            }
 
-                for (name, v) in &content.1.messages {
-                    super_messages.insert(name.clone(), v.to_owned());
-                }
+            for (name, v) in &content.1.messages {
+                super_messages.insert(name.clone(), v.to_owned());
+            }
 
-                for (name, v) in &content.1.services {
-                    super_services.insert(name.clone(), v.to_owned());
-                }
+            for (name, v) in &content.1.services {
+                super_services.insert(name.clone(), v.to_owned());
             }
+        }
 
-            for (module, content) in &mut modules {
-                if !path.contains(&module.components[0]) {
-                    continue;
-                }
+        for (module, content) in &mut modules {
+            if !requested.contains(module) {
+                continue;
+            }
 
-                for (k, v) in &super_enums {
-                    content.1.enums.insert(k.to_owned(), v.to_owned());
-                }
+            for (k, v) in &super_enums {
+                content.1.enums.insert(k.to_owned(), v.to_owned());
+            }
 
-                for (k, v) in &super_messages {
-                    content.1.messages.insert(k.to_owned(), v.to_owned());
-                }
+            for (k, v) in &super_messages {
+                content.1.messages.insert(k.to_owned(), v.to_owned());
+            }
 
-                for (k, v) in &super_services {
-                    content.1.services.insert(k.to_owned(), v.to_owned());
-                }
+            for (k, v) in &super_services {
+                content.1.services.insert(k.to_owned(), v.to_owned());
             }
         }
 
@@ -472,6 +558,113 @@ impl default::Default for Config {
     }
 }
 
+/// Compiles the proto source `proto_src` into its Cairo bindings, Oracle.lock and server skeleton,
+/// without writing them, for build scripts and tools embedding the code generation.
+///
+/// `protoc` still reads the proto from a temporary directory, so it can only import the
+/// well-known protos. Use [`Config::generate_bindings`] for protos importing other files.
+pub fn generate_cairo_bindings(proto_src: &str) -> std::io::Result<GeneratedBindings> {
+    let tmp = tempfile::Builder::new()
+        .prefix("cairo-proto-build")
+        .tempdir()?;
+    let proto = tmp.path().join("oracle.proto");
+    fs::write(&proto, proto_src)?;
+    Config::new().generate_bindings(&proto, &[tmp.path()])
+}
+
+/// A server skeleton generated from an `Oracle.lock`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerStub {
+    /// An axum server, see [`server_stub::generate_server_stub`].
+    Rust,
+    /// A FastAPI server, see [`python_stub::generate_python_stub`].
+    Python,
+}
+
+impl ServerStub {
+    /// Writes the skeleton of the lock at `lock` to `output`, unless it exists and `force` is
+    /// false.
+    pub fn write(self, output: &Path, force: bool, lock: &Path) -> std::io::Result<()> {
+        if output.exists() && !force {
+            println!(
+                "{} already exists, pass --force to overwrite it",
+                output.display()
+            );
+            return Ok(());
+        }
+        let configuration: Configuration = serde_json::from_slice(&fs::read(lock)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let (stub, name) = match self {
+            ServerStub::Rust => (server_stub::generate_server_stub(&configuration), "Rust"),
+            ServerStub::Python => (python_stub::generate_python_stub(&configuration), "Python"),
+        };
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(output, stub)?;
+        println!("{name} server stub written to {}", output.display());
+        Ok(())
+    }
+}
+
+/// Start of the comment lines of a generated module recording the SHA-256 of each proto it was
+/// generated from, followed by the hash and the name of the proto relative to its include path.
+pub const PROTO_HASH_MARKER: &str = "// proto-sha256 ";
+
+/// Returns the protos recorded at the top of the `generated` Cairo module that changed since it
+/// was generated, or that are no longer found in `includes`.
+///
+/// A module generated before the hashes were recorded has none, and is never reported.
+pub fn stale_protos(generated: &str, includes: &[impl AsRef<Path>]) -> Vec<String> {
+    generated
+        .lines()
+        .take_while(|line| line.starts_with("//"))
+        .filter_map(|line| line.strip_prefix(PROTO_HASH_MARKER)?.split_once(' '))
+        .filter(|(hash, name)| proto_hash(name, includes).as_deref() != Some(*hash))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// The comment lines recording the hashes of the protos of `fds`. The protos found in none of
+/// `includes`, such as the well-known ones shipped with protoc, are left out.
+fn proto_hash_marker(fds: &FileDescriptorSet, includes: &[impl AsRef<Path>]) -> String {
+    fds.file
+        .iter()
+        .filter_map(|descriptor| {
+            let hash = proto_hash(descriptor.name(), includes)?;
+            Some(format!("{PROTO_HASH_MARKER}{hash} {}\n", descriptor.name()))
+        })
+        .collect()
+}
+
+/// The SHA-256 of the proto `name`, read from the first of `includes` containing it.
+fn proto_hash(name: &str, includes: &[impl AsRef<Path>]) -> Option<String> {
+    let content = includes
+        .iter()
+        .find_map(|include| fs::read(include.as_ref().join(name)).ok())?;
+    let digest = Sha256::digest(content);
+    Some(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Returns the modules of the files of `protos`, as opposed to the files they import.
+///
+/// protoc names the files relative to the include path they are found in, which is a suffix of
+/// the path of the proto.
+fn requested_modules(
+    protos: &[impl AsRef<Path>],
+    requests: &[(Module, FileDescriptorProto)],
+) -> HashSet<Module> {
+    requests
+        .iter()
+        .filter(|(_, descriptor)| {
+            protos
+                .iter()
+                .any(|proto| proto.as_ref().ends_with(descriptor.name()))
+        })
+        .map(|(module, _)| module.clone())
+        .collect()
+}
+
 /// Returns the path to the `protoc` binary.
 pub fn protoc_from_env() -> PathBuf {
     let os_specific_hint = if cfg!(target_os = "macos") {
@@ -519,3 +712,112 @@ pub fn protoc_include_from_env() -> Option<PathBuf> {
 
     Some(protoc_include)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_proto_serde::configuration::{FieldType, PrimitiveType};
+    use prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FieldDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    fn message(name: &str, field: &str, ty: Type, type_name: Option<&str>) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.into()),
+            field: vec![FieldDescriptorProto {
+                name: Some(field.into()),
+                number: Some(1),
+                label: Some(Label::Optional.into()),
+                r#type: Some(ty.into()),
+                type_name: type_name.map(String::from),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_imported_proto() {
+        let types = FileDescriptorProto {
+            name: Some("types.proto".into()),
+            package: Some("types".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![message("Point", "x", Type::Uint64, None)],
+            ..Default::default()
+        };
+        let service = FileDescriptorProto {
+            name: Some("service.proto".into()),
+            package: Some("oracle".into()),
+            syntax: Some("proto3".into()),
+            dependency: vec!["types.proto".into()],
+            message_type: vec![message("Request", "p", Type::Message, Some(".types.Point"))],
+            service: vec![ServiceDescriptorProto {
+                name: Some("PointOracle".into()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("Norm".into()),
+                    input_type: Some(".oracle.Request".into()),
+                    output_type: Some(".types.Point".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let oracle = Module::from_parts(["oracle"]);
+        let types_module = Module::from_parts(["types"]);
+        let requests = vec![(types_module.clone(), types), (oracle.clone(), service)];
+
+        // the proto is given with its directory, protoc names it relative to the include path
+        let protos = [Path::new("proto/service.proto")];
+        assert_eq!(
+            requested_modules(&protos, &requests),
+            HashSet::from([oracle.clone()])
+        );
+
+        let modules = Config::new().generate(&protos, requests).unwrap();
+        let (code, configuration) = &modules[&oracle];
+        assert!(code.contains("p: Option<super::types::Point>"));
+        assert!(modules[&types_module].0.contains("struct Point"));
+
+        // the lock of service.proto declares the messages of types.proto
+        let point = &configuration.messages["types::Point"];
+        assert_eq!(point[0].ty, FieldType::Primitive(PrimitiveType::U64));
+        let norm = &configuration.services["PointOracle"].methods["norm"];
+        assert_eq!(norm.output, FieldType::Message("types::Point".into()));
+    }
+
+    #[test]
+    fn test_stale_protos() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("oracle.proto"), "syntax = \"proto3\";").unwrap();
+        let fds = FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    name: Some("oracle.proto".into()),
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    name: Some("google/protobuf/empty.proto".into()),
+                    ..Default::default()
+                },
+            ],
+        };
+        let generated =
+            proto_hash_marker(&fds, &[dir.path()]) + "use starknet::testing::cheatcode;\n";
+        assert_eq!(generated.lines().count(), 2);
+        assert!(stale_protos(&generated, &[dir.path()]).is_empty());
+
+        fs::write(dir.path().join("oracle.proto"), "syntax = \"proto2\";").unwrap();
+        assert_eq!(
+            stale_protos(&generated, &[dir.path()]),
+            vec!["oracle.proto"]
+        );
+        fs::remove_file(dir.path().join("oracle.proto")).unwrap();
+        assert_eq!(
+            stale_protos(&generated, &[dir.path()]),
+            vec!["oracle.proto"]
+        );
+        assert!(stale_protos("use starknet::testing::cheatcode;\n", &[dir.path()]).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use cairo_proto_serde::configuration::{Configuration, Field, FieldType, Mapping, PrimitiveType};
+use heck::{ToShoutySnakeCase, ToUpperCamelCase};
+use indoc::formatdoc;
+
+const HEADER: &str = "\
+# Oracle server stub generated by `scarb hints-generate --python-stub`.
+# This file is meant to be edited: it is not overwritten by `scarb hints-generate` unless
+# `--force` is passed. Run it with `uvicorn server:app --port 3000`.
+";
+
+/// Keywords that can't name a Python attribute, such fields get a trailing `_` and an alias.
+const PYTHON_KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Generates a FastAPI server skeleton with one route per oracle method of `configuration`, and a
+/// Pydantic model for each message and an `IntEnum` for each enum of the lock.
+///
+/// The models follow the JSON the hint processor sends and expects: felts are decimal strings,
+/// enums their proto number, and absent options `null`.
+pub fn generate_python_stub(configuration: &Configuration) -> String {
+    let mut methods = configuration
+        .services
+        .iter()
+        .flat_map(|(service, methods)| {
+            methods
+                .methods
+                .iter()
+                .map(move |(selector, method)| (service, selector, method))
+        })
+        .collect::<Vec<_>>();
+    methods.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let mut buf = String::from(HEADER);
+    buf.push_str(&formatdoc! {r#"
+
+        from __future__ import annotations
+
+        from enum import IntEnum
+        from typing import Generic, List, Optional, TypeVar
+
+        from fastapi import Body, FastAPI
+        from fastapi import Request as HttpRequest
+        from fastapi.responses import JSONResponse
+        from pydantic import BaseModel, Field
+
+        T = TypeVar("T")
+
+
+        class JsonResult(BaseModel, Generic[T]):
+            """Body of the responses expected by the hint processor."""
+
+            result: T
+
+
+        class OracleRejected(Exception):
+            """Raised by a method to reject the request, the run stops with this message."""
+    "#});
+
+    for (path, mappings) in &configuration.enums {
+        buf.push_str(&python_enum(path, mappings));
+    }
+    for (path, fields) in &configuration.messages {
+        buf.push_str(&python_model(path, fields));
+    }
+
+    buf.push_str(&formatdoc! {r#"
+
+
+        app = FastAPI()
+
+
+        @app.exception_handler(OracleRejected)
+        async def reject(_request: HttpRequest, error: OracleRejected) -> JSONResponse:
+            # an `error` in place of the `result` stops the run with `OracleRejected`
+            return JSONResponse(status_code=400, content={{"error": str(error)}})
+    "#});
+
+    for (service, selector, method) in &methods {
+        let input = python_type(&method.input);
+        // a body that isn't a model would be read from the query string
+        let body = match method.input {
+            FieldType::Message(_) => "",
+            _ => " = Body()",
+        };
+        let output = python_type(&method.output);
+        buf.push_str(&formatdoc! {r#"
+
+
+            @app.post("/{selector}")
+            def {selector}(request: {input}{body}) -> JsonResult[{output}]:
+                """Answers the `{selector}` method of `{service}`."""
+                raise NotImplementedError("implement {service}.{selector}")
+        "#});
+    }
+
+    if !configuration.messages.is_empty() {
+        buf.push_str("\n\n");
+        for path in configuration.messages.keys() {
+            buf.push_str(&format!("{}.model_rebuild()\n", class_name(path)));
+        }
+    }
+    buf
+}
+
+/// Generates an `IntEnum` for the proto enum at `path`, sent as its proto number.
+fn python_enum(path: &str, mappings: &[Mapping]) -> String {
+    let name = class_name(path);
+    let variants = mappings
+        .iter()
+        .map(|m| format!("    {} = {}\n", m.name.to_shouty_snake_case(), m.nb))
+        .collect::<String>();
+    formatdoc! {r#"
+
+
+        class {name}(IntEnum):
+            """The `{path}` enum, sent as its proto number."""
+
+        {variants}"#}
+}
+
+/// Generates a Pydantic model for the message at `path`.
+fn python_model(path: &str, fields: &[Field]) -> String {
+    let name = class_name(path);
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let ty = python_type(&field.ty);
+            let (attribute, alias) = if PYTHON_KEYWORDS.contains(&field.name.as_str()) {
+                (format!("{}_", field.name), Some(&field.name))
+            } else {
+                (field.name.clone(), None)
+            };
+            let default = match (&field.ty, alias) {
+                (FieldType::Option(_), Some(alias)) => {
+                    format!(" = Field(None, alias=\"{alias}\")")
+                }
+                (FieldType::Option(_), None) => " = None".to_string(),
+                (_, Some(alias)) => format!(" = Field(alias=\"{alias}\")"),
+                (_, None) => String::new(),
+            };
+            format!("    {attribute}: {ty}{default}\n")
+        })
+        .collect::<String>();
+    let fields = if fields.is_empty() {
+        "    pass\n".to_string()
+    } else {
+        fields
+    };
+    formatdoc! {r#"
+
+
+        class {name}(BaseModel):
+            """The `{path}` message."""
+
+        {fields}"#}
+}
+
+fn python_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Primitive(
+            PrimitiveType::U32 | PrimitiveType::U64 | PrimitiveType::I32 | PrimitiveType::I64,
+        ) => "int".to_string(),
+        FieldType::Primitive(PrimitiveType::BOOL) => "bool".to_string(),
+        // felts are decimal strings, a byte array its UTF-8 string
+        FieldType::Primitive(PrimitiveType::FELT252 | PrimitiveType::BYTEARRAY) => {
+            "str".to_string()
+        }
+        FieldType::Message(path) | FieldType::Enum(path) => class_name(path),
+        FieldType::Option(ty) => format!("Optional[{}]", python_type(ty)),
+        FieldType::Array(ty) => format!("List[{}]", python_type(ty)),
+    }
+}
+
+/// Names a message or an enum after the last segment of its path, as the Rust server stub does.
+fn class_name(path: &str) -> String {
+    path.rsplit("::")
+        .next()
+        .unwrap_or(path)
+        .to_upper_camel_case()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_python_stub;
+    use cairo_proto_serde::configuration::{
+        Configuration, Field, FieldType, Mapping, MethodDeclaration, PrimitiveType, Service,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_generates_a_model_per_message_and_a_route_per_method() {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "shirts::Request".into(),
+            vec![
+                Field {
+                    name: "from".into(),
+                    ty: FieldType::Primitive(PrimitiveType::FELT252),
+                    number: Some(1),
+                },
+                Field {
+                    name: "sizes".into(),
+                    ty: FieldType::Array(Box::new(FieldType::Enum("shirts::Size".into()))),
+                    number: Some(2),
+                },
+                Field {
+                    name: "limit".into(),
+                    ty: FieldType::Option(Box::new(FieldType::Primitive(PrimitiveType::U64))),
+                    number: Some(3),
+                },
+            ],
+        );
+        configuration.services.insert(
+            "ShirtsOracle".into(),
+            Service {
+                methods: HashMap::from([(
+                    "shirt".into(),
+                    MethodDeclaration {
+                        input: FieldType::Message("shirts::Request".into()),
+                        output: FieldType::Message("shirts::Request".into()),
+                        grpc_path: None,
+                    },
+                )]),
+            },
+        );
+        configuration.enums.insert(
+            "shirts::Size".into(),
+            vec![
+                Mapping {
+                    name: "Small".into(),
+                    nb: 0,
+                },
+                Mapping {
+                    name: "Large".into(),
+                    nb: 2,
+                },
+            ],
+        );
+
+        let stub = generate_python_stub(&configuration);
+        assert!(stub.contains("class Size(IntEnum):\n"));
+        assert!(stub.contains("    SMALL = 0\n    LARGE = 2\n"));
+        assert!(stub.contains(
+            "    from_: str = Field(alias=\"from\")\n    sizes: List[Size]\n    limit: Optional[int] = None\n"
+        ));
+        assert!(stub.contains(
+            "@app.post(\"/shirt\")\ndef shirt(request: Request) -> JsonResult[Request]:\n"
+        ));
+        assert!(stub.ends_with("\n\nRequest.model_rebuild()\n"));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeSet;
+
+use cairo_proto_serde::configuration::{Configuration, FieldType, Mapping};
+use heck::ToUpperCamelCase;
+use indoc::formatdoc;
+
+const HEADER: &str = "\
+// Oracle server stub generated by `scarb hints-generate --server-stub`.
+// This file is meant to be edited: it is not overwritten by `scarb hints-generate` unless
+// `--force` is passed.
+";
+
+/// Generates an axum server skeleton with one handler per oracle method of `configuration`.
+///
+/// The request and response types are the ones `prost-build` generates from the same proto, in
+/// the module named after the proto package.
+pub fn generate_server_stub(configuration: &Configuration) -> String {
+    let mut methods = configuration
+        .services
+        .iter()
+        .flat_map(|(service, methods)| {
+            methods
+                .methods
+                .iter()
+                .map(move |(selector, method)| (service, selector, method))
+        })
+        .collect::<Vec<_>>();
+    methods.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let modules = methods
+        .iter()
+        .flat_map(|(_, _, method)| [&method.input, &method.output])
+        .filter_map(|ty| rust_type(ty).split_once("::").map(|(m, _)| m.to_string()))
+        .collect::<BTreeSet<_>>();
+
+    let mut buf = String::from(HEADER);
+    buf.push_str(
+        "\nuse axum::{routing::post, Json, Router};\nuse serde::{Deserialize, Serialize};\n",
+    );
+    if !modules.is_empty() {
+        buf.push('\n');
+        for module in &modules {
+            buf.push_str(&format!("use crate::{module};\n"));
+        }
+    }
+
+    buf.push_str(&formatdoc! {r#"
+
+        /// Body of the responses expected by the hint processor.
+        #[derive(Debug, Serialize, Deserialize)]
+        pub struct JsonResult<T> {{
+            pub result: T,
+        }}
+    "#});
+
+    for (path, mappings) in &configuration.enums {
+        buf.push_str(&server_enum(path, mappings));
+    }
+
+    for (service, selector, method) in &methods {
+        let input = rust_type(&method.input);
+        let output = rust_type(&method.output);
+        buf.push_str(&formatdoc! {r#"
+
+            /// Answers the `{selector}` method of `{service}`.
+            pub async fn {selector}(Json(request): Json<{input}>) -> Json<JsonResult<{output}>> {{
+                let _ = request;
+                todo!("implement {service}.{selector}")
+            }}
+        "#});
+    }
+
+    buf.push_str("\n/// Routes every oracle method to its handler.\npub fn router() -> Router {\n    Router::new()");
+    for (_, selector, _) in &methods {
+        buf.push_str(&format!(
+            "\n        .route(\"/{selector}\", post({selector}))"
+        ));
+    }
+    buf.push_str("\n}\n");
+    buf
+}
+
+/// Generates a Rust enum for the proto enum at `path`, (de)serialized as its proto number like
+/// the `i32` fields of the `prost-build` messages, the zero value being the default.
+fn server_enum(path: &str, mappings: &[Mapping]) -> String {
+    let name = path
+        .rsplit("::")
+        .next()
+        .unwrap_or(path)
+        .to_upper_camel_case();
+    let variants = mappings
+        .iter()
+        .map(|m| {
+            let default = if m.nb == 0 { "    #[default]\n" } else { "" };
+            format!(
+                "{default}    {} = {},\n",
+                m.name.to_upper_camel_case(),
+                m.nb
+            )
+        })
+        .collect::<String>();
+    let arms = mappings
+        .iter()
+        .map(|m| {
+            let variant = m.name.to_upper_camel_case();
+            format!("            {} => Ok({name}::{variant}),\n", m.nb)
+        })
+        .collect::<String>();
+    let derive = if mappings.iter().any(|m| m.nb == 0) {
+        "Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize"
+    } else {
+        "Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize"
+    };
+    formatdoc! {r#"
+
+        /// The `{path}` enum, sent as its proto number.
+        #[derive({derive})]
+        #[serde(into = "i32", try_from = "i32")]
+        #[repr(i32)]
+        pub enum {name} {{
+        {variants}}}
+
+        impl From<{name}> for i32 {{
+            fn from(value: {name}) -> i32 {{
+                value as i32
+            }}
+        }}
+
+        impl TryFrom<i32> for {name} {{
+            type Error = String;
+
+            fn try_from(value: i32) -> Result<Self, Self::Error> {{
+                match value {{
+        {arms}            _ => Err(format!("{{value}} is not a value of {path}")),
+                }}
+            }}
+        }}
+    "#}
+}
+
+fn rust_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Message(path) => path.clone(),
+        _ => "serde_json::Value".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_server_stub;
+    use cairo_proto_serde::configuration::{
+        Configuration, FieldType, Mapping, MethodDeclaration, Service,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_generates_one_route_per_method() {
+        let method = MethodDeclaration {
+            input: FieldType::Message("shirts::Request".into()),
+            output: FieldType::Message("shirts::Response".into()),
+            grpc_path: None,
+        };
+        let mut configuration = Configuration::default();
+        configuration.services.insert(
+            "ShirtsOracle".into(),
+            Service {
+                methods: HashMap::from([
+                    ("shirt".into(), method.clone()),
+                    ("pants".into(), method),
+                ]),
+            },
+        );
+        configuration.enums.insert(
+            "shirts::Size".into(),
+            vec![
+                Mapping {
+                    name: "Small".into(),
+                    nb: 0,
+                },
+                Mapping {
+                    name: "Large".into(),
+                    nb: 2,
+                },
+            ],
+        );
+
+        let stub = generate_server_stub(&configuration);
+        assert!(stub.contains("use crate::shirts;\n"));
+        assert!(stub.contains(
+            "pub async fn shirt(Json(request): Json<shirts::Request>) -> Json<JsonResult<shirts::Response>> {"
+        ));
+        assert!(
+            stub.contains("pub enum Size {\n    #[default]\n    Small = 0,\n    Large = 2,\n}\n")
+        );
+        assert!(stub.contains("            2 => Ok(Size::Large),\n"));
+        assert!(stub.ends_with(
+            "    Router::new()\n        .route(\"/pants\", post(pants))\n        .route(\"/shirt\", post(shirt))\n}\n"
+        ));
+    }
+}
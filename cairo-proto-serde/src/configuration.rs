@@ -1,12 +1,181 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct Configuration {
     pub enums: BTreeMap<String, Vec<Mapping>>,
     pub messages: BTreeMap<String, Vec<Field>>,
     pub services: BTreeMap<String, Service>,
     pub servers_config: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub service_options: BTreeMap<String, ServiceOptions>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefetch: Vec<PrefetchRequest>,
+    // per environment overrides of the service_options, by profile then service
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, BTreeMap<String, ServiceOptions>>,
+}
+
+/// Profile whose overrides apply to every run, before those of the selected profile.
+pub const DEFAULT_PROFILE: &str = "default";
+
+impl Configuration {
+    /// Merges the `default` profile, then `profile` when given, into the `service_options`.
+    ///
+    /// Fails on a `profile` the lock doesn't define, other than `default` which may be omitted.
+    pub fn apply_profile(&mut self, profile: Option<&str>) -> Result<(), String> {
+        let profile = profile.filter(|profile| *profile != DEFAULT_PROFILE);
+        if let Some(profile) = profile.filter(|profile| !self.profiles.contains_key(*profile)) {
+            let defined = self.profiles.keys().map(String::as_str).collect::<Vec<_>>();
+            return Err(format!(
+                "unknown oracle profile {profile}, the Oracle.lock defines: {}",
+                if defined.is_empty() {
+                    "none".to_string()
+                } else {
+                    defined.join(", ")
+                }
+            ));
+        }
+        for profile in [Some(DEFAULT_PROFILE), profile].into_iter().flatten() {
+            let Some(overrides) = self.profiles.get(profile) else {
+                continue;
+            };
+            for (service, options) in overrides {
+                self.service_options
+                    .entry(service.clone())
+                    .or_default()
+                    .merge(options);
+            }
+        }
+        Ok(())
+    }
+}
+
+// oracle request known before the run, its response can be fetched while the program compiles
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PrefetchRequest {
+    pub method: String,
+    pub request: serde_json::Value,
+}
+
+// per service settings that are not derived from the proto definitions
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ServiceOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Protocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    // values may reference environment variables as `${NAME}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // probed with a GET before the run, relative to the url of the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+    // expected `schema::schema_hash` of the service, the run is refused on a mismatch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_hash: Option<String>,
+    // the calls of a method with the same request read the successive responses of one stream
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub streaming: bool,
+    // hex encoded Ed25519 key the responses of the service must be signed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    // replicas of `url`, tried in order when it fails to answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failover_urls: Vec<String>,
+    // path of the HTTP requests relative to `url`, `{service}` and `{method}` are replaced with
+    // the names of the call, `{method}` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_template: Option<String>,
+    // `path_template` of single methods, by selector
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub method_paths: BTreeMap<String, String>,
+    // the requests are sent as `{"request": ..., "context": ...}`, with the run id and the hash
+    // of the program input
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub run_context: bool,
+    // HTTP method of the requests, POST when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_method: Option<HttpMethod>,
+    // `Content-Type` of the requests, in place of the one of the `encoding`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+impl ServiceOptions {
+    /// Replaces the settings given in `overrides`. The headers and the method paths are merged,
+    /// and `streaming` and `run_context` can only be turned on.
+    pub fn merge(&mut self, overrides: &ServiceOptions) {
+        let ServiceOptions {
+            url,
+            protocol,
+            timeout_ms,
+            max_retries,
+            headers,
+            health_path,
+            encoding,
+            schema_hash,
+            streaming,
+            public_key,
+            failover_urls,
+            path_template,
+            method_paths,
+            run_context,
+            http_method,
+            content_type,
+        } = overrides.clone();
+        self.url = url.or(self.url.take());
+        self.protocol = protocol.or(self.protocol);
+        self.timeout_ms = timeout_ms.or(self.timeout_ms);
+        self.max_retries = max_retries.or(self.max_retries);
+        self.headers.extend(headers);
+        self.health_path = health_path.or(self.health_path.take());
+        self.encoding = encoding.or(self.encoding);
+        self.schema_hash = schema_hash.or(self.schema_hash.take());
+        self.streaming |= streaming;
+        self.public_key = public_key.or(self.public_key.take());
+        if !failover_urls.is_empty() {
+            self.failover_urls = failover_urls;
+        }
+        self.path_template = path_template.or(self.path_template.take());
+        self.method_paths.extend(method_paths);
+        self.run_context |= run_context;
+        self.http_method = http_method.or(self.http_method);
+        self.content_type = content_type.or(self.content_type.take());
+    }
+}
+
+// wire protocol spoken by an oracle server
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Http,
+    Grpc,
+}
+
+// HTTP method of the requests sent to an oracle server
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    Post,
+    Put,
+}
+
+// encoding of the bodies exchanged with an HTTP oracle server
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Msgpack,
 }
 
 // primitive types supported by both Protocol Buffers and Cairo
@@ -36,6 +205,8 @@ pub enum FieldType {
 pub struct Field {
     pub name: String,
     pub ty: FieldType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<i32>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -54,6 +225,8 @@ pub struct Service {
 pub struct MethodDeclaration {
     pub input: FieldType,
     pub output: FieldType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_path: Option<String>,
 }
 
 impl From<String> for FieldType {
@@ -70,3 +243,260 @@ impl From<String> for FieldType {
         }
     }
 }
+
+// keys of the objects of the lock, the other keys are ignored when it is deserialized
+const LOCK_KEYS: &[&str] = &[
+    "enums",
+    "messages",
+    "services",
+    "servers_config",
+    "service_options",
+    "prefetch",
+    "profiles",
+];
+const REQUIRED_LOCK_KEYS: &[&str] = &["enums", "messages", "services", "servers_config"];
+const SERVICE_OPTIONS_KEYS: &[&str] = &[
+    "url",
+    "protocol",
+    "timeout_ms",
+    "max_retries",
+    "headers",
+    "health_path",
+    "encoding",
+    "schema_hash",
+    "streaming",
+    "public_key",
+    "failover_urls",
+    "path_template",
+    "method_paths",
+    "run_context",
+    "http_method",
+    "content_type",
+];
+const FIELD_TYPE_KEYS: &[&str] = &["primitive", "message", "enum", "option", "array"];
+
+/// Checks the structure of an `Oracle.lock` parsed as JSON: the objects it must contain and the
+/// keys they have, so an error names the key at fault rather than failing deep in the run.
+///
+/// Returns the paths of the keys that are not part of the lock, such as
+/// `service_options.SqrtOracle.timeout`, which are ignored when it is deserialized. The values
+/// themselves are checked when the lock is deserialized.
+pub fn check_lock(lock: &Value) -> Result<Vec<String>, String> {
+    let mut unknown = Vec::new();
+    let root = keys(lock, "", LOCK_KEYS, REQUIRED_LOCK_KEYS, &mut unknown)?;
+    for (name, mappings) in entries(root.get("enums"), "enums")? {
+        let path = format!("enums.{name}");
+        for (i, mapping) in items(mappings, &path)?.iter().enumerate() {
+            let path = format!("{path}[{i}]");
+            keys(
+                mapping,
+                &path,
+                &["name", "nb"],
+                &["name", "nb"],
+                &mut unknown,
+            )?;
+        }
+    }
+    for (name, fields) in entries(root.get("messages"), "messages")? {
+        let path = format!("messages.{name}");
+        for (i, field) in items(fields, &path)?.iter().enumerate() {
+            let path = format!("{path}[{i}]");
+            let allowed = ["name", "ty", "number"];
+            let field = keys(field, &path, &allowed, &["name", "ty"], &mut unknown)?;
+            check_field_type(&field["ty"], &format!("{path}.ty"))?;
+        }
+    }
+    for (service, methods) in entries(root.get("services"), "services")? {
+        for (method, declaration) in entries(Some(methods), &format!("services.{service}"))? {
+            let path = format!("services.{service}.{method}");
+            let declaration = keys(
+                declaration,
+                &path,
+                &["input", "output", "grpc_path"],
+                &["input", "output"],
+                &mut unknown,
+            )?;
+            check_field_type(&declaration["input"], &format!("{path}.input"))?;
+            check_field_type(&declaration["output"], &format!("{path}.output"))?;
+        }
+    }
+    entries(root.get("servers_config"), "servers_config")?;
+    for (service, options) in entries(root.get("service_options"), "service_options")? {
+        let path = format!("service_options.{service}");
+        keys(options, &path, SERVICE_OPTIONS_KEYS, &[], &mut unknown)?;
+    }
+    for (profile, services) in entries(root.get("profiles"), "profiles")? {
+        for (service, options) in entries(Some(services), &format!("profiles.{profile}"))? {
+            let path = format!("profiles.{profile}.{service}");
+            keys(options, &path, SERVICE_OPTIONS_KEYS, &[], &mut unknown)?;
+        }
+    }
+    if let Some(prefetch) = root.get("prefetch") {
+        for (i, entry) in items(prefetch, "prefetch")?.iter().enumerate() {
+            let path = format!("prefetch[{i}]");
+            keys(
+                entry,
+                &path,
+                &["method", "request"],
+                &["method", "request"],
+                &mut unknown,
+            )?;
+        }
+    }
+    Ok(unknown)
+}
+
+// the object at `path`, once its required keys are found; its unknown keys are added to `unknown`
+fn keys<'a>(
+    value: &'a Value,
+    path: &str,
+    allowed: &[&str],
+    required: &[&str],
+    unknown: &mut Vec<String>,
+) -> Result<&'a Map<String, Value>, String> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| format!("{}: expected an object", display_path(path)))?;
+    if let Some(missing) = required.iter().find(|key| !object.contains_key(**key)) {
+        return Err(format!("{}: missing key `{missing}`", display_path(path)));
+    }
+    unknown.extend(
+        object
+            .keys()
+            .filter(|key| !allowed.contains(&key.as_str()))
+            .map(|key| join_path(path, key)),
+    );
+    Ok(object)
+}
+
+// the entries of the optional object at `path`, whose keys are names
+fn entries<'a>(
+    value: Option<&'a Value>,
+    path: &str,
+) -> Result<impl Iterator<Item = (&'a String, &'a Value)>, String> {
+    let object = match value {
+        None => None,
+        Some(Value::Object(object)) => Some(object),
+        Some(_) => return Err(format!("{path}: expected an object")),
+    };
+    Ok(object.into_iter().flat_map(|object| object.iter()))
+}
+
+fn items<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, String> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("{path}: expected an array"))
+}
+
+fn check_field_type(value: &Value, path: &str) -> Result<(), String> {
+    let (kind, inner) = value
+        .as_object()
+        .and_then(|object| object.iter().next().filter(|_| object.len() == 1))
+        .ok_or_else(|| {
+            format!(
+                "{path}: expected an object with one of the keys {}",
+                FIELD_TYPE_KEYS.join(", ")
+            )
+        })?;
+    match kind.as_str() {
+        "option" | "array" => check_field_type(inner, &format!("{path}.{kind}")),
+        "primitive" | "message" | "enum" if inner.is_string() => Ok(()),
+        "primitive" | "message" | "enum" => Err(format!("{path}.{kind}: expected a string")),
+        _ => Err(format!(
+            "{path}: unknown type `{kind}`, expected one of {}",
+            FIELD_TYPE_KEYS.join(", ")
+        )),
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "the lock"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_lock, Configuration};
+    use serde_json::json;
+
+    #[test]
+    fn it_checks_the_keys_of_the_lock() {
+        let lock = |method: serde_json::Value| {
+            json!({
+                "enums": {},
+                "messages": { "Request": [{ "name": "n", "ty": { "primitive": "u64" } }] },
+                "services": { "SqrtOracle": { "sqrt": method } },
+                "servers_config": {},
+                "service_options": { "SqrtOracle": { "url": "http://localhost", "timeout": 5 } },
+            })
+        };
+        let request = json!({ "message": "Request" });
+        let method = json!({ "input": request, "output": request });
+        assert_eq!(
+            check_lock(&lock(method)).unwrap(),
+            ["service_options.SqrtOracle.timeout"]
+        );
+
+        let missing = json!({ "inptu": request, "output": request });
+        assert_eq!(
+            check_lock(&lock(missing)).unwrap_err(),
+            "services.SqrtOracle.sqrt: missing key `input`"
+        );
+        let unknown_type = json!({ "input": { "messag": "Request" }, "output": request });
+        assert!(check_lock(&lock(unknown_type))
+            .unwrap_err()
+            .starts_with("services.SqrtOracle.sqrt.input: unknown type `messag`"));
+        assert_eq!(
+            check_lock(&json!({ "enums": {} })).unwrap_err(),
+            "the lock: missing key `messages`"
+        );
+    }
+
+    #[test]
+    fn it_merges_the_default_profile_then_the_selected_one() {
+        let lock = json!({
+            "enums": {},
+            "messages": {},
+            "services": {},
+            "servers_config": {},
+            "service_options": { "SqrtOracle": { "url": "http://localhost", "max_retries": 1 } },
+            "profiles": {
+                "default": { "SqrtOracle": { "timeout_ms": 500 } },
+                "prod": { "SqrtOracle": { "url": "https://sqrt.example.com" } },
+            },
+        });
+        let configuration: Configuration = serde_json::from_value(lock).unwrap();
+
+        let mut prod = configuration.clone();
+        prod.apply_profile(Some("prod")).unwrap();
+        let options = &prod.service_options["SqrtOracle"];
+        assert_eq!(options.url.as_deref(), Some("https://sqrt.example.com"));
+        assert_eq!(
+            (options.timeout_ms, options.max_retries),
+            (Some(500), Some(1))
+        );
+
+        let mut dev = configuration.clone();
+        dev.apply_profile(None).unwrap();
+        let options = &dev.service_options["SqrtOracle"];
+        assert_eq!(options.url.as_deref(), Some("http://localhost"));
+        assert_eq!(options.timeout_ms, Some(500));
+
+        let mut staging = configuration;
+        assert_eq!(
+            staging.apply_profile(Some("staging")).unwrap_err(),
+            "unknown oracle profile staging, the Oracle.lock defines: default, prod"
+        );
+    }
+}
@@ -6,17 +6,54 @@ use num_traits::Zero;
 use serde_json::{json, Map, Value};
 
 pub mod configuration;
+pub mod msgpack;
+pub mod protobuf;
+pub mod schema;
+
+/// The field prime P, in decimal.
+const FELT252_PRIME_DEC: &str =
+    "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+/// The field prime P, in hexadecimal.
+const FELT252_PRIME_HEX: &str = "800000000000011000000000000000000000000000000000000000000000001";
+
+/// Parses the JSON representation of a felt252: an unsigned decimal string in `[0, P)`, which
+/// is what felts are written as, or a `0x` prefixed hexadecimal string in the same range. A sign
+/// or a value of P or more is rejected rather than reduced modulo P, as the oracle and the Cairo
+/// code would then disagree on the value.
+pub fn felt_from_str(s: &str) -> Result<Felt252, String> {
+    let (digits, prime, radix) = match s.strip_prefix("0x") {
+        Some(hex) => (hex, FELT252_PRIME_HEX, 16),
+        None => (s, FELT252_PRIME_DEC, 10),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(format!(
+            "felt252 {s:?} is not an unsigned decimal or 0x hexadecimal string"
+        ));
+    }
+    let digits = digits.trim_start_matches('0').to_ascii_lowercase();
+    if (digits.len(), digits.as_str()) >= (prime.len(), prime) {
+        let prime = FELT252_PRIME_DEC;
+        return Err(format!(
+            "felt252 {s} is out of range, it must be less than P = {prime}"
+        ));
+    }
+    let felt = match radix {
+        16 => Felt252::from_hex(s),
+        _ => Felt252::from_dec_str(s),
+    };
+    felt.map_err(|_| format!("felt252 {s} is not a valid felt252"))
+}
+
+/// Returns the canonical JSON representation of a felt252, its decimal string in `[0, P)`.
+/// It is read with `to_biguint`, as `to_bigint` is negative above half the prime.
+pub fn felt_to_json(felt: &Felt252) -> Value {
+    Value::String(felt.to_biguint().to_str_radix(10))
+}
 
 fn serialize_primitive(ty: &PrimitiveType, value: &Value) -> Vec<Felt252> {
     let element = match ty {
         PrimitiveType::FELT252 => match value {
-            Value::String(s) => {
-                if s.starts_with("0x") {
-                    Felt252::from_hex(s).expect("Invalid FELT252 hex string")
-                } else {
-                    Felt252::from_dec_str(s).expect("Invalid FELT252 decimal string")
-                }
-            }
+            Value::String(s) => felt_from_str(s).unwrap_or_else(|e| panic!("{e}")),
             _ => panic!("FELT252 must be a string"),
         },
         PrimitiveType::U64 => Felt252::from(
@@ -67,14 +104,12 @@ fn serialize_primitive(ty: &PrimitiveType, value: &Value) -> Vec<Felt252> {
 }
 
 fn deserialize_primitive(ty: &PrimitiveType, value: &mut &[Felt252]) -> Value {
-    let num = value[0].to_bigint();
+    let felt = value[0];
+    let num = felt.to_bigint();
     *value = &value[1..];
 
     match ty {
-        PrimitiveType::FELT252 => {
-            let hex_string = format!("0x{}", num.to_str_radix(16));
-            json!(hex_string)
-        }
+        PrimitiveType::FELT252 => felt_to_json(&felt),
         PrimitiveType::U64 => {
             json!(u64::try_from(num).expect(format!("Error converting {value:?} to u64").as_str()))
         }
@@ -110,6 +145,34 @@ fn deserialize_primitive(ty: &PrimitiveType, value: &mut &[Felt252]) -> Value {
     }
 }
 
+// Cairo serializes an enum as the index of its variant, while the JSON value is the proto
+// number, which are the same only for enums numbered from 0 without gaps.
+fn enum_index(config: &Configuration, enum_ty: &str, value: &Value) -> u64 {
+    let number = value
+        .as_i64()
+        .expect(format!("must be an integer to serialize as enum {enum_ty}").as_str());
+    match config.enums.get(enum_ty) {
+        Some(mappings) => mappings
+            .iter()
+            .position(|m| i64::from(m.nb) == number)
+            .expect(format!("{number} is not a value of enum {enum_ty}").as_str())
+            as u64,
+        None => number as u64,
+    }
+}
+
+fn enum_number(config: &Configuration, enum_ty: &str, index: u64) -> Value {
+    match config.enums.get(enum_ty) {
+        Some(mappings) => json!(
+            mappings
+                .get(index as usize)
+                .expect(format!("{index} is not a variant of enum {enum_ty}").as_str())
+                .nb
+        ),
+        None => json!(index),
+    }
+}
+
 pub fn serialize_cairo_serde(
     config: &Configuration,
     ty: &FieldType,
@@ -138,7 +201,10 @@ pub fn serialize_cairo_serde(
                 }
             }
         }
-        FieldType::Enum(_) => result.append(&mut serialize_primitive(&PrimitiveType::I32, value)),
+        FieldType::Enum(enum_ty) => {
+            let index = enum_index(config, enum_ty, value);
+            result.append(&mut serialize_primitive(&PrimitiveType::U32, &json!(index)));
+        }
         FieldType::Option(inner_ty) => {
             if value.is_null() {
                 result.append(&mut serialize_primitive(&PrimitiveType::U64, &json!(1)));
@@ -186,7 +252,10 @@ pub fn deserialize_cairo_serde(
             }
             Value::Object(result)
         }
-        FieldType::Enum(_) => deserialize_primitive(&PrimitiveType::I32, value),
+        FieldType::Enum(enum_ty) => {
+            let index = deserialize_primitive(&PrimitiveType::U32, value);
+            enum_number(config, enum_ty, index.as_u64().unwrap())
+        }
         FieldType::Option(inner_ty) => {
             let idx = deserialize_primitive(&PrimitiveType::U64, value);
             if idx == 0 {
@@ -215,9 +284,9 @@ pub fn deserialize_cairo_serde(
 #[cfg(test)]
 mod tests {
     use crate::configuration::{
-        Configuration, Field, FieldType, MethodDeclaration, PrimitiveType, Service,
+        Configuration, Field, FieldType, Mapping, MethodDeclaration, PrimitiveType, Service,
     };
-    use crate::{deserialize_cairo_serde, serialize_cairo_serde};
+    use crate::{deserialize_cairo_serde, felt_from_str, serialize_cairo_serde};
     use cairo_vm::Felt252;
     use serde_json::{json, Value};
     use std::collections::{BTreeMap, HashMap};
@@ -261,6 +330,57 @@ mod tests {
         assert_eq!(deserialized, expected_json);
     }
 
+    #[test]
+    fn it_maps_enum_variants_to_their_proto_number() {
+        let mut configuration = test_configuration();
+        configuration.enums.insert(
+            "Size".into(),
+            vec![
+                Mapping {
+                    name: "Small".into(),
+                    nb: 0,
+                },
+                Mapping {
+                    name: "Large".into(),
+                    nb: 5,
+                },
+            ],
+        );
+        let ty = FieldType::Array(Box::new(FieldType::Enum("Size".into())));
+
+        let felts = serialize_cairo_serde(&configuration, &ty, &json!([5, 0]));
+        assert_eq!(felts, [2, 1, 0].map(Felt252::from));
+        let value = deserialize_cairo_serde(&configuration, &ty, &mut felts.as_ref());
+        assert_eq!(value, json!([5, 0]));
+    }
+
+    #[test]
+    fn it_keeps_felts_in_the_range_of_the_field() {
+        let configuration = test_configuration();
+        let ty = FieldType::Primitive(PrimitiveType::FELT252);
+        let p_minus_one =
+            "3618502788666131213697322783095070105623107215331596699973092056135872020480";
+        let p = "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+        let p_minus_one_hex = "0x800000000000011000000000000000000000000000000000000000000000000";
+        let p_hex = "0x800000000000011000000000000000000000000000000000000000000000001";
+
+        for s in ["0", p_minus_one] {
+            let felts = serialize_cairo_serde(&configuration, &ty, &json!(s));
+            let value = deserialize_cairo_serde(&configuration, &ty, &mut felts.as_ref());
+            assert_eq!(value, json!(s));
+        }
+        let minus_one = Felt252::from(0) - Felt252::from(1);
+        assert_eq!(felt_from_str("0x0"), Ok(Felt252::from(0)));
+        assert_eq!(felt_from_str(p_minus_one), Ok(minus_one));
+        assert_eq!(felt_from_str(p_minus_one_hex), Ok(minus_one));
+
+        assert!(felt_from_str(p).unwrap_err().contains("out of range"));
+        assert!(felt_from_str(&format!("000{p}")).is_err());
+        assert!(felt_from_str(p_hex).is_err());
+        assert!(felt_from_str("-1").is_err());
+        assert!(felt_from_str("").is_err());
+    }
+
     #[test]
     fn it_saves_configuration() {
         let configuration = test_configuration();
@@ -285,6 +405,7 @@ mod tests {
             vec![Field {
                 name: "inner".into(),
                 ty: FieldType::Primitive(PrimitiveType::U32),
+                number: None,
             }],
         );
         messages.insert(
@@ -293,14 +414,17 @@ mod tests {
                 Field {
                     name: "n".into(),
                     ty: FieldType::Primitive(PrimitiveType::U64),
+                    number: None,
                 },
                 Field {
                     name: "x".into(),
                     ty: FieldType::Option(Box::new(FieldType::Message("Inner".into()))),
+                    number: None,
                 },
                 Field {
                     name: "y".into(),
                     ty: FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::I32))),
+                    number: None,
                 },
             ],
         );
@@ -309,6 +433,7 @@ mod tests {
             vec![Field {
                 name: "n".into(),
                 ty: FieldType::Primitive(PrimitiveType::U64),
+                number: None,
             }],
         );
 
@@ -318,6 +443,7 @@ mod tests {
             MethodDeclaration {
                 input: FieldType::Message("Request".into()),
                 output: FieldType::Message("Response".into()),
+                grpc_path: None,
             },
         );
 
@@ -334,6 +460,9 @@ mod tests {
             messages,
             services,
             servers_config,
+            service_options: BTreeMap::new(),
+            prefetch: Vec::new(),
+            profiles: BTreeMap::new(),
         }
     }
 
@@ -0,0 +1,401 @@
+use crate::configuration::{Configuration, Field, FieldType, PrimitiveType};
+use cairo_vm::Felt252;
+use serde_json::{json, Map, Value};
+
+/// Body of an oracle response, `{"result": <value>}` or `{"error": <message>}`.
+#[derive(Debug, PartialEq)]
+pub enum Response {
+    Result(Value),
+    Error(String),
+}
+
+/// Encodes a JSON value into the MessagePack representation of `ty`.
+///
+/// Messages are maps keyed by field name, and felts are 32 bytes big endian binaries rather than
+/// strings, so their full 252 bits go through.
+pub fn encode_value(
+    config: &Configuration,
+    ty: &FieldType,
+    value: &Value,
+) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    encode(config, ty, value, &mut buf)?;
+    Ok(buf)
+}
+
+/// Decodes the MessagePack representation of `ty` into its JSON value.
+pub fn decode_value(
+    config: &Configuration,
+    ty: &FieldType,
+    mut bytes: &[u8],
+) -> Result<Value, String> {
+    let value = decode(config, ty, &mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(format!("{} trailing bytes", bytes.len()));
+    }
+    Ok(value)
+}
+
+/// Decodes a MessagePack response body whose `result` has the representation of `ty`.
+pub fn decode_response(
+    config: &Configuration,
+    ty: &FieldType,
+    mut bytes: &[u8],
+) -> Result<Response, String> {
+    let len = read_map_len(&mut bytes)?;
+    let mut response = None;
+    for _ in 0..len {
+        match read_str(&mut bytes)?.as_str() {
+            "result" => response = Some(Response::Result(decode(config, ty, &mut bytes)?)),
+            "error" => response = Some(Response::Error(read_str(&mut bytes)?)),
+            _ => skip(&mut bytes)?,
+        }
+    }
+    response.ok_or_else(|| "expected a map with a `result` or an `error`".to_string())
+}
+
+fn message_fields<'a>(config: &'a Configuration, name: &str) -> Result<&'a Vec<Field>, String> {
+    config
+        .messages
+        .get(name)
+        .ok_or_else(|| format!("Key `{name}` not found in configuration JSON file"))
+}
+
+fn encode(
+    config: &Configuration,
+    ty: &FieldType,
+    value: &Value,
+    buf: &mut Vec<u8>,
+) -> Result<(), String> {
+    let mismatch = || format!("{value} can't be encoded as {ty:?}");
+    match ty {
+        FieldType::Primitive(PrimitiveType::U32 | PrimitiveType::U64) => {
+            write_uint(value.as_u64().ok_or_else(mismatch)?, buf)
+        }
+        FieldType::Primitive(PrimitiveType::I32 | PrimitiveType::I64) | FieldType::Enum(_) => {
+            write_int(value.as_i64().ok_or_else(mismatch)?, buf)
+        }
+        FieldType::Primitive(PrimitiveType::BOOL) => {
+            buf.push(if value.as_bool().ok_or_else(mismatch)? {
+                0xc3
+            } else {
+                0xc2
+            })
+        }
+        FieldType::Primitive(PrimitiveType::BYTEARRAY) => {
+            write_str(value.as_str().ok_or_else(mismatch)?, buf)
+        }
+        FieldType::Primitive(PrimitiveType::FELT252) => {
+            let s = value.as_str().ok_or_else(mismatch)?;
+            let felt = crate::felt_from_str(s)?;
+            buf.extend([0xc4, 32]);
+            buf.extend(felt.to_bytes_be());
+        }
+        FieldType::Message(name) => {
+            let fields = message_fields(config, name)?;
+            let object = value.as_object().ok_or_else(mismatch)?;
+            write_len(fields.len(), [0x80, 0xde, 0xdf], 15, buf);
+            for field in fields {
+                let field_value = object
+                    .get(&field.name)
+                    .or_else(|| object.get(field.name.trim_start_matches("felt252_")))
+                    .ok_or_else(|| format!("Field {} not found in value object", field.name))?;
+                write_str(&field.name, buf);
+                encode(config, &field.ty, field_value, buf)?;
+            }
+        }
+        FieldType::Option(_) if value.is_null() => buf.push(0xc0),
+        FieldType::Option(inner) => encode(config, inner, value, buf)?,
+        FieldType::Array(inner) => {
+            let elements = value.as_array().ok_or_else(mismatch)?;
+            write_len(elements.len(), [0x90, 0xdc, 0xdd], 15, buf);
+            for element in elements {
+                encode(config, inner, element, buf)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode(config: &Configuration, ty: &FieldType, bytes: &mut &[u8]) -> Result<Value, String> {
+    let value = match ty {
+        FieldType::Primitive(PrimitiveType::U32) => {
+            json!(u32::try_from(read_int(bytes)?).map_err(|_| "u32 out of range")?)
+        }
+        FieldType::Primitive(PrimitiveType::U64) => {
+            json!(u64::try_from(read_int(bytes)?).map_err(|_| "u64 out of range")?)
+        }
+        FieldType::Primitive(PrimitiveType::I32) | FieldType::Enum(_) => {
+            json!(i32::try_from(read_int(bytes)?).map_err(|_| "i32 out of range")?)
+        }
+        FieldType::Primitive(PrimitiveType::I64) => {
+            json!(i64::try_from(read_int(bytes)?).map_err(|_| "i64 out of range")?)
+        }
+        FieldType::Primitive(PrimitiveType::BOOL) => match take(bytes, 1)?[0] {
+            0xc2 => json!(false),
+            0xc3 => json!(true),
+            marker => return Err(format!("expected a boolean, got marker {marker:#04x}")),
+        },
+        FieldType::Primitive(PrimitiveType::BYTEARRAY) => json!(read_str(bytes)?),
+        FieldType::Primitive(PrimitiveType::FELT252) => {
+            let len = match take(bytes, 1)?[0] {
+                0xc4 => take(bytes, 1)?[0] as usize,
+                marker => {
+                    return Err(format!(
+                        "expected a felt252 binary, got marker {marker:#04x}"
+                    ))
+                }
+            };
+            let data: [u8; 32] = take(bytes, len)?
+                .try_into()
+                .map_err(|_| format!("a felt252 is 32 bytes, got {len}"))?;
+            let felt = Felt252::from_bytes_be(&data);
+            if felt.to_bytes_be() != data {
+                return Err("felt252 is larger than the field prime".to_string());
+            }
+            crate::felt_to_json(&felt)
+        }
+        FieldType::Message(name) => {
+            let fields = message_fields(config, name)?;
+            let mut result = Map::new();
+            for _ in 0..read_map_len(bytes)? {
+                let key = read_str(bytes)?;
+                match fields.iter().find(|field| field.name == key) {
+                    Some(field) => {
+                        result.insert(key, decode(config, &field.ty, bytes)?);
+                    }
+                    None => skip(bytes)?,
+                }
+            }
+            Value::Object(result)
+        }
+        FieldType::Option(_) if bytes.first() == Some(&0xc0) => {
+            *bytes = &bytes[1..];
+            Value::Null
+        }
+        FieldType::Option(inner) => decode(config, inner, bytes)?,
+        FieldType::Array(inner) => {
+            let len = match take(bytes, 1)?[0] {
+                marker @ 0x90..=0x9f => (marker & 0x0f) as usize,
+                0xdc => read_be(bytes, 2)? as usize,
+                0xdd => read_be(bytes, 4)? as usize,
+                marker => return Err(format!("expected an array, got marker {marker:#04x}")),
+            };
+            // every element takes at least a byte, this bounds the work on a corrupted length
+            if len > bytes.len() {
+                return Err(format!("array of {len} elements is longer than the input"));
+            }
+            let elements = (0..len)
+                .map(|_| decode(config, inner, bytes))
+                .collect::<Result<Vec<_>, _>>()?;
+            Value::Array(elements)
+        }
+    };
+    Ok(value)
+}
+
+fn write_uint(n: u64, buf: &mut Vec<u8>) {
+    match n {
+        0..=0x7f => buf.push(n as u8),
+        0x80..=0xff => buf.extend([0xcc, n as u8]),
+        0x100..=0xffff => {
+            buf.push(0xcd);
+            buf.extend((n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(0xce);
+            buf.extend((n as u32).to_be_bytes());
+        }
+        _ => {
+            buf.push(0xcf);
+            buf.extend(n.to_be_bytes());
+        }
+    }
+}
+
+fn write_int(n: i64, buf: &mut Vec<u8>) {
+    if n >= 0 {
+        return write_uint(n as u64, buf);
+    }
+    if n >= -32 {
+        buf.push(n as i8 as u8);
+    } else if n >= i64::from(i8::MIN) {
+        buf.extend([0xd0, n as i8 as u8]);
+    } else if n >= i64::from(i16::MIN) {
+        buf.push(0xd1);
+        buf.extend((n as i16).to_be_bytes());
+    } else if n >= i64::from(i32::MIN) {
+        buf.push(0xd2);
+        buf.extend((n as i32).to_be_bytes());
+    } else {
+        buf.push(0xd3);
+        buf.extend(n.to_be_bytes());
+    }
+}
+
+fn write_str(s: &str, buf: &mut Vec<u8>) {
+    match s.len() {
+        len @ 0..=31 => buf.push(0xa0 | len as u8),
+        len @ 32..=0xff => buf.extend([0xd9, len as u8]),
+        len @ 0x100..=0xffff => {
+            buf.push(0xda);
+            buf.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            buf.push(0xdb);
+            buf.extend((len as u32).to_be_bytes());
+        }
+    }
+    buf.extend(s.as_bytes());
+}
+
+/// Writes the header of an array or a map, given its fix, 16 and 32 bits markers.
+fn write_len(len: usize, [fix, marker16, marker32]: [u8; 3], fix_max: usize, buf: &mut Vec<u8>) {
+    if len <= fix_max {
+        buf.push(fix | len as u8);
+    } else if len <= 0xffff {
+        buf.push(marker16);
+        buf.extend((len as u16).to_be_bytes());
+    } else {
+        buf.push(marker32);
+        buf.extend((len as u32).to_be_bytes());
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if bytes.len() < len {
+        return Err("truncated MessagePack value".to_string());
+    }
+    let (data, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(data)
+}
+
+fn read_be(bytes: &mut &[u8], len: usize) -> Result<u64, String> {
+    Ok(take(bytes, len)?
+        .iter()
+        .fold(0, |n, &byte| (n << 8) | u64::from(byte)))
+}
+
+fn read_int(bytes: &mut &[u8]) -> Result<i128, String> {
+    let n = match take(bytes, 1)?[0] {
+        marker @ 0x00..=0x7f => i128::from(marker),
+        marker @ 0xe0..=0xff => i128::from(marker as i8),
+        0xcc => i128::from(read_be(bytes, 1)?),
+        0xcd => i128::from(read_be(bytes, 2)?),
+        0xce => i128::from(read_be(bytes, 4)?),
+        0xcf => i128::from(read_be(bytes, 8)?),
+        0xd0 => i128::from(read_be(bytes, 1)? as u8 as i8),
+        0xd1 => i128::from(read_be(bytes, 2)? as u16 as i16),
+        0xd2 => i128::from(read_be(bytes, 4)? as u32 as i32),
+        0xd3 => i128::from(read_be(bytes, 8)? as i64),
+        marker => return Err(format!("expected an integer, got marker {marker:#04x}")),
+    };
+    Ok(n)
+}
+
+fn read_str(bytes: &mut &[u8]) -> Result<String, String> {
+    let len = match take(bytes, 1)?[0] {
+        marker @ 0xa0..=0xbf => (marker & 0x1f) as usize,
+        0xd9 => read_be(bytes, 1)? as usize,
+        0xda => read_be(bytes, 2)? as usize,
+        0xdb => read_be(bytes, 4)? as usize,
+        marker => return Err(format!("expected a string, got marker {marker:#04x}")),
+    };
+    String::from_utf8(take(bytes, len)?.to_vec()).map_err(|e| e.to_string())
+}
+
+fn read_map_len(bytes: &mut &[u8]) -> Result<usize, String> {
+    let len = match take(bytes, 1)?[0] {
+        marker @ 0x80..=0x8f => (marker & 0x0f) as usize,
+        0xde => read_be(bytes, 2)? as usize,
+        0xdf => read_be(bytes, 4)? as usize,
+        marker => return Err(format!("expected a map, got marker {marker:#04x}")),
+    };
+    Ok(len)
+}
+
+/// Skips a value of any type, such as the unknown fields of a message.
+fn skip(bytes: &mut &[u8]) -> Result<(), String> {
+    let marker = take(bytes, 1)?[0];
+    let (len, values) = match marker {
+        0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => (0, 0),
+        0x80..=0x8f => (0, 2 * (marker & 0x0f) as u64),
+        0x90..=0x9f => (0, (marker & 0x0f) as u64),
+        0xa0..=0xbf => ((marker & 0x1f) as u64, 0),
+        0xcc | 0xd0 => (1, 0),
+        0xcd | 0xd1 => (2, 0),
+        0xca | 0xce | 0xd2 => (4, 0),
+        0xcb | 0xcf | 0xd3 => (8, 0),
+        0xc4 | 0xd9 => (read_be(bytes, 1)?, 0),
+        0xc5 | 0xda => (read_be(bytes, 2)?, 0),
+        0xc6 | 0xdb => (read_be(bytes, 4)?, 0),
+        0xdc => (0, read_be(bytes, 2)?),
+        0xdd => (0, read_be(bytes, 4)?),
+        0xde => (0, 2 * read_be(bytes, 2)?),
+        0xdf => (0, 2 * read_be(bytes, 4)?),
+        // fixext 1 to 16 and ext 8 to 32, the type byte follows the length
+        0xd4..=0xd8 => (1 + (1 << (marker - 0xd4)), 0),
+        0xc7 => (1 + read_be(bytes, 1)?, 0),
+        0xc8 => (1 + read_be(bytes, 2)?, 0),
+        0xc9 => (1 + read_be(bytes, 4)?, 0),
+        _ => return Err(format!("invalid MessagePack marker {marker:#04x}")),
+    };
+    take(bytes, len as usize)?;
+    if values > bytes.len() as u64 {
+        return Err("truncated MessagePack value".to_string());
+    }
+    (0..values).try_for_each(|_| skip(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_response, decode_value, encode_value, Response};
+    use crate::configuration::{Configuration, Field, FieldType, PrimitiveType};
+    use crate::{deserialize_cairo_serde, serialize_cairo_serde};
+    use serde_json::json;
+
+    #[test]
+    fn it_round_trips_a_252_bits_felt_as_json_and_msgpack() {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "Response".into(),
+            vec![
+                Field {
+                    name: "x".into(),
+                    ty: FieldType::Primitive(PrimitiveType::FELT252),
+                    number: Some(1),
+                },
+                Field {
+                    name: "n".into(),
+                    ty: FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::U64))),
+                    number: Some(2),
+                },
+            ],
+        );
+        let ty = FieldType::Message("Response".into());
+        // the field prime minus one
+        let felt = "3618502788666131213697322783095070105623107215331596699973092056135872020480";
+        let value = json!({ "x": felt, "n": [0, u64::MAX] });
+
+        let felts = serialize_cairo_serde(&configuration, &ty, &value);
+        let from_json = deserialize_cairo_serde(&configuration, &ty, &mut felts.as_ref());
+        assert_eq!(from_json, value);
+
+        let bytes = encode_value(&configuration, &ty, &value).unwrap();
+        assert_eq!(&bytes[..5], &[0x82, 0xa1, b'x', 0xc4, 32]);
+        assert_eq!(bytes[5], 0x08);
+        assert_eq!(
+            decode_value(&configuration, &ty, &bytes).unwrap(),
+            from_json
+        );
+
+        let mut response = vec![0x81, 0xa6];
+        response.extend(b"result");
+        response.extend(&bytes);
+        assert_eq!(
+            decode_response(&configuration, &ty, &response).unwrap(),
+            Response::Result(value)
+        );
+    }
+}
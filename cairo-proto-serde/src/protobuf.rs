@@ -0,0 +1,316 @@
+use crate::configuration::{Configuration, Field, FieldType, PrimitiveType};
+use serde_json::{json, Map, Value};
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+/// Encodes a JSON value into the protobuf binary format of `ty`.
+///
+/// The field numbers are read from the configuration, so the `Oracle.lock` must have been
+/// generated by a version of `scarb hints-generate` that records them.
+pub fn encode_message(
+    config: &Configuration,
+    ty: &FieldType,
+    value: &Value,
+) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let fields = message_fields(config, ty)?;
+    let value = value
+        .as_object()
+        .ok_or_else(|| format!("must be an object to encode as message {ty:?}"))?;
+    for field in fields {
+        let number = field_number(field)? as u64;
+        let Some(field_value) = value
+            .get(&field.name)
+            .or_else(|| value.get(field.name.trim_start_matches("felt252_")))
+        else {
+            continue;
+        };
+        encode_field(config, &field.ty, number, field_value, &mut buf)?;
+    }
+    Ok(buf)
+}
+
+/// Decodes the protobuf binary format of `ty` into a JSON value.
+///
+/// Fields missing on the wire are filled with their proto3 default values.
+pub fn decode_message(
+    config: &Configuration,
+    ty: &FieldType,
+    mut bytes: &[u8],
+) -> Result<Value, String> {
+    let fields = message_fields(config, ty)?;
+    let mut result = Map::new();
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes)?;
+        let number = (key >> 3) as i32;
+        let wire_type = (key & 0x7) as u8;
+        let Some(field) = fields.iter().find(|f| f.number == Some(number)) else {
+            skip_value(wire_type, &mut bytes)?;
+            continue;
+        };
+        match &field.ty {
+            FieldType::Array(inner) => {
+                let entry = result
+                    .entry(field.name.clone())
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                let Value::Array(items) = entry else {
+                    unreachable!("repeated fields are always arrays")
+                };
+                if wire_type == WIRE_LEN && is_varint(inner) {
+                    // packed repeated scalars
+                    let mut packed = read_len(&mut bytes)?;
+                    while !packed.is_empty() {
+                        items.push(decode_value(config, inner, WIRE_VARINT, &mut packed)?);
+                    }
+                } else {
+                    items.push(decode_value(config, inner, wire_type, &mut bytes)?);
+                }
+            }
+            FieldType::Option(inner) => {
+                let value = decode_value(config, inner, wire_type, &mut bytes)?;
+                result.insert(field.name.clone(), value);
+            }
+            ty => {
+                let value = decode_value(config, ty, wire_type, &mut bytes)?;
+                result.insert(field.name.clone(), value);
+            }
+        }
+    }
+    for field in fields {
+        if !result.contains_key(&field.name) {
+            result.insert(field.name.clone(), default_value(config, &field.ty)?);
+        }
+    }
+    Ok(Value::Object(result))
+}
+
+fn message_fields<'a>(config: &'a Configuration, ty: &FieldType) -> Result<&'a Vec<Field>, String> {
+    let FieldType::Message(message_ty) = ty else {
+        return Err(format!("{ty:?} is not a message type"));
+    };
+    config
+        .messages
+        .get(message_ty)
+        .ok_or_else(|| format!("Key `{message_ty}` not found in configuration JSON file"))
+}
+
+fn field_number(field: &Field) -> Result<i32, String> {
+    field.number.ok_or_else(|| {
+        format!(
+            "Field {} has no protobuf field number, regenerate the Oracle.lock with `scarb hints-generate`",
+            field.name
+        )
+    })
+}
+
+fn is_varint(ty: &FieldType) -> bool {
+    match ty {
+        FieldType::Primitive(PrimitiveType::BYTEARRAY | PrimitiveType::FELT252) => false,
+        FieldType::Primitive(_) | FieldType::Enum(_) => true,
+        _ => false,
+    }
+}
+
+fn encode_field(
+    config: &Configuration,
+    ty: &FieldType,
+    number: u64,
+    value: &Value,
+    buf: &mut Vec<u8>,
+) -> Result<(), String> {
+    match ty {
+        FieldType::Option(inner) => {
+            if !value.is_null() {
+                encode_field(config, inner, number, value, buf)?;
+            }
+        }
+        FieldType::Array(inner) => {
+            let values = value.as_array().ok_or("must be an array")?;
+            for element in values {
+                encode_field(config, inner, number, element, buf)?;
+            }
+        }
+        FieldType::Message(_) => {
+            let nested = encode_message(config, ty, value)?;
+            write_varint(number << 3 | WIRE_LEN as u64, buf);
+            write_varint(nested.len() as u64, buf);
+            buf.extend(nested);
+        }
+        FieldType::Primitive(PrimitiveType::BYTEARRAY | PrimitiveType::FELT252) => {
+            let s = value
+                .as_str()
+                .ok_or_else(|| format!("Error converting {value:?} to string"))?;
+            write_varint(number << 3 | WIRE_LEN as u64, buf);
+            write_varint(s.len() as u64, buf);
+            buf.extend(s.as_bytes());
+        }
+        FieldType::Primitive(PrimitiveType::BOOL) => {
+            let b = value
+                .as_bool()
+                .ok_or_else(|| format!("Error converting {value} to bool"))?;
+            write_varint(number << 3 | WIRE_VARINT as u64, buf);
+            write_varint(b as u64, buf);
+        }
+        FieldType::Primitive(PrimitiveType::U32 | PrimitiveType::U64) => {
+            let n = value
+                .as_u64()
+                .ok_or_else(|| format!("Error converting {value:?} to u64"))?;
+            write_varint(number << 3 | WIRE_VARINT as u64, buf);
+            write_varint(n, buf);
+        }
+        FieldType::Primitive(PrimitiveType::I32 | PrimitiveType::I64) | FieldType::Enum(_) => {
+            let n = value
+                .as_i64()
+                .ok_or_else(|| format!("Error converting {value:?} to i64"))?;
+            write_varint(number << 3 | WIRE_VARINT as u64, buf);
+            // negative values are sign extended to 64 bits, as for int32/int64
+            write_varint(n as u64, buf);
+        }
+    }
+    Ok(())
+}
+
+fn decode_value(
+    config: &Configuration,
+    ty: &FieldType,
+    wire_type: u8,
+    bytes: &mut &[u8],
+) -> Result<Value, String> {
+    let expected = if is_varint(ty) { WIRE_VARINT } else { WIRE_LEN };
+    if wire_type != expected {
+        return Err(format!(
+            "unexpected wire type {wire_type} for {ty:?}, expected {expected}"
+        ));
+    }
+    let value = match ty {
+        FieldType::Primitive(PrimitiveType::U32 | PrimitiveType::U64) => {
+            json!(read_varint(bytes)?)
+        }
+        FieldType::Primitive(PrimitiveType::I32) | FieldType::Enum(_) => {
+            json!(read_varint(bytes)? as i64 as i32)
+        }
+        FieldType::Primitive(PrimitiveType::I64) => json!(read_varint(bytes)? as i64),
+        FieldType::Primitive(PrimitiveType::BOOL) => json!(read_varint(bytes)? != 0),
+        FieldType::Primitive(PrimitiveType::BYTEARRAY | PrimitiveType::FELT252) => {
+            let data = read_len(bytes)?;
+            json!(String::from_utf8(data.to_vec()).map_err(|e| e.to_string())?)
+        }
+        FieldType::Message(_) => decode_message(config, ty, read_len(bytes)?)?,
+        FieldType::Option(_) | FieldType::Array(_) => {
+            return Err(format!("nested {ty:?} is not supported"))
+        }
+    };
+    Ok(value)
+}
+
+fn default_value(config: &Configuration, ty: &FieldType) -> Result<Value, String> {
+    let value = match ty {
+        FieldType::Primitive(PrimitiveType::BOOL) => json!(false),
+        FieldType::Primitive(PrimitiveType::BYTEARRAY) => json!(""),
+        FieldType::Primitive(PrimitiveType::FELT252) => json!("0"),
+        FieldType::Primitive(_) | FieldType::Enum(_) => json!(0),
+        FieldType::Message(_) => decode_message(config, ty, &[])?,
+        FieldType::Option(_) => Value::Null,
+        FieldType::Array(_) => Value::Array(Vec::new()),
+    };
+    Ok(value)
+}
+
+fn write_varint(mut n: u64, buf: &mut Vec<u8>) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut result = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("truncated varint")?;
+        *bytes = rest;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err("varint is too long".to_string())
+}
+
+fn read_len<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], String> {
+    let len = read_varint(bytes)? as usize;
+    if bytes.len() < len {
+        return Err("truncated length delimited field".to_string());
+    }
+    let (data, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(data)
+}
+
+fn skip_value(wire_type: u8, bytes: &mut &[u8]) -> Result<(), String> {
+    let len = match wire_type {
+        WIRE_VARINT => return read_varint(bytes).map(|_| ()),
+        WIRE_LEN => return read_len(bytes).map(|_| ()),
+        WIRE_FIXED64 => 8,
+        WIRE_FIXED32 => 4,
+        _ => return Err(format!("unsupported wire type {wire_type}")),
+    };
+    if bytes.len() < len {
+        return Err("truncated fixed width field".to_string());
+    }
+    *bytes = &bytes[len..];
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_message, encode_message};
+    use crate::configuration::{Configuration, Field, FieldType, PrimitiveType};
+    use serde_json::json;
+
+    fn field(name: &str, number: i32, ty: FieldType) -> Field {
+        Field {
+            name: name.into(),
+            ty,
+            number: Some(number),
+        }
+    }
+
+    #[test]
+    fn it_round_trips_protobuf() {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "Inner".into(),
+            vec![field("inner", 1, FieldType::Primitive(PrimitiveType::U32))],
+        );
+        configuration.messages.insert(
+            "Request".into(),
+            vec![
+                field("n", 1, FieldType::Primitive(PrimitiveType::U64)),
+                field(
+                    "x",
+                    2,
+                    FieldType::Option(Box::new(FieldType::Message("Inner".into()))),
+                ),
+                field(
+                    "y",
+                    3,
+                    FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::I32))),
+                ),
+                field("s", 4, FieldType::Primitive(PrimitiveType::BYTEARRAY)),
+            ],
+        );
+        let ty = FieldType::Message("Request".into());
+        let value = json!({ "n": 300, "x": { "inner": 7 }, "y": [-1, 18], "s": "hints" });
+
+        let bytes = encode_message(&configuration, &ty, &value).unwrap();
+        assert_eq!(&bytes[..3], &[0x08, 0xac, 0x02]);
+        assert_eq!(decode_message(&configuration, &ty, &bytes).unwrap(), value);
+
+        let defaults = decode_message(&configuration, &ty, &[]).unwrap();
+        assert_eq!(defaults, json!({ "n": 0, "x": null, "y": [], "s": "" }));
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::configuration::{Configuration, Field, FieldType, Mapping, PrimitiveType};
+
+/// Returns the name of `ty` as written in the Cairo bindings, such as `Array<u64>`.
+pub fn type_name(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Primitive(ty) => primitive_name(ty).to_string(),
+        FieldType::Message(name) | FieldType::Enum(name) => name.clone(),
+        FieldType::Option(ty) => format!("Option<{}>", type_name(ty)),
+        FieldType::Array(ty) => format!("Array<{}>", type_name(ty)),
+    }
+}
+
+fn primitive_name(ty: &PrimitiveType) -> &'static str {
+    match ty {
+        PrimitiveType::U64 => "u64",
+        PrimitiveType::U32 => "u32",
+        PrimitiveType::I32 => "i32",
+        PrimitiveType::I64 => "i64",
+        PrimitiveType::BOOL => "bool",
+        PrimitiveType::BYTEARRAY => "ByteArray",
+        PrimitiveType::FELT252 => "felt252",
+    }
+}
+
+/// Returns the JSON schema of the JSON representation of `ty`, with the messages and enums
+/// referenced from the `$defs` of [`json_schema`].
+pub fn type_schema(ty: &FieldType) -> Value {
+    match ty {
+        FieldType::Primitive(PrimitiveType::U64 | PrimitiveType::U32) => {
+            json!({ "type": "integer", "minimum": 0 })
+        }
+        FieldType::Primitive(PrimitiveType::I32 | PrimitiveType::I64) => {
+            json!({ "type": "integer" })
+        }
+        FieldType::Primitive(PrimitiveType::BOOL) => json!({ "type": "boolean" }),
+        FieldType::Primitive(PrimitiveType::BYTEARRAY) => json!({ "type": "string" }),
+        FieldType::Primitive(PrimitiveType::FELT252) => json!({
+            "type": "string",
+            "description": "felt252, as an unsigned decimal string less than the field prime",
+            "pattern": "^([0-9]+|0x[0-9a-fA-F]+)$",
+        }),
+        FieldType::Message(name) | FieldType::Enum(name) => {
+            json!({ "$ref": format!("#/$defs/{name}") })
+        }
+        FieldType::Option(ty) => json!({ "anyOf": [type_schema(ty), { "type": "null" }] }),
+        FieldType::Array(ty) => json!({ "type": "array", "items": type_schema(ty) }),
+    }
+}
+
+/// Returns a JSON schema document with the request and response of every oracle method, by
+/// service and method name.
+pub fn json_schema(configuration: &Configuration) -> Value {
+    let mut defs = Map::new();
+    for (name, fields) in &configuration.messages {
+        let properties = fields
+            .iter()
+            .map(|field| (field.name.clone(), type_schema(&field.ty)))
+            .collect::<Map<_, _>>();
+        // every field must be present, `null` being the value of an absent option
+        let required = fields
+            .iter()
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>();
+        defs.insert(
+            name.clone(),
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            }),
+        );
+    }
+    for (name, mappings) in &configuration.enums {
+        let values = mappings.iter().map(|m| m.nb).collect::<Vec<_>>();
+        let description = mappings
+            .iter()
+            .map(|m| format!("{} = {}", m.name, m.nb))
+            .collect::<Vec<_>>()
+            .join(", ");
+        defs.insert(
+            name.clone(),
+            json!({ "type": "integer", "enum": values, "description": description }),
+        );
+    }
+
+    let services = configuration
+        .services
+        .iter()
+        .map(|(service, methods)| {
+            let mut methods = methods.methods.iter().collect::<Vec<_>>();
+            methods.sort_by_key(|(name, _)| *name);
+            let methods = methods
+                .into_iter()
+                .map(|(name, method)| {
+                    let schema = json!({
+                        "request": type_schema(&method.input),
+                        "response": type_schema(&method.output),
+                    });
+                    (name.clone(), schema)
+                })
+                .collect::<Map<_, _>>();
+            (service.clone(), Value::Object(methods))
+        })
+        .collect::<Map<_, _>>();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "services": services,
+        "$defs": defs,
+    })
+}
+
+/// Returns the hash of the schema of `service`: the request and response of its methods, with the
+/// fields of the messages and the values of the enums they use. It is the hex SHA-256 of their
+/// JSON, sorted by name, so it only changes with the declarations of the service. `None` when the
+/// service isn't declared.
+pub fn schema_hash(configuration: &Configuration, service: &str) -> Option<String> {
+    let service = configuration.services.get(service)?;
+    let (mut messages, mut enums) = (BTreeMap::new(), BTreeMap::new());
+    let methods = service
+        .methods
+        .iter()
+        .map(|(name, method)| {
+            collect_types(configuration, &method.input, &mut messages, &mut enums);
+            collect_types(configuration, &method.output, &mut messages, &mut enums);
+            (
+                name,
+                json!({ "input": method.input, "output": method.output }),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    let schema = json!({ "methods": methods, "messages": messages, "enums": enums });
+    let digest = Sha256::digest(schema.to_string().as_bytes());
+    Some(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Adds the messages and enums used by `ty` to `messages` and `enums`.
+fn collect_types<'a>(
+    configuration: &'a Configuration,
+    ty: &'a FieldType,
+    messages: &mut BTreeMap<&'a str, &'a Vec<Field>>,
+    enums: &mut BTreeMap<&'a str, &'a Vec<Mapping>>,
+) {
+    match ty {
+        FieldType::Primitive(_) => {}
+        FieldType::Message(name) => {
+            let Some(fields) = configuration.messages.get(name) else {
+                return;
+            };
+            // a recursive message is only walked once
+            if messages.insert(name.as_str(), fields).is_none() {
+                for field in fields {
+                    collect_types(configuration, &field.ty, messages, enums);
+                }
+            }
+        }
+        FieldType::Enum(name) => {
+            if let Some(mappings) = configuration.enums.get(name) {
+                enums.insert(name.as_str(), mappings);
+            }
+        }
+        FieldType::Option(inner) | FieldType::Array(inner) => {
+            collect_types(configuration, inner, messages, enums)
+        }
+    }
+}
+
+/// Checks that `value` has the JSON representation of `ty` expected by `serialize_cairo_serde`,
+/// which panics otherwise. The error names the offending field, such as `items[2].price`.
+pub fn validate(
+    configuration: &Configuration,
+    ty: &FieldType,
+    value: &Value,
+) -> Result<(), String> {
+    validate_at(configuration, ty, value, "")
+}
+
+fn validate_at(
+    configuration: &Configuration,
+    ty: &FieldType,
+    value: &Value,
+    path: &str,
+) -> Result<(), String> {
+    let mismatch = || {
+        let at = if path.is_empty() {
+            String::new()
+        } else {
+            format!("`{path}`: ")
+        };
+        Err(format!("{at}expected {}, got {value}", type_name(ty)))
+    };
+    let valid = match ty {
+        FieldType::Primitive(PrimitiveType::U64) => value.as_u64().is_some(),
+        FieldType::Primitive(PrimitiveType::U32) => {
+            value.as_u64().is_some_and(|n| u32::try_from(n).is_ok())
+        }
+        FieldType::Primitive(PrimitiveType::I64) => value.as_i64().is_some(),
+        FieldType::Primitive(PrimitiveType::I32) => {
+            value.as_i64().is_some_and(|n| i32::try_from(n).is_ok())
+        }
+        FieldType::Primitive(PrimitiveType::BOOL) => value.is_boolean(),
+        FieldType::Primitive(PrimitiveType::BYTEARRAY) => value.is_string(),
+        FieldType::Primitive(PrimitiveType::FELT252) => match value.as_str() {
+            Some(s) => {
+                return crate::felt_from_str(s).map(|_| ()).map_err(|e| match path {
+                    "" => e,
+                    path => format!("`{path}`: {e}"),
+                })
+            }
+            None => false,
+        },
+        FieldType::Enum(name) => value.as_i64().is_some_and(|n| {
+            configuration.enums.get(name).map_or(true, |mappings| {
+                mappings.iter().any(|m| i64::from(m.nb) == n)
+            })
+        }),
+        FieldType::Option(_) if value.is_null() => true,
+        FieldType::Option(ty) => return validate_at(configuration, ty, value, path),
+        FieldType::Array(ty) => match value.as_array() {
+            Some(elements) => {
+                for (i, element) in elements.iter().enumerate() {
+                    validate_at(configuration, ty, element, &format!("{path}[{i}]"))?;
+                }
+                true
+            }
+            None => false,
+        },
+        FieldType::Message(name) => match value.as_object() {
+            Some(object) => {
+                let fields = configuration
+                    .messages
+                    .get(name)
+                    .ok_or_else(|| format!("message {name} is not in the Oracle.lock"))?;
+                for field in fields {
+                    let path = match path {
+                        "" => field.name.clone(),
+                        path => format!("{path}.{}", field.name),
+                    };
+                    let field_value = object
+                        .get(&field.name)
+                        .or_else(|| object.get(field.name.trim_start_matches("felt252_")))
+                        .ok_or_else(|| format!("`{path}`: missing {}", type_name(&field.ty)))?;
+                    validate_at(configuration, &field.ty, field_value, &path)?;
+                }
+                true
+            }
+            None => false,
+        },
+    };
+    if valid {
+        Ok(())
+    } else {
+        mismatch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_schema, schema_hash, type_name, validate};
+    use crate::configuration::{
+        Configuration, Field, FieldType, MethodDeclaration, PrimitiveType, Service,
+    };
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_describes_the_request_and_response_of_each_method() {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "oracle::Request".into(),
+            vec![Field {
+                name: "n".into(),
+                ty: FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::U64))),
+                number: Some(1),
+            }],
+        );
+        configuration.services.insert(
+            "SqrtOracle".into(),
+            Service {
+                methods: HashMap::from([(
+                    "sqrt".into(),
+                    MethodDeclaration {
+                        input: FieldType::Message("oracle::Request".into()),
+                        output: FieldType::Option(Box::new(FieldType::Primitive(
+                            PrimitiveType::FELT252,
+                        ))),
+                        grpc_path: None,
+                    },
+                )]),
+            },
+        );
+
+        let schema = json_schema(&configuration);
+        assert_eq!(
+            schema["services"]["SqrtOracle"]["sqrt"]["request"],
+            json!({ "$ref": "#/$defs/oracle::Request" })
+        );
+        assert_eq!(
+            schema["$defs"]["oracle::Request"]["properties"]["n"],
+            json!({ "type": "array", "items": { "type": "integer", "minimum": 0 } })
+        );
+        assert_eq!(
+            type_name(&configuration.services["SqrtOracle"].methods["sqrt"].output),
+            "Option<felt252>"
+        );
+    }
+
+    #[test]
+    fn it_hashes_the_declarations_used_by_a_service() {
+        let mut configuration = Configuration::default();
+        let request = |ty| {
+            vec![Field {
+                name: "n".into(),
+                ty: FieldType::Primitive(ty),
+                number: Some(1),
+            }]
+        };
+        configuration
+            .messages
+            .insert("oracle::Request".into(), request(PrimitiveType::U64));
+        configuration.services.insert(
+            "SqrtOracle".into(),
+            Service {
+                methods: HashMap::from([(
+                    "sqrt".into(),
+                    MethodDeclaration {
+                        input: FieldType::Message("oracle::Request".into()),
+                        output: FieldType::Message("oracle::Request".into()),
+                        grpc_path: None,
+                    },
+                )]),
+            },
+        );
+        let hash = schema_hash(&configuration, "SqrtOracle").unwrap();
+        assert_eq!(hash.len(), 64);
+
+        // a message the service doesn't use leaves its hash unchanged
+        configuration
+            .messages
+            .insert("oracle::Other".into(), request(PrimitiveType::BOOL));
+        assert_eq!(schema_hash(&configuration, "SqrtOracle").unwrap(), hash);
+
+        configuration
+            .messages
+            .insert("oracle::Request".into(), request(PrimitiveType::U32));
+        assert_ne!(schema_hash(&configuration, "SqrtOracle").unwrap(), hash);
+        assert!(schema_hash(&configuration, "CbrtOracle").is_none());
+    }
+
+    #[test]
+    fn it_names_the_invalid_field_of_a_value() {
+        let mut configuration = Configuration::default();
+        configuration.messages.insert(
+            "oracle::Response".into(),
+            vec![Field {
+                name: "n".into(),
+                ty: FieldType::Array(Box::new(FieldType::Primitive(PrimitiveType::U64))),
+                number: Some(1),
+            }],
+        );
+        let ty = FieldType::Message("oracle::Response".into());
+
+        assert!(validate(&configuration, &ty, &json!({ "n": [1, 2] })).is_ok());
+        assert_eq!(
+            validate(&configuration, &ty, &json!({})).unwrap_err(),
+            "`n`: missing Array<u64>"
+        );
+        assert_eq!(
+            validate(&configuration, &ty, &json!({ "n": [1, "2"] })).unwrap_err(),
+            "`n[1]`: expected u64, got \"2\""
+        );
+
+        let felt = FieldType::Primitive(PrimitiveType::FELT252);
+        assert!(validate(&configuration, &felt, &json!("0x2a")).is_ok());
+        assert!(validate(&configuration, &felt, &json!("-1"))
+            .unwrap_err()
+            .contains("unsigned"));
+    }
+}
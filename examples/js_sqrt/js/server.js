@@ -1,7 +1,7 @@
 const express = require('express');
 const app = express();
 const hostname = '127.0.0.1';
-const port = 3000;
+const port = process.env.PORT || 3000;
 
 app.use(express.json());
 
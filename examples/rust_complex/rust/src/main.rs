@@ -20,6 +20,30 @@ async fn root(extract::Json(payload): extract::Json<Request>) -> Json<JsonResult
     })
 }
 
+/// Address the server listens on: `--addr`, else `BIND_ADDR`, else port `PORT` of all the
+/// interfaces, else `0.0.0.0:3000`.
+fn bind_addr() -> String {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--addr" {
+            return args
+                .next()
+                .expect("--addr takes an address, such as 127.0.0.1:3001");
+        }
+        if let Some(addr) = arg.strip_prefix("--addr=") {
+            return addr.to_string();
+        }
+    }
+    std::env::var("BIND_ADDR")
+        .ok()
+        .or_else(|| {
+            std::env::var("PORT")
+                .ok()
+                .map(|port| format!("0.0.0.0:{port}"))
+        })
+        .unwrap_or_else(|| "0.0.0.0:3000".to_string())
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     tracing_subscriber::fmt()
@@ -30,10 +54,11 @@ async fn main() {
         .route("/shirt", post(root))
         .layer(TraceLayer::new_for_http());
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+    let addr = bind_addr();
+    let listener = tokio::net::TcpListener::bind(&addr)
         .await
-        .expect("Failed to bind to port 3000, port already in use by another process. Change the port or terminate the other process.");
+        .unwrap_or_else(|e| panic!("Failed to bind to {addr}: {e}. If the port is used by another process, terminate it or pass another address with --addr, BIND_ADDR or PORT."));
 
-    debug!("Server started on http://0.0.0.0:3000");
+    debug!("Server started on http://{addr}");
     axum::serve(listener, app).await.unwrap();
 }
@@ -14,8 +14,9 @@ mod request {
 struct Response {
     color: super::shirts::Size,
 }
-#[derive(Drop, Serde, PartialEq)]
+#[derive(Drop, Copy, Serde, PartialEq, Default)]
 enum Size {
+    #[default]
     Small,
     Medium,
     Large,